@@ -0,0 +1,190 @@
+#!/usr/bin/env python3
+"""
+Capture the BLE frames pypixelcolor writes for each panel command, the golden
+vectors of the native Rust encoder (src-tauri/src/ipixel/codec.rs).
+
+No panel is needed: bleak is replaced by a client recording the writes and
+answering the device info query. The clock is frozen at FROZEN_NOW, the Rust
+test expects that date and time in the clock frames.
+
+    pip install -r requirements.txt
+    python capture_reference_frames.py ../src-tauri/tests/fixtures/pypixelcolor_frames.json
+"""
+import asyncio
+import datetime
+import json
+import sys
+import time
+from importlib.metadata import version
+from pathlib import Path
+
+import bleak
+
+FROZEN_NOW = datetime.datetime(2025, 3, 14, 12, 30, 45)
+# Answer to the set_time query: device type 0x82 (32x32), MCU 1.0, LED type 3, no Wi-Fi
+DEVICE_INFO_NOTIFICATION = bytes([0x09, 0x00, 0x01, 0x80, 0x82, 0x01, 0x00, 0x03, 0x00])
+SET_TIME_COMMAND = bytes([0x01, 0x80])
+
+# pypixelcolor calls to record, with the arguments named as in the Rust test
+CALLS = [
+    ("set_brightness", {"brightness": 0}),
+    ("set_brightness", {"brightness": 50}),
+    ("set_brightness", {"brightness": 100}),
+    ("set_orientation", {"orientation": 0}),
+    ("set_orientation", {"orientation": 3}),
+    ("set_power", {"on": True}),
+    ("set_power", {"on": False}),
+    ("set_fun_mode", {"enable": True}),
+    ("set_fun_mode", {"enable": False}),
+    ("set_pixel", {"x": 0, "y": 0, "color": "FF0000"}),
+    ("set_pixel", {"x": 31, "y": 15, "color": "123456"}),
+    ("set_clock_mode", {"style": 1, "format_24": True, "show_date": True}),
+    ("set_clock_mode", {"style": 8, "format_24": False, "show_date": False}),
+    ("set_rhythm_mode", {"style": 2, "levels": [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 15]}),
+    ("set_rhythm_mode_2", {"style": 1, "time": 7}),
+]
+
+
+class FrozenDateTime(datetime.datetime):
+    @classmethod
+    def now(cls, tz=None):
+        return FROZEN_NOW.replace(tzinfo=tz)
+
+    @classmethod
+    def today(cls):
+        return FROZEN_NOW
+
+
+real_localtime = time.localtime
+
+
+def frozen_localtime(seconds=None):
+    if seconds is None:
+        return FROZEN_NOW.timetuple()
+    return real_localtime(seconds)
+
+
+class RecordingClient:
+    """Stand-in for bleak.BleakClient, keeping what is written"""
+
+    frames: list = []
+
+    def __init__(self, address_or_device, *args, **kwargs):
+        self.address = getattr(address_or_device, "address", address_or_device)
+        self.callbacks = []
+
+    async def __aenter__(self):
+        await self.connect()
+        return self
+
+    async def __aexit__(self, *exc):
+        await self.disconnect()
+
+    @property
+    def is_connected(self):
+        return True
+
+    async def connect(self, **kwargs):
+        return True
+
+    async def disconnect(self):
+        return True
+
+    async def start_notify(self, characteristic, callback, **kwargs):
+        self.callbacks.append(callback)
+
+    async def stop_notify(self, characteristic):
+        pass
+
+    async def write_gatt_char(self, characteristic, data, response=None):
+        data = bytes(data)
+        RecordingClient.frames.append(data)
+        if data[2:4] == SET_TIME_COMMAND:
+            for callback in self.callbacks:
+                result = callback(characteristic, bytearray(DEVICE_INFO_NOTIFICATION))
+                if asyncio.iscoroutine(result):
+                    await result
+
+
+class Device:
+    def __init__(self, address):
+        self.address = address
+        self.name = "LED_BLE_CAPTURE"
+        self.details = None
+
+
+async def find_device_by_address(address, *args, **kwargs):
+    return Device(address)
+
+
+def install_fakes():
+    datetime.datetime = FrozenDateTime
+    time.localtime = frozen_localtime
+    bleak.BleakClient = RecordingClient
+    bleak.BleakScanner.find_device_by_address = staticmethod(find_device_by_address)
+
+
+def call_pypixelcolor(client, call, args):
+    """Call pypixelcolor the way led_controller.py does"""
+    method = getattr(client, call)
+    if call in ("set_brightness", "set_orientation"):
+        return method(*args.values())
+    if call == "set_pixel":
+        return method(args["x"], args["y"], args["color"])
+    if call == "set_rhythm_mode":
+        levels = {f"l{index}": level for index, level in enumerate(args["levels"], 1)}
+        return method(style=args["style"], **levels)
+    if call == "set_rhythm_mode_2":
+        return method(style=args["style"], t=args["time"])
+    return method(**args)
+
+
+def as_hex(frame):
+    return " ".join(f"{byte:02x}" for byte in frame)
+
+
+async def capture():
+    install_fakes()
+    # Imported after the fakes, in case it binds them at import time
+    from pypixelcolor import AsyncClient
+
+    for module in [m for name, m in sys.modules.items() if name.startswith("pypixelcolor")]:
+        if hasattr(module, "BleakClient"):
+            module.BleakClient = RecordingClient
+
+    client = AsyncClient("AA:BB:CC:DD:EE:FF")
+    await client.connect()
+    # Connecting sets the clock, which answers with the device info
+    calls = [{
+        "call": "connect",
+        "args": {},
+        "frames": [as_hex(frame) for frame in RecordingClient.frames],
+    }]
+
+    for call, args in CALLS:
+        RecordingClient.frames.clear()
+        await call_pypixelcolor(client, call, args)
+        calls.append({
+            "call": call,
+            "args": args,
+            "frames": [as_hex(frame) for frame in RecordingClient.frames],
+        })
+
+    await client.disconnect()
+    return {
+        "generator": f"pypixelcolor {version('pypixelcolor')}",
+        "now": FROZEN_NOW.isoformat(),
+        "calls": calls,
+    }
+
+
+def main():
+    output = json.dumps(asyncio.run(capture()), indent=2) + "\n"
+    if len(sys.argv) > 1:
+        Path(sys.argv[1]).write_text(output)
+    else:
+        sys.stdout.write(output)
+
+
+if __name__ == "__main__":
+    main()
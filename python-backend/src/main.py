@@ -4,6 +4,7 @@ This server wraps the pypixelcolor library and exposes it via REST API
 """
 
 import asyncio
+import hmac
 import logging
 import tempfile
 import os
@@ -11,7 +12,8 @@ from typing import List, Optional
 from contextlib import asynccontextmanager
 from pathlib import Path
 
-from fastapi import FastAPI, WebSocket, WebSocketDisconnect, UploadFile, File, HTTPException
+from fastapi import FastAPI, WebSocket, WebSocketDisconnect, UploadFile, File, HTTPException, Request, status
+from fastapi.responses import JSONResponse
 from fastapi.middleware.cors import CORSMiddleware
 from pydantic import BaseModel
 
@@ -107,6 +109,9 @@ class AppState:
 
 app_state = AppState()
 
+# Uvicorn server instance, set when running as a script so /shutdown can stop it
+server = None
+
 
 @asynccontextmanager
 async def lifespan(app: FastAPI):
@@ -141,13 +146,64 @@ app.add_middleware(
 )
 
 
+# Session token set by the desktop app, see BACKEND_TOKEN_ENV in src-tauri/src/lib.rs.
+# Every request must carry it, the health check too: it tells the app that the
+# backend on the port is the one it started. Left unset when the backend is
+# started by hand for development, which disables the check.
+BACKEND_TOKEN = os.environ.get("PIXELART_BACKEND_TOKEN") or None
+
+
+def token_matches(token: Optional[str]) -> bool:
+    if BACKEND_TOKEN is None:
+        return True
+    return token is not None and hmac.compare_digest(token.encode(), BACKEND_TOKEN.encode())
+
+
+@app.middleware("http")
+async def require_token(request: Request, call_next):
+    # CORS preflights never carry credentials
+    if request.method == "OPTIONS":
+        return await call_next(request)
+
+    authorization = request.headers.get("authorization", "")
+    token = authorization[len("Bearer "):] if authorization.startswith("Bearer ") else None
+    if not token_matches(token):
+        return JSONResponse(status_code=status.HTTP_401_UNAUTHORIZED, content={"detail": "Missing or invalid backend token"})
+    return await call_next(request)
+
+
+# Version of the HTTP API, checked by the desktop app at startup.
+# Bump it together with API_VERSION in src-tauri/src/panel/mod.rs on breaking changes.
+API_VERSION = 1
+
+# Features announced to the desktop app, see Capability in src-tauri/src/panel/types.rs
+CAPABILITIES = [
+    "text",
+    "image",
+    "gif",
+    "brightness",
+    "orientation",
+    "device_info",
+    "clock_mode",
+    "rhythm_mode",
+    "rhythm_mode2",
+    "diy_mode",
+    "pixel_batch",
+    "power",
+    "shutdown",
+    "status_websocket",
+]
+
+
 @app.get("/")
 async def root():
     """Health check endpoint"""
     return {
         "status": "ok",
         "message": "LED Panel Control Backend is running",
-        "version": "1.0.0"
+        "version": "1.0.0",
+        "api_version": API_VERSION,
+        "capabilities": CAPABILITIES
     }
 
 
@@ -547,6 +603,22 @@ async def send_pixels(data: PixelData):
         raise HTTPException(status_code=500, detail=f"Failed to send pixels: {str(e)}")
 
 
+@app.post("/shutdown")
+async def shutdown():
+    """
+    Gracefully stop the backend
+
+    Called by the desktop app on exit. The BLE connection is closed by the
+    lifespan handler once uvicorn stops serving.
+    """
+    if server is None:
+        raise HTTPException(status_code=501, detail="Shutdown is not supported in this mode")
+
+    logger.info("Shutdown requested")
+    server.should_exit = True
+    return {"status": "shutting_down"}
+
+
 async def broadcast_status():
     """
     Broadcast device status to all connected WebSocket clients
@@ -579,6 +651,11 @@ async def websocket_endpoint(websocket: WebSocket):
     """
     WebSocket endpoint for real-time device status updates
     """
+    # Browsers can't set headers on a WebSocket, the token comes in the query
+    if not token_matches(websocket.query_params.get("token")):
+        await websocket.close(code=status.WS_1008_POLICY_VIOLATION)
+        return
+
     await websocket.accept()
     app_state.websocket_connections.append(websocket)
 
@@ -603,10 +680,23 @@ async def websocket_endpoint(websocket: WebSocket):
 
 
 if __name__ == "__main__":
+    import argparse
     import uvicorn
-    uvicorn.run(
+
+    # The Tauri shell picks a free port and passes it via --port or PIXELART_BACKEND_PORT
+    parser = argparse.ArgumentParser(description="LED Panel Control Backend")
+    parser.add_argument(
+        "--port",
+        type=int,
+        default=int(os.environ.get("PIXELART_BACKEND_PORT", "8000")),
+        help="Port to listen on (default: $PIXELART_BACKEND_PORT or 8000)"
+    )
+    args = parser.parse_args()
+
+    server = uvicorn.Server(uvicorn.Config(
         app,
         host="127.0.0.1",
-        port=8000,
+        port=args.port,
         log_level="info"
-    )
+    ))
+    server.run()
@@ -0,0 +1,227 @@
+//! Lock file recording the running backend.
+//!
+//! The backend outlives the app when the app crashes, and would keep its port
+//! and the BLE link. The lock file written at spawn time lets the next launch
+//! find that backend and stop it before starting a new one. The command line
+//! interface also uses it to reach the backend of the running app.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::panel::types::HealthResponse;
+use crate::panel::{bearer, API_VERSION};
+use crate::process_tree;
+use crate::startup_error::StartupError;
+
+const LOCK_FILE_NAME: &str = "backend.lock";
+/// Timeout of the health check telling whether a leftover backend is ours
+const STALE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Backend recorded in the lock file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub port: u16,
+    /// Version of the app that started it
+    pub app_version: String,
+    /// Session token the backend requires
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+pub struct BackendLock {
+    path: PathBuf,
+}
+
+impl BackendLock {
+    pub fn new(dir: &Path) -> Self {
+        let _ = fs::create_dir_all(dir);
+        Self {
+            path: dir.join(LOCK_FILE_NAME),
+        }
+    }
+
+    /// Record the backend that was just spawned. The file holds the token,
+    /// only the user may read it.
+    pub fn write(&self, pid: u32, port: u16, token: &str) -> io::Result<()> {
+        let info = LockInfo {
+            pid,
+            port,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            token: Some(token.to_string()),
+        };
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            // The mode only applies to new files
+            if self.path.exists() {
+                fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        options.open(&self.path)?.write_all(&serde_json::to_vec(&info)?)
+    }
+
+    pub fn read(&self) -> Option<LockInfo> {
+        let content = fs::read(&self.path).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    pub fn remove(&self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!(error = %e, path = %self.path.display(), "Failed to remove the backend lock file");
+            }
+        }
+    }
+
+    /// Stop the backend left behind by a previous session, if any.
+    ///
+    /// A recorded process is only stopped while its port answers the health
+    /// check with the recorded session token. After a reboot, its pid and port
+    /// may both belong to unrelated programs.
+    pub fn reclaim_stale(&self) -> Result<(), StartupError> {
+        let Ok(content) = fs::read(&self.path) else {
+            return Ok(());
+        };
+        let Ok(stale) = serde_json::from_slice::<LockInfo>(&content) else {
+            warn!(path = %self.path.display(), "Removing an unreadable backend lock file");
+            self.remove();
+            return Ok(());
+        };
+
+        if !process_tree::is_running(stale.pid) {
+            info!(pid = stale.pid, "Removing outdated backend lock file");
+        } else if is_our_backend(&stale) {
+            warn!(
+                pid = stale.pid,
+                port = stale.port,
+                app_version = %stale.app_version,
+                "Stopping a backend left behind by a previous session"
+            );
+            process_tree::kill_group(stale.pid).map_err(|source| StartupError::StaleBackend {
+                pid: stale.pid,
+                source,
+            })?;
+        } else {
+            warn!(
+                pid = stale.pid,
+                port = stale.port,
+                "Port of the previous backend is taken by another program, leaving it alone"
+            );
+        }
+
+        self.remove();
+        Ok(())
+    }
+}
+
+/// Whether the recorded port answers like the backend started with the
+/// recorded token
+fn is_our_backend(stale: &LockInfo) -> bool {
+    let request = ureq::AgentBuilder::new()
+        .timeout(STALE_CHECK_TIMEOUT)
+        .build()
+        .get(&format!("http://127.0.0.1:{}/", stale.port));
+    let request = match &stale.token {
+        Some(token) => request.set("Authorization", &bearer(token)),
+        None => request,
+    };
+    request
+        .call()
+        .ok()
+        .and_then(|response| response.into_json::<HealthResponse>().ok())
+        .is_some_and(|health| health.api_version == Some(API_VERSION))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::process::{Command, Stdio};
+
+    use super::*;
+
+    fn lock_in(name: &str) -> (BackendLock, PathBuf) {
+        let dir = std::env::temp_dir()
+            .join(format!("backend-lock-test-{}-{}", name, std::process::id()));
+        (BackendLock::new(&dir), dir)
+    }
+
+    /// Port nothing listens on
+    fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    /// Port of a server answering every request with `body`, like a program
+    /// other than the backend
+    fn serve(body: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().filter_map(Result::ok) {
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                    line.clear();
+                }
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn removes_the_lock_of_a_dead_backend() {
+        let (lock, dir) = lock_in("dead");
+        let mut child = Command::new("rustc")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        lock.write(pid, closed_port(), "token").unwrap();
+        lock.reclaim_stale().unwrap();
+        assert!(lock.read().is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn leaves_unrelated_processes_alone() {
+        // This test process stands for a program that got the recorded pid
+        let pid = std::process::id();
+        let (lock, dir) = lock_in("unrelated");
+        for port in [closed_port(), serve(r#"{"status": "ok"}"#)] {
+            lock.write(pid, port, "token").unwrap();
+            lock.reclaim_stale().unwrap();
+            assert!(lock.read().is_none());
+        }
+        assert!(process_tree::is_running(pid));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn removes_unreadable_lock_files() {
+        let (lock, dir) = lock_in("garbage");
+        fs::write(dir.join(LOCK_FILE_NAME), "not a lock file").unwrap();
+        lock.reclaim_stale().unwrap();
+        assert!(!dir.join(LOCK_FILE_NAME).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
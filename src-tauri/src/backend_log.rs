@@ -0,0 +1,223 @@
+//! Capture of the backend's stdout/stderr.
+//!
+//! Every line is tagged with a timestamp and the stream it came from, appended
+//! to a rotating log file in the app log directory and forwarded to the
+//! frontend as a `backend://log` event.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::Mutex;
+
+use chrono::{Local, SecondsFormat};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{error, info, warn};
+
+/// Name of the active log file in the app log directory
+const LOG_FILE_NAME: &str = "backend.log";
+/// Size at which the active log file is rotated
+const MAX_LOG_FILE_SIZE: u64 = 5 * 1024 * 1024;
+/// Number of rotated files kept next to the active one
+const MAX_ROTATED_FILES: u32 = 3;
+/// Number of recent lines kept in memory for the log viewer
+const RECENT_LINES_CAPACITY: usize = 500;
+
+/// One line of backend output, as emitted to the frontend
+#[derive(Clone, Serialize)]
+pub struct BackendLogLine {
+    pub timestamp: String,
+    pub stream: &'static str,
+    pub line: String,
+}
+
+/// Log file that is rotated once it grows past `max_size`.
+///
+/// `backend.log` is renamed to `backend.log.1`, `backend.log.1` to
+/// `backend.log.2` and so on, dropping the oldest file.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: u32,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: u32) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+
+        Ok(())
+    }
+}
+
+/// Shared sink for backend output, managed as Tauri state
+pub struct BackendLog {
+    file: Mutex<Option<RotatingFile>>,
+    recent: Mutex<VecDeque<BackendLogLine>>,
+}
+
+impl BackendLog {
+    /// Open the backend log in `log_dir`, falling back to in-memory only
+    /// logging if the file can't be created.
+    pub fn open(log_dir: &Path) -> Self {
+        let path = log_dir.join(LOG_FILE_NAME);
+        let file = match RotatingFile::open(path.clone(), MAX_LOG_FILE_SIZE, MAX_ROTATED_FILES) {
+            Ok(file) => {
+                info!(path = %path.display(), "Backend logs are written to file");
+                Some(file)
+            }
+            Err(e) => {
+                error!(path = %path.display(), error = %e, "Failed to open backend log file");
+                None
+            }
+        };
+
+        Self {
+            file: Mutex::new(file),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_LINES_CAPACITY)),
+        }
+    }
+
+    /// Most recent lines, oldest first
+    pub fn recent_lines(&self) -> Vec<BackendLogLine> {
+        self.recent
+            .lock()
+            .map(|recent| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, entry: &BackendLogLine) {
+        let formatted = format!("{} [{}] {}", entry.timestamp, entry.stream, entry.line);
+
+        // Keep the output visible in the app log and terminal during development
+        info!(target: "backend", stream = entry.stream, "{}", entry.line);
+
+        if let Ok(mut file_opt) = self.file.lock() {
+            if let Some(file) = file_opt.as_mut() {
+                if let Err(e) = file.write_line(&formatted) {
+                    warn!(error = %e, "Failed to write backend log, disabling file logging");
+                    file_opt.take();
+                }
+            }
+        }
+
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_LINES_CAPACITY {
+                recent.pop_front();
+            }
+            recent.push_back(entry.clone());
+        }
+    }
+}
+
+/// Forward every line read from `reader` to the backend log and the frontend
+fn forward_lines<R: Runtime>(app: AppHandle<R>, stream: &'static str, reader: impl Read + Send + 'static) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let entry = BackendLogLine {
+                timestamp: Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+                stream,
+                line,
+            };
+
+            app.state::<BackendLog>().record(&entry);
+            let _ = app.emit("backend://log", entry);
+        }
+    });
+}
+
+/// Start capturing the piped stdout/stderr of a freshly spawned backend
+pub fn capture<R: Runtime>(app: &AppHandle<R>, child: &mut Child) {
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(app.clone(), "stdout", stdout);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(app.clone(), "stderr", stderr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_past_the_size_limit() {
+        let dir = std::env::temp_dir().join(format!("backend-log-test-{}", std::process::id()));
+        let path = dir.join(LOG_FILE_NAME);
+        // Two 9 byte lines per file, keeping two rotated files
+        let mut file = RotatingFile::open(path.clone(), 20, 2).unwrap();
+        for index in 0..8 {
+            file.write_line(&format!("line {:03}", index)).unwrap();
+        }
+
+        let read = |path: &Path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "line 006\nline 007\n");
+        assert_eq!(read(&file.rotated_path(1)), "line 004\nline 005\n");
+        assert_eq!(read(&file.rotated_path(2)), "line 002\nline 003\n");
+        assert!(!file.rotated_path(3).exists());
+
+        // Reopening continues the active file from its current size
+        let mut file = RotatingFile::open(path.clone(), 20, 2).unwrap();
+        file.write_line("line 008").unwrap();
+        assert_eq!(read(&path), "line 008\n");
+        assert_eq!(read(&file.rotated_path(2)), "line 004\nline 005\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,365 @@
+//! Color calibration of the panels, saved per device.
+//!
+//! Every panel command goes through a [`CalibratedPanel`], which applies the
+//! [`ColorProfile`] of the connected panel to the colors it sends: text color
+//! and pixels. It also hands the profile to the conversion of images and
+//! animations, which applies it before reducing the colors. A panel without a profile of
+//! its own starts from the last one saved for its LED type, and gets its
+//! colors unchanged until one is.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::warn;
+
+use crate::imaging::{self, ColorProfile, ColorTransform, TestPattern};
+use crate::ipixel::protocol::Rgb;
+use crate::panel::commands::uncalibrated_api;
+use crate::panel::types::*;
+use crate::panel::{ApiError, PanelApi};
+
+const CALIBRATION_FILE_NAME: &str = "calibration.json";
+/// Color the backend shows text in when none is given
+const DEFAULT_TEXT_COLOR: Rgb = Rgb(255, 255, 255);
+
+/// Where the profile of a panel comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileSource {
+    /// Saved for this panel
+    Device,
+    /// Saved for another panel with the same LED type
+    LedType,
+    /// Never calibrated, colors are sent unchanged
+    Default,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Profiles {
+    /// By device address
+    #[serde(default)]
+    devices: BTreeMap<String, ColorProfile>,
+    /// Last profile saved for each LED type
+    #[serde(default)]
+    led_types: BTreeMap<u32, ColorProfile>,
+}
+
+/// Color profiles saved in the app data directory
+#[derive(Clone)]
+pub struct CalibrationStore {
+    path: PathBuf,
+}
+
+impl CalibrationStore {
+    pub fn new(dir: &Path) -> Self {
+        let _ = fs::create_dir_all(dir);
+        Self {
+            path: dir.join(CALIBRATION_FILE_NAME),
+        }
+    }
+
+    fn read(&self) -> Profiles {
+        let Ok(json) = fs::read_to_string(&self.path) else {
+            return Profiles::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            warn!(error = %e, path = %self.path.display(), "Ignoring invalid color profiles");
+            Profiles::default()
+        })
+    }
+
+    fn write(&self, profiles: &Profiles) -> Result<(), ApiError> {
+        let json =
+            serde_json::to_string_pretty(profiles).map_err(|e| ApiError::Storage(e.to_string()))?;
+        fs::write(&self.path, json).map_err(|e| ApiError::Storage(e.to_string()))
+    }
+
+    /// Profile of a panel and where it comes from
+    pub fn profile(&self, address: &str, led_type: u32) -> (ColorProfile, ProfileSource) {
+        let profiles = self.read();
+        if let Some(profile) = profiles.devices.get(address) {
+            return (*profile, ProfileSource::Device);
+        }
+        match profiles.led_types.get(&led_type) {
+            Some(profile) => (*profile, ProfileSource::LedType),
+            None => (ColorProfile::default(), ProfileSource::Default),
+        }
+    }
+
+    /// Save the profile of a panel, also the starting point of the panels of
+    /// the same LED type
+    pub fn save(
+        &self,
+        address: &str,
+        led_type: u32,
+        profile: ColorProfile,
+    ) -> Result<(), ApiError> {
+        let mut profiles = self.read();
+        profiles.devices.insert(address.to_string(), profile);
+        profiles.led_types.insert(led_type, profile);
+        self.write(&profiles)
+    }
+
+    /// Forget the profile of a panel
+    pub fn remove(&self, address: &str) -> Result<(), ApiError> {
+        let mut profiles = self.read();
+        if profiles.devices.remove(address).is_some() {
+            self.write(&profiles)?;
+        }
+        Ok(())
+    }
+}
+
+/// Calibration of the connected panel
+#[derive(Debug, Clone, Serialize)]
+pub struct PanelCalibration {
+    pub address: String,
+    pub led_type: u32,
+    pub profile: ColorProfile,
+    pub source: ProfileSource,
+}
+
+/// Address of the connected panel
+fn connected_address(api: &dyn PanelApi) -> Result<String, ApiError> {
+    let status = api.status()?;
+    match status.device_address {
+        Some(address) if status.connected => Ok(address),
+        _ => Err(ApiError::NotConnected),
+    }
+}
+
+/// Address and LED type of the connected panel
+fn connected_panel(api: &dyn PanelApi) -> Result<(String, u32), ApiError> {
+    let address = connected_address(api)?;
+    Ok((address, api.device_info()?.led_type))
+}
+
+fn calibration(api: &dyn PanelApi, store: &CalibrationStore) -> Result<PanelCalibration, ApiError> {
+    let (address, led_type) = connected_panel(api)?;
+    let (profile, source) = store.profile(&address, led_type);
+    Ok(PanelCalibration {
+        address,
+        led_type,
+        profile,
+        source,
+    })
+}
+
+/// Panel API applying the color profile of the connected panel to what is sent
+pub struct CalibratedPanel {
+    inner: Arc<dyn PanelApi>,
+    store: CalibrationStore,
+}
+
+impl CalibratedPanel {
+    pub fn new(inner: Arc<dyn PanelApi>, store: CalibrationStore) -> Self {
+        Self { inner, store }
+    }
+
+    fn transform(&self) -> Result<ColorTransform, ApiError> {
+        let calibration = calibration(self.inner.as_ref(), &self.store)?;
+        Ok(ColorTransform::new(&calibration.profile))
+    }
+}
+
+fn calibrate_hex(transform: &ColorTransform, hex: &str) -> Result<String, ApiError> {
+    let color = Rgb::from_hex(hex).map_err(|e| ApiError::Invalid(e.to_string()))?;
+    Ok(transform.apply_rgb(color).to_hex())
+}
+
+impl PanelApi for CalibratedPanel {
+    fn health(&self) -> Result<HealthResponse, ApiError> {
+        self.inner.health()
+    }
+
+    fn scan_devices(&self) -> Result<Vec<Device>, ApiError> {
+        self.inner.scan_devices()
+    }
+
+    fn connect(&self, request: &ConnectRequest) -> Result<ApiResponse, ApiError> {
+        self.inner.connect(request)
+    }
+
+    fn disconnect(&self) -> Result<ApiResponse, ApiError> {
+        self.inner.disconnect()
+    }
+
+    fn status(&self) -> Result<DeviceStatus, ApiError> {
+        self.inner.status()
+    }
+
+    fn device_info(&self) -> Result<DeviceInfo, ApiError> {
+        self.inner.device_info()
+    }
+
+    fn send_text(&self, request: &TextRequest) -> Result<ApiResponse, ApiError> {
+        let transform = self.transform()?;
+        let color = match &request.color {
+            Some(color) => calibrate_hex(&transform, color)?,
+            None => transform.apply_rgb(DEFAULT_TEXT_COLOR).to_hex(),
+        };
+        self.inner.send_text(&TextRequest {
+            color: Some(color),
+            ..request.clone()
+        })
+    }
+
+    /// Images are corrected while they are converted, see `color_profile`
+    fn send_image(&self, file_name: &str, data: &[u8]) -> Result<ApiResponse, ApiError> {
+        self.inner.send_image(file_name, data)
+    }
+
+    fn set_mode(&self, mode: PanelMode) -> Result<ApiResponse, ApiError> {
+        self.inner.set_mode(mode)
+    }
+
+    fn set_brightness(&self, request: &BrightnessRequest) -> Result<ApiResponse, ApiError> {
+        self.inner.set_brightness(request)
+    }
+
+    fn set_orientation(&self, request: &OrientationRequest) -> Result<ApiResponse, ApiError> {
+        self.inner.set_orientation(request)
+    }
+
+    fn send_pixels(&self, request: &PixelsRequest) -> Result<ApiResponse, ApiError> {
+        let transform = self.transform()?;
+        let pixels = request
+            .pixels
+            .iter()
+            .map(|pixel| {
+                Ok(PixelData {
+                    color: calibrate_hex(&transform, &pixel.color)?,
+                    ..pixel.clone()
+                })
+            })
+            .collect::<Result<_, ApiError>>()?;
+        self.inner.send_pixels(&PixelsRequest { pixels })
+    }
+
+    fn set_clock_mode(&self, settings: &ClockSettings) -> Result<ApiResponse, ApiError> {
+        self.inner.set_clock_mode(settings)
+    }
+
+    fn set_rhythm_mode(&self, settings: &RhythmSettings) -> Result<ApiResponse, ApiError> {
+        self.inner.set_rhythm_mode(settings)
+    }
+
+    fn set_rhythm_mode_2(&self, settings: &RhythmSettings2) -> Result<ApiResponse, ApiError> {
+        self.inner.set_rhythm_mode_2(settings)
+    }
+
+    fn set_power(&self, request: &PowerRequest) -> Result<ApiResponse, ApiError> {
+        self.inner.set_power(request)
+    }
+
+    fn color_profile(&self, info: &DeviceInfo) -> Result<ColorProfile, ApiError> {
+        let address = connected_address(self.inner.as_ref())?;
+        Ok(self.store.profile(&address, info.led_type).0)
+    }
+}
+
+/// Run a blocking call on the uncalibrated panel API, off the async runtime
+async fn blocking<R, T, F>(app: &AppHandle<R>, call: F) -> Result<T, ApiError>
+where
+    R: Runtime,
+    T: Send + 'static,
+    F: FnOnce(&dyn PanelApi, &CalibrationStore) -> Result<T, ApiError> + Send + 'static,
+{
+    let api = uncalibrated_api(app)?;
+    let store = app.state::<CalibrationStore>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || call(api.as_ref(), &store))
+        .await
+        .map_err(|e| ApiError::Unreachable(e.to_string()))?
+}
+
+/// Color profile of the connected panel
+#[tauri::command]
+pub async fn get_color_profile<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<PanelCalibration, ApiError> {
+    blocking(&app, |api, store| calibration(api, store)).await
+}
+
+/// Save the color profile of the connected panel
+#[tauri::command]
+pub async fn save_color_profile<R: Runtime>(
+    app: AppHandle<R>,
+    profile: ColorProfile,
+) -> Result<PanelCalibration, ApiError> {
+    blocking(&app, move |api, store| {
+        profile.validate()?;
+        let (address, led_type) = connected_panel(api)?;
+        store.save(&address, led_type, profile)?;
+        calibration(api, store)
+    })
+    .await
+}
+
+/// Forget the color profile of the connected panel
+#[tauri::command]
+pub async fn reset_color_profile<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<PanelCalibration, ApiError> {
+    blocking(&app, |api, store| {
+        let (address, _) = connected_panel(api)?;
+        store.remove(&address)?;
+        calibration(api, store)
+    })
+    .await
+}
+
+/// Show a test pattern corrected with `profile`, which doesn't need to be
+/// saved, so its effect can be seen while tuning it
+#[tauri::command]
+pub async fn send_test_pattern<R: Runtime>(
+    app: AppHandle<R>,
+    pattern: TestPattern,
+    profile: ColorProfile,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api, _| {
+        profile.validate()?;
+        let info = api.device_info()?;
+        let mut image = pattern.render(info.width, info.height);
+        ColorTransform::new(&profile).apply_image(&mut image);
+        api.send_image("test-pattern.png", &imaging::encode_png(&image)?)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_profiles_per_device_and_led_type() {
+        let dir = std::env::temp_dir().join(format!("calibration-test-{}", std::process::id()));
+        let store = CalibrationStore::new(&dir);
+        let _ = fs::remove_file(&store.path);
+
+        assert_eq!(
+            store.profile("AA:BB", 3),
+            (ColorProfile::default(), ProfileSource::Default)
+        );
+
+        let profile = ColorProfile {
+            gamma: 2.4,
+            ..ColorProfile::default()
+        };
+        store.save("AA:BB", 3, profile).unwrap();
+        assert_eq!(store.profile("AA:BB", 3), (profile, ProfileSource::Device));
+        // Another panel of the same type starts from it
+        assert_eq!(store.profile("CC:DD", 3), (profile, ProfileSource::LedType));
+        assert_eq!(store.profile("CC:DD", 1).1, ProfileSource::Default);
+
+        store.remove("AA:BB").unwrap();
+        assert_eq!(store.profile("AA:BB", 3).1, ProfileSource::LedType);
+
+        fs::write(&store.path, "not json").unwrap();
+        assert_eq!(store.profile("AA:BB", 3).1, ProfileSource::Default);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
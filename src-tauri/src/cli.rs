@@ -0,0 +1,862 @@
+//! Command line interface for scripting the panel.
+//!
+//! `pixelart-controller <command>` drives the panel without opening a window,
+//! e.g. from a shell script or a cron job. Commands go through the backend of
+//! the running app when there is one, otherwise a backend is started for the
+//! duration of the command. Panel commands reconnect to the last panel first
+//! when needed.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::backend_lock::BackendLock;
+use crate::calibration::{CalibratedPanel, CalibrationStore};
+use crate::font::{Font, TextStyle};
+use crate::font_library::render_for_panel;
+use crate::imaging::{ConvertOptions, Dither, ImageError, Palette};
+use crate::ipixel::protocol::Rgb;
+use crate::last_device::LastDevice;
+use crate::panel::types::*;
+use crate::panel::{send_image_resized, ApiError, BackendClient, PanelApi};
+use crate::startup_error::StartupError;
+use crate::{
+    backend_command, configure_backend_command, ensure_port_available, generate_backend_token,
+    pick_backend_port, process_tree, stop_backend_process, wait_for_backend, BackendMode,
+    BACKEND_TOKEN_ENV,
+};
+
+/// Print results as JSON instead of text
+const JSON_FLAG: &str = "--json";
+
+const USAGE: &str = "\
+Usage: pixelart-controller <command> [options] [--json]
+
+Commands:
+  scan                          List the panels in range
+  connect <address>             Connect to a panel, remembered for later commands
+  status                        Show the connection status
+  text <text> [--color RRGGBB] [--font NAME] [--animation N] [--speed N]
+       [--rainbow N] [--char-height N]
+                                Show text in a font of the panel
+  text <text> --font-file <file> [--color RRGGBB] [--background RRGGBB]
+       [--size N] [--no-antialias] [--align left|center|right] [--wrap]
+                                Show text in a BDF, PCF, TrueType or OpenType
+                                font, drawn as an image of the panel size. Text
+                                is as large as fits unless a size is given.
+  image <file> [--resize crop|fit|stretch] [--background RRGGBB]
+        [--filter auto|nearest|box|lanczos] [--palette full|2-256|RRGGBB,...]
+        [--dither none|floyd-steinberg|atkinson|ordered] [--max-bytes N]
+                                Show a PNG, JPEG, BMP or WebP image, or a GIF,
+                                APNG or WebP animation, resized to the panel (fit
+                                by default, on black) and optionally reduced to a
+                                palette. Animations are reduced to fit N bytes
+                                (64 KiB by default).
+  brightness <0-100>            Set the brightness
+  orientation <0-3>             Rotate the display by 90° steps
+  power <on|off>                Switch the display on or off
+  clock [--style 0-8] [--12h] [--no-date]
+                                Show the clock
+  rhythm <style 0-4> <levels>   Show rhythm bars, levels are 11 comma separated values 0-15
+  rhythm --v2 <style 0-1> <time 0-7>
+                                Show the animated rhythm mode
+  pixels <x,y,RRGGBB>...        Set pixels, or read {\"pixels\": [...]} from stdin with `-`
+
+Exit status: 0 on success, 1 when the panel command failed, 2 on invalid
+arguments, 3 when no backend could be reached, 4 when no panel is connected.";
+
+/// Exit status of the command line interface
+mod exit_code {
+    pub const FAILED: i32 = 1;
+    pub const USAGE: i32 = 2;
+    pub const NO_BACKEND: i32 = 3;
+    pub const NOT_CONNECTED: i32 = 4;
+}
+
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error("{0}")]
+    Usage(String),
+    #[error(transparent)]
+    Backend(#[from] StartupError),
+    #[error(transparent)]
+    Panel(#[from] ApiError),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) | CliError::Panel(ApiError::Invalid(_)) => exit_code::USAGE,
+            CliError::Backend(_) => exit_code::NO_BACKEND,
+            CliError::Panel(ApiError::NotConnected) => exit_code::NOT_CONNECTED,
+            CliError::Panel(_) => exit_code::FAILED,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            CliError::Usage(_) => Some("Run `pixelart-controller help` for the usage.".to_string()),
+            CliError::Backend(e) => Some(e.hint()),
+            CliError::Panel(ApiError::NotConnected) => {
+                Some("Connect once with `pixelart-controller connect <address>`.".to_string())
+            }
+            CliError::Panel(_) => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            CliError::Usage(_) => "usage",
+            CliError::Backend(e) => e.kind(),
+            CliError::Panel(e) => e.kind(),
+        }
+    }
+}
+
+/// A command of the command line interface
+#[derive(Debug)]
+enum Action {
+    Scan,
+    Connect(ConnectRequest),
+    Status,
+    Text(TextRequest),
+    /// Text drawn in a font file, sent as an image
+    RenderedText {
+        font: PathBuf,
+        text: String,
+        style: TextStyle,
+    },
+    Image(PathBuf, ConvertOptions),
+    Brightness(BrightnessRequest),
+    Orientation(OrientationRequest),
+    Power(PowerRequest),
+    Clock(ClockSettings),
+    Rhythm(RhythmSettings),
+    Rhythm2(RhythmSettings2),
+    Pixels(PixelsRequest),
+}
+
+const COMMANDS: [&str; 12] = [
+    "scan",
+    "connect",
+    "status",
+    "text",
+    "image",
+    "brightness",
+    "orientation",
+    "power",
+    "clock",
+    "rhythm",
+    "pixels",
+    "help",
+];
+
+/// Run the command given in `args` (without the executable), returns the
+/// exit status, or `None` when the arguments are not a command and the app
+/// should start normally.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.first()?;
+    if !COMMANDS.contains(&command.as_str()) {
+        return None;
+    }
+    attach_console();
+    if command == "help" {
+        println!("{}", USAGE);
+        return Some(0);
+    }
+
+    let json = args.iter().any(|arg| arg == JSON_FLAG);
+    let rest: Vec<&str> = args[1..]
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| *arg != JSON_FLAG)
+        .collect();
+
+    let result = parse(command, &rest).and_then(execute);
+    Some(match result {
+        Ok(output) => {
+            print_output(&output, json);
+            0
+        }
+        Err(e) => {
+            print_error(&e, json);
+            e.exit_code()
+        }
+    })
+}
+
+/// Release builds on Windows are GUI programs and start without a console,
+/// print to the one of the shell the command runs in. Redirected output
+/// already has handles and is left alone.
+#[cfg(windows)]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{
+        AttachConsole, GetStdHandle, ATTACH_PARENT_PROCESS, STD_OUTPUT_HANDLE,
+    };
+    // SAFETY: neither call takes pointers, both fail harmlessly
+    unsafe {
+        if GetStdHandle(STD_OUTPUT_HANDLE).is_null() {
+            AttachConsole(ATTACH_PARENT_PROCESS);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+fn parse(command: &str, args: &[&str]) -> Result<Action, CliError> {
+    let mut args = Args::new(args);
+    let action = match command {
+        "scan" => Action::Scan,
+        "status" => Action::Status,
+        "connect" => Action::Connect(ConnectRequest {
+            address: args.positional("address")?,
+        }),
+        "text" => {
+            let mut request = TextRequest {
+                text: args.positional("text")?,
+                color: None,
+                font: None,
+                animation: None,
+                speed: None,
+                rainbow_mode: None,
+                char_height: None,
+            };
+            let mut font_file = None;
+            let mut style = TextStyle::default();
+            // Last option given that is only for the panel fonts, or only for
+            // font files
+            let mut panel_font_option = None;
+            let mut font_file_option = None;
+            while let Some(flag) = args.next_flag()? {
+                match flag {
+                    "--color" => request.color = Some(args.value(flag)?),
+                    "--font" => request.font = Some(args.value(flag)?),
+                    "--animation" => request.animation = Some(args.value(flag)?),
+                    "--speed" => request.speed = Some(args.value(flag)?),
+                    "--rainbow" => request.rainbow_mode = Some(args.value(flag)?),
+                    "--char-height" => request.char_height = Some(args.value(flag)?),
+                    "--font-file" => font_file = Some(PathBuf::from(args.value::<String>(flag)?)),
+                    "--background" => style.background = parse_color(args.value(flag)?)?,
+                    "--size" => style.size = Some(args.value(flag)?),
+                    "--no-antialias" => style.antialias = false,
+                    "--align" => style.align = args.value(flag)?,
+                    "--wrap" => style.wrap = true,
+                    _ => return Err(unknown_option(flag)),
+                }
+                match flag {
+                    "--color" | "--font-file" => {}
+                    "--font" | "--animation" | "--speed" | "--rainbow" | "--char-height" => {
+                        panel_font_option = Some(flag)
+                    }
+                    _ => font_file_option = Some(flag),
+                }
+            }
+            match font_file {
+                Some(font) => {
+                    if let Some(flag) = panel_font_option {
+                        return Err(CliError::Usage(format!(
+                            "{} is only for the fonts of the panel, not with --font-file",
+                            flag
+                        )));
+                    }
+                    if let Some(color) = request.color {
+                        style.color = parse_color(color)?;
+                    }
+                    Action::RenderedText {
+                        font,
+                        text: request.text,
+                        style,
+                    }
+                }
+                None => {
+                    if let Some(flag) = font_file_option {
+                        return Err(CliError::Usage(format!("{} needs --font-file", flag)));
+                    }
+                    request.validate()?;
+                    Action::Text(request)
+                }
+            }
+        }
+        "image" => {
+            let path = PathBuf::from(args.positional::<String>("file")?);
+            let mut options = ConvertOptions::default();
+            while let Some(flag) = args.next_flag()? {
+                match flag {
+                    "--resize" => options.method = args.value(flag)?,
+                    "--background" => options.background = parse_color(args.value(flag)?)?,
+                    "--filter" => options.filter = args.value(flag)?,
+                    "--palette" => {
+                        let palette: String = args.value(flag)?;
+                        options.palette = palette.parse().map_err(CliError::Usage)?;
+                    }
+                    "--dither" => options.dither = args.value(flag)?,
+                    "--max-bytes" => options.budget.max_bytes = args.value(flag)?,
+                    _ => return Err(unknown_option(flag)),
+                }
+            }
+            if options.palette == Palette::Full && options.dither != Dither::None {
+                let error = ImageError::DitherWithoutPalette;
+                return Err(CliError::Usage(error.to_string()));
+            }
+            Action::Image(path, options)
+        }
+        "brightness" => {
+            let request = BrightnessRequest {
+                brightness: args.positional("brightness")?,
+            };
+            request.validate()?;
+            Action::Brightness(request)
+        }
+        "orientation" => {
+            let request = OrientationRequest {
+                orientation: args.positional("orientation")?,
+            };
+            request.validate()?;
+            Action::Orientation(request)
+        }
+        "power" => {
+            let on = match args.positional::<String>("on|off")?.as_str() {
+                "on" => true,
+                "off" => false,
+                other => {
+                    return Err(CliError::Usage(format!(
+                        "Invalid power state: {} (expected on or off)",
+                        other
+                    )))
+                }
+            };
+            Action::Power(PowerRequest { on })
+        }
+        "clock" => {
+            let mut settings = ClockSettings {
+                style: 0,
+                format_24: true,
+                show_date: true,
+            };
+            while let Some(flag) = args.next_flag()? {
+                match flag {
+                    "--style" => settings.style = args.value(flag)?,
+                    "--12h" => settings.format_24 = false,
+                    "--no-date" => settings.show_date = false,
+                    _ => return Err(unknown_option(flag)),
+                }
+            }
+            settings.validate()?;
+            Action::Clock(settings)
+        }
+        "rhythm" if args.take_flag("--v2") => {
+            let settings = RhythmSettings2 {
+                style: args.positional("style")?,
+                time: args.positional("time")?,
+            };
+            settings.validate()?;
+            Action::Rhythm2(settings)
+        }
+        "rhythm" => {
+            let style = args.positional("style")?;
+            let levels = args.positional::<String>("levels")?;
+            let settings = RhythmSettings {
+                style,
+                levels: levels
+                    .split(',')
+                    .map(|level| level.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| CliError::Usage(format!("Invalid levels: {}", levels)))?,
+            };
+            settings.validate()?;
+            Action::Rhythm(settings)
+        }
+        "pixels" => {
+            let request = if args.take_flag("-") {
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut input)
+                    .map_err(|e| CliError::Usage(format!("Failed to read stdin: {}", e)))?;
+                serde_json::from_str(&input)
+                    .map_err(|e| CliError::Usage(format!("Invalid pixels JSON: {}", e)))?
+            } else {
+                PixelsRequest {
+                    pixels: args
+                        .rest()
+                        .iter()
+                        .map(|pixel| parse_pixel(pixel))
+                        .collect::<Result<_, _>>()?,
+                }
+            };
+            if request.pixels.is_empty() {
+                return Err(CliError::Usage("No pixels given".to_string()));
+            }
+            request.validate()?;
+            Action::Pixels(request)
+        }
+        _ => unreachable!("not a command: {}", command),
+    };
+    args.finish()?;
+    Ok(action)
+}
+
+fn parse_pixel(pixel: &str) -> Result<PixelData, CliError> {
+    let invalid = || CliError::Usage(format!("Invalid pixel: {} (expected x,y,RRGGBB)", pixel));
+    let mut parts = pixel.split(',');
+    let (Some(x), Some(y), Some(color), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    Ok(PixelData {
+        x: x.trim().parse().map_err(|_| invalid())?,
+        y: y.trim().parse().map_err(|_| invalid())?,
+        color: color.trim().trim_start_matches('#').to_string(),
+    })
+}
+
+fn parse_color(color: String) -> Result<Rgb, CliError> {
+    Rgb::from_hex(&color).map_err(|e| CliError::Usage(e.to_string()))
+}
+
+fn unknown_option(flag: &str) -> CliError {
+    CliError::Usage(format!("Unknown option: {}", flag))
+}
+
+/// Arguments of a command, consumed from the front
+struct Args<'a> {
+    args: Vec<&'a str>,
+}
+
+impl<'a> Args<'a> {
+    fn new(args: &[&'a str]) -> Self {
+        Self {
+            args: args.to_vec(),
+        }
+    }
+
+    /// Next positional argument, they come before the options
+    fn positional<T: std::str::FromStr>(&mut self, name: &str) -> Result<T, CliError> {
+        match self.args.first() {
+            Some(arg) if !arg.starts_with("--") => {}
+            _ => return Err(CliError::Usage(format!("Missing <{}>", name))),
+        }
+        let value = self.args.remove(0);
+        value
+            .parse()
+            .map_err(|_| CliError::Usage(format!("Invalid <{}>: {}", name, value)))
+    }
+
+    /// Next option, e.g. `--color`
+    fn next_flag(&mut self) -> Result<Option<&'a str>, CliError> {
+        match self.args.first() {
+            None => Ok(None),
+            Some(arg) if arg.starts_with("--") => Ok(Some(self.args.remove(0))),
+            Some(arg) => Err(CliError::Usage(format!("Unexpected argument: {}", arg))),
+        }
+    }
+
+    fn value<T: std::str::FromStr>(&mut self, flag: &str) -> Result<T, CliError> {
+        if self.args.is_empty() {
+            return Err(CliError::Usage(format!("{} expects a value", flag)));
+        }
+        let value = self.args.remove(0);
+        value
+            .parse()
+            .map_err(|_| CliError::Usage(format!("Invalid {} value: {}", flag, value)))
+    }
+
+    /// Remove `flag` wherever it is, returns whether it was given
+    fn take_flag(&mut self, flag: &str) -> bool {
+        let len = self.args.len();
+        self.args.retain(|arg| *arg != flag);
+        self.args.len() != len
+    }
+
+    fn rest(&mut self) -> Vec<&'a str> {
+        std::mem::take(&mut self.args)
+    }
+
+    fn finish(self) -> Result<(), CliError> {
+        match self.args.first() {
+            Some(arg) => Err(CliError::Usage(format!("Unexpected argument: {}", arg))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Result of a command
+enum Output {
+    Devices(Vec<Device>),
+    Status(DeviceStatus),
+    Response(ApiResponse),
+}
+
+fn execute(action: Action) -> Result<Output, CliError> {
+    let session = Session::open()?;
+    let api = session.api.as_ref();
+
+    let response = match action {
+        Action::Scan => return Ok(Output::Devices(api.scan_devices()?)),
+        Action::Status => return Ok(Output::Status(api.status()?)),
+        Action::Connect(request) => {
+            let response = api.connect(&request)?;
+            session.last_device.write(&request.address);
+            response
+        }
+        action => {
+            session.ensure_connected()?;
+            match action {
+                Action::Text(request) => api.send_text(&request)?,
+                Action::RenderedText { font, text, style } => {
+                    let font = Font::load(&read_file(&font)?).map_err(ApiError::from)?;
+                    let png = render_for_panel(api, &font, &text, &style)?;
+                    api.send_image("text.png", &png)?
+                }
+                Action::Image(path, options) => {
+                    let data = read_file(&path)?;
+                    let file_name = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| "image".to_string());
+                    send_image_resized(api, &file_name, &data, &options)?
+                }
+                Action::Brightness(request) => api.set_brightness(&request)?,
+                Action::Orientation(request) => api.set_orientation(&request)?,
+                Action::Power(request) => api.set_power(&request)?,
+                Action::Clock(settings) => api.set_clock_mode(&settings)?,
+                Action::Rhythm(settings) => api.set_rhythm_mode(&settings)?,
+                Action::Rhythm2(settings) => api.set_rhythm_mode_2(&settings)?,
+                Action::Pixels(request) => api.send_pixels(&request)?,
+                Action::Scan | Action::Status | Action::Connect(_) => unreachable!(),
+            }
+        }
+    };
+    Ok(Output::Response(response))
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, CliError> {
+    std::fs::read(path)
+        .map_err(|e| CliError::Usage(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+/// Backend started for a single command
+struct OwnedBackend {
+    child: Child,
+    url: String,
+    token: String,
+}
+
+/// Access to the panel for the duration of a command
+struct Session {
+    api: Arc<dyn PanelApi>,
+    last_device: LastDevice,
+    /// Set when the backend was started for this command, stopped on drop
+    owned: Option<OwnedBackend>,
+}
+
+impl Session {
+    fn open() -> Result<Self, CliError> {
+        let context = crate::context();
+        let data_dir = dirs::data_local_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(&context.config().identifier);
+        let last_device = LastDevice::new(&data_dir);
+
+        let (api, owned): (Arc<dyn PanelApi>, _) = match BackendMode::from_env()? {
+            BackendMode::External(url) => {
+                let token = std::env::var(BACKEND_TOKEN_ENV).ok();
+                (Arc::new(attach(&url, token)?), None)
+            }
+            BackendMode::Native => (native_panel()?, None),
+            _ => match running_app_backend(&data_dir) {
+                Some(client) => (Arc::new(client), None),
+                None => {
+                    let resource_dir = || {
+                        tauri::utils::platform::resource_dir(
+                            context.package_info(),
+                            &tauri::utils::Env::default(),
+                        )
+                        .map_err(|e| StartupError::ResourceDirMissing(e.to_string()))
+                    };
+                    let owned = start_owned_backend(resource_dir)?;
+                    let client =
+                        BackendClient::new(owned.url.clone()).with_token(Some(owned.token.clone()));
+                    (Arc::new(client), Some(owned))
+                }
+            },
+        };
+
+        Ok(Self {
+            api: Arc::new(CalibratedPanel::new(api, CalibrationStore::new(&data_dir))),
+            last_device,
+            owned,
+        })
+    }
+
+    /// Reconnect to the last panel unless one is connected
+    fn ensure_connected(&self) -> Result<(), ApiError> {
+        if self.api.status()?.connected {
+            return Ok(());
+        }
+        let address = self.last_device.read().ok_or(ApiError::NotConnected)?;
+        self.api.connect(&ConnectRequest { address }).map(drop)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(owned) = self.owned.as_mut() {
+            stop_backend_process(&mut owned.child, Some(&owned.url), &owned.token);
+        }
+    }
+}
+
+/// Client for a backend started by someone else, once it answers
+fn attach(url: &str, token: Option<String>) -> Result<BackendClient, StartupError> {
+    let client = BackendClient::new(url).with_token(token);
+    let health = client
+        .health()
+        .map_err(|e| StartupError::ExternalUnreachable {
+            url: url.to_string(),
+            last_error: e.to_string(),
+        })?;
+    health.check_compatible()?;
+    Ok(client)
+}
+
+/// Client for the backend of the running app, found through its lock file
+fn running_app_backend(data_dir: &std::path::Path) -> Option<BackendClient> {
+    let info = BackendLock::new(data_dir).read()?;
+    if !process_tree::is_running(info.pid) {
+        return None;
+    }
+    let url = format!("http://127.0.0.1:{}", info.port);
+    attach(&url, info.token).ok()
+}
+
+fn start_owned_backend(
+    resource_dir: impl FnOnce() -> Result<PathBuf, StartupError>,
+) -> Result<OwnedBackend, StartupError> {
+    let port = pick_backend_port()?;
+    ensure_port_available(port)?;
+    let token = generate_backend_token()?;
+
+    let mut command = backend_command(resource_dir)?;
+    let child = configure_backend_command(&mut command, port, &token)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(StartupError::Spawn)?;
+
+    // Stopped on drop, also when the backend never becomes ready
+    let mut owned = OwnedBackend {
+        child,
+        url: format!("http://127.0.0.1:{}", port),
+        token,
+    };
+    let client = BackendClient::new(owned.url.clone()).with_token(Some(owned.token.clone()));
+    match wait_for_backend(&client, |_, _| {}).and_then(|health| health.check_compatible()) {
+        Ok(()) => Ok(owned),
+        Err(e) => {
+            let _ = process_tree::kill_tree(&mut owned.child);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(feature = "native-ble")]
+fn native_panel() -> Result<Arc<dyn PanelApi>, StartupError> {
+    Ok(Arc::new(crate::panel::native::NativePanel::default()))
+}
+
+#[cfg(not(feature = "native-ble"))]
+fn native_panel() -> Result<Arc<dyn PanelApi>, StartupError> {
+    Err(StartupError::FeatureMissing {
+        setting: format!("{}=native", crate::BACKEND_MODE_ENV),
+        feature: "native-ble",
+    })
+}
+
+fn print_output(output: &Output, json: bool) {
+    if json {
+        let value = match output {
+            Output::Devices(devices) => to_json(devices),
+            Output::Status(status) => to_json(status),
+            Output::Response(response) => to_json(response),
+        };
+        println!("{}", value);
+        return;
+    }
+
+    match output {
+        Output::Devices(devices) if devices.is_empty() => println!("No panel found"),
+        Output::Devices(devices) => {
+            for device in devices {
+                let rssi = device
+                    .rssi
+                    .map(|rssi| format!("{} dBm", rssi))
+                    .unwrap_or_default();
+                println!("{}\t{}\t{}", device.address, device.name, rssi);
+            }
+        }
+        Output::Status(status) => match &status.device_address {
+            Some(address) if status.connected => println!("Connected to {}", address),
+            _ => println!("Not connected"),
+        },
+        Output::Response(response) => match response.extra.get("message").and_then(Value::as_str) {
+            Some(message) => println!("{}", message),
+            None => println!("{}", response.status),
+        },
+    }
+}
+
+fn print_error(error: &CliError, json: bool) {
+    if json {
+        println!(
+            "{}",
+            json!({ "error": { "kind": error.kind(), "message": error.to_string(), "hint": error.hint() } })
+        );
+        return;
+    }
+    eprintln!("Error: {}", error);
+    if let Some(hint) = error.hint() {
+        eprintln!("{}", hint);
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Align;
+    use crate::imaging::{Budget, ResampleFilter, ResizeMethod};
+
+    fn parse_args(args: &[&str]) -> Result<Action, CliError> {
+        parse(args[0], &args[1..])
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(
+            parse_args(&["text", "Hello", "--color", "ff0000", "--speed", "50"]),
+            Ok(Action::Text(TextRequest { ref text, color: Some(ref color), speed: Some(50), .. }))
+                if text == "Hello" && color == "ff0000"
+        ));
+        assert!(matches!(
+            parse_args(&[
+                "text",
+                "Hi",
+                "--font-file",
+                "tiny.bdf",
+                "--color",
+                "ff0000",
+                "--size",
+                "12",
+                "--align",
+                "left",
+                "--no-antialias"
+            ]),
+            Ok(Action::RenderedText {
+                ref text,
+                style: TextStyle {
+                    size: Some(12.0),
+                    antialias: false,
+                    color: Rgb(255, 0, 0),
+                    align: Align::Left,
+                    wrap: false,
+                    ..
+                },
+                ..
+            }) if text == "Hi"
+        ));
+        assert!(matches!(
+            parse_args(&["clock", "--style", "3", "--12h"]),
+            Ok(Action::Clock(ClockSettings {
+                style: 3,
+                format_24: false,
+                show_date: true
+            }))
+        ));
+        assert!(matches!(
+            parse_args(&["rhythm", "--v2", "1", "4"]),
+            Ok(Action::Rhythm2(RhythmSettings2 { style: 1, time: 4 }))
+        ));
+        assert!(matches!(
+            parse_args(&["pixels", "1,2,ff0000", "3,4,#00ff00"]),
+            Ok(Action::Pixels(PixelsRequest { ref pixels })) if pixels.len() == 2 && pixels[1].color == "00ff00"
+        ));
+        assert!(matches!(
+            parse_args(&[
+                "image",
+                "cat.jpg",
+                "--resize",
+                "crop",
+                "--background",
+                "#102030"
+            ]),
+            Ok(Action::Image(
+                _,
+                ConvertOptions {
+                    method: ResizeMethod::Crop,
+                    background: Rgb(0x10, 0x20, 0x30),
+                    ..
+                }
+            ))
+        ));
+        assert!(matches!(
+            parse_args(&[
+                "image",
+                "cat.png",
+                "--filter",
+                "nearest",
+                "--palette",
+                "16",
+                "--dither",
+                "atkinson",
+                "--max-bytes",
+                "16384"
+            ]),
+            Ok(Action::Image(
+                _,
+                ConvertOptions {
+                    filter: ResampleFilter::Nearest,
+                    palette: Palette::Colors(16),
+                    dither: Dither::Atkinson,
+                    budget: Budget {
+                        max_bytes: 16384,
+                        ..
+                    },
+                    ..
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for args in [
+            &["brightness"][..],
+            &["brightness", "150"],
+            &["power", "maybe"],
+            &["text", "Hi", "--bogus"],
+            &["text", "--color", "ff0000", "Hi"],
+            &["text", "Hi", "--wrap"],
+            &["text", "Hi", "--font-file", "tiny.bdf", "--speed", "50"],
+            &["text", "Hi", "--font-file", "tiny.bdf", "--align", "top"],
+            &["status", "extra"],
+            &["pixels", "1,2"],
+            &["image", "cat.jpg", "--resize", "zoom"],
+            &["image", "cat.jpg", "--background", "blue"],
+            &["image", "cat.jpg", "--palette", "1"],
+            &["image", "cat.jpg", "--dither", "ordered"],
+            &["image", "cat.gif", "--max-bytes", "-1"],
+        ] {
+            let error = parse_args(args).unwrap_err();
+            assert_eq!(error.exit_code(), exit_code::USAGE, "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! BDF, the text format of X11 bitmap fonts.
+//!
+//! Characters are looked up by their encoding, taken as a Unicode code point:
+//! right for ISO 10646 and ISO 8859-1 fonts, the common ones.
+
+use std::collections::HashMap;
+
+use super::{BitmapFont, FontError, Glyph};
+
+fn invalid(reason: impl Into<String>) -> FontError {
+    FontError::Invalid {
+        format: "BDF",
+        reason: reason.into(),
+    }
+}
+
+/// Numbers following a keyword
+fn numbers<const N: usize>(keyword: &str, values: &str) -> Result<[i32; N], FontError> {
+    let values: Vec<i32> = values
+        .split_whitespace()
+        .take(N)
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid(format!("invalid {}", keyword)))?;
+    values
+        .try_into()
+        .map_err(|_| invalid(format!("invalid {}", keyword)))
+}
+
+/// Character being read
+#[derive(Default)]
+struct Char {
+    encoding: Option<u32>,
+    advance: Option<i32>,
+    /// Width, height and offset from the pen of the bitmap
+    bbx: Option<[i32; 4]>,
+    rows: Vec<Vec<u8>>,
+}
+
+impl Char {
+    fn glyph(&self, default_advance: i32) -> Result<Glyph, FontError> {
+        let [width, height, x, y] = self.bbx.ok_or_else(|| invalid("character without BBX"))?;
+        if width < 0 || height < 0 || self.rows.len() != height as usize {
+            return Err(invalid("bitmap size doesn't match BBX"));
+        }
+        let row_bytes = (width as usize).div_ceil(8);
+        if self.rows.iter().any(|row| row.len() < row_bytes) {
+            return Err(invalid("bitmap row shorter than BBX"));
+        }
+        Ok(Glyph::from_bits(
+            width as u32,
+            height as u32,
+            x,
+            y + height,
+            self.advance.unwrap_or(default_advance),
+            |col, row| self.rows[row as usize][col as usize / 8] & (0x80 >> (col % 8)) != 0,
+        ))
+    }
+}
+
+fn hex_row(line: &str) -> Result<Vec<u8>, FontError> {
+    if !line.len().is_multiple_of(2) || !line.is_ascii() {
+        return Err(invalid("invalid bitmap row"));
+    }
+    (0..line.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&line[i..i + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid("invalid bitmap row"))
+}
+
+pub fn parse(data: &[u8]) -> Result<BitmapFont, FontError> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| invalid("not a text file"))?
+        .lines();
+
+    let mut bounding_box = None;
+    let mut ascent = None;
+    let mut descent = None;
+    let mut default_char = None;
+    let mut default_advance = 0;
+    let mut glyphs = HashMap::new();
+    let mut current: Option<Char> = None;
+    let mut in_bitmap = false;
+
+    for line in text {
+        let line = line.trim();
+        let (keyword, values) = line.split_once(' ').unwrap_or((line, ""));
+        if in_bitmap && keyword != "ENDCHAR" {
+            let glyph = current.as_mut().expect("bitmap outside of a character");
+            glyph.rows.push(hex_row(line)?);
+            continue;
+        }
+        match (keyword, current.as_mut()) {
+            ("FONTBOUNDINGBOX", _) => bounding_box = Some(numbers::<4>(keyword, values)?),
+            ("FONT_ASCENT", None) => ascent = Some(numbers::<1>(keyword, values)?[0]),
+            ("FONT_DESCENT", None) => descent = Some(numbers::<1>(keyword, values)?[0]),
+            ("DEFAULT_CHAR", None) => {
+                default_char = char::from_u32(numbers::<1>(keyword, values)?[0] as u32)
+            }
+            ("DWIDTH", None) => default_advance = numbers::<2>(keyword, values)?[0],
+            ("STARTCHAR", None) => current = Some(Char::default()),
+            ("STARTCHAR", Some(_)) => return Err(invalid("STARTCHAR without ENDCHAR")),
+            ("ENCODING", Some(glyph)) => {
+                // -1 for characters outside of the encoding
+                glyph.encoding = u32::try_from(numbers::<1>(keyword, values)?[0]).ok()
+            }
+            ("DWIDTH", Some(glyph)) => glyph.advance = Some(numbers::<2>(keyword, values)?[0]),
+            ("BBX", Some(glyph)) => glyph.bbx = Some(numbers::<4>(keyword, values)?),
+            ("BITMAP", Some(_)) => in_bitmap = true,
+            ("ENDCHAR", Some(_)) => {
+                in_bitmap = false;
+                let parsed = current.take().expect("in a character");
+                let glyph = parsed.glyph(default_advance)?;
+                if let Some(c) = parsed.encoding.and_then(char::from_u32) {
+                    glyphs.insert(c, glyph);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if current.is_some() {
+        return Err(invalid("STARTCHAR without ENDCHAR"));
+    }
+    if glyphs.is_empty() {
+        return Err(invalid("no characters"));
+    }
+    // The ascent and descent properties are optional, the bounding box isn't
+    let [_, height, _, y] = bounding_box.ok_or_else(|| invalid("missing FONTBOUNDINGBOX"))?;
+    Ok(BitmapFont {
+        glyphs,
+        default_char,
+        ascent: ascent.unwrap_or(height + y),
+        descent: descent.unwrap_or(-y),
+    })
+}
+
+/// Font of the tests: a 3x5 `A`, a block for `#`, and a blank space, on a
+/// baseline with one pixel of descent
+#[cfg(test)]
+pub(super) const TEST_FONT: &str = "\
+STARTFONT 2.1
+FONT -test-tiny-medium-r-normal--6-60-75-75-c-40-iso10646-1
+SIZE 6 75 75
+FONTBOUNDINGBOX 4 6 0 -1
+STARTPROPERTIES 3
+FONT_ASCENT 5
+FONT_DESCENT 1
+DEFAULT_CHAR 35
+ENDPROPERTIES
+CHARS 3
+STARTCHAR A
+ENCODING 65
+SWIDTH 666 0
+DWIDTH 4 0
+BBX 3 5 0 0
+BITMAP
+40
+A0
+E0
+A0
+A0
+ENDCHAR
+STARTCHAR numbersign
+ENCODING 35
+DWIDTH 4 0
+BBX 3 6 0 -1
+BITMAP
+E0
+E0
+E0
+E0
+E0
+E0
+ENDCHAR
+STARTCHAR space
+ENCODING 32
+DWIDTH 4 0
+BBX 0 0 0 0
+BITMAP
+ENDCHAR
+ENDFONT
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_characters() {
+        let font = parse(TEST_FONT.as_bytes()).unwrap();
+        assert_eq!((font.ascent, font.descent), (5, 1));
+        assert_eq!(font.line_height(), 6);
+
+        let a = font.glyph('A').unwrap();
+        assert_eq!(
+            (a.width, a.height, a.left, a.top, a.advance),
+            (3, 5, 0, 5, 4)
+        );
+        assert_eq!(&a.coverage[..6], [0, 255, 0, 255, 0, 255]);
+
+        let block = font.glyph('#').unwrap();
+        assert_eq!((block.top, block.height), (5, 6));
+        assert_eq!(font.glyph(' ').unwrap().width, 0);
+        // Missing characters show the default one
+        assert_eq!(font.glyph('z'), Some(block));
+    }
+
+    #[test]
+    fn falls_back_to_the_bounding_box() {
+        let font = TEST_FONT
+            .replace("FONT_ASCENT 5\n", "")
+            .replace("FONT_DESCENT 1\n", "");
+        let font = parse(font.as_bytes()).unwrap();
+        assert_eq!((font.ascent, font.descent), (5, 1));
+    }
+
+    #[test]
+    fn rejects_broken_fonts() {
+        for font in [
+            TEST_FONT.replace("BBX 3 5 0 0", "BBX 3 4 0 0"),
+            TEST_FONT.replace("A0\nE0", "A0\nZZ"),
+            TEST_FONT.replace("FONTBOUNDINGBOX 4 6 0 -1\n", ""),
+            TEST_FONT.replace("ENDCHAR\nSTARTCHAR space", "STARTCHAR space"),
+        ] {
+            assert!(parse(font.as_bytes()).is_err(), "{}", font);
+        }
+    }
+}
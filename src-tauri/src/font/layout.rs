@@ -0,0 +1,454 @@
+//! Lay text out on an image of the panel size.
+
+use std::fmt;
+use std::str::FromStr;
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+use super::outline::OutlineFont;
+use super::{BitmapFont, Font, FontError, Glyph, SIZE_RANGE};
+use crate::ipixel::protocol::Rgb;
+
+/// Horizontal alignment of the lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+impl Align {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Right => "right",
+        }
+    }
+}
+
+impl fmt::Display for Align {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Align {
+    type Err = String;
+
+    fn from_str(align: &str) -> Result<Self, Self::Err> {
+        match align {
+            "left" => Ok(Align::Left),
+            "center" => Ok(Align::Center),
+            "right" => Ok(Align::Right),
+            other => Err(format!(
+                "Invalid alignment \"{}\" (expected left, center or right)",
+                other
+            )),
+        }
+    }
+}
+
+/// How text is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// Height of the lines in pixels, bitmap fonts are scaled by the closest
+    /// whole factor. By default the text is as large as fits the panel.
+    pub size: Option<f32>,
+    /// Smooth the edges of scalable fonts, off for crisp pixels
+    pub antialias: bool,
+    pub color: Rgb,
+    pub background: Rgb,
+    pub align: Align,
+    /// Break lines too wide for the panel between words
+    pub wrap: bool,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: None,
+            antialias: true,
+            color: Rgb(255, 255, 255),
+            background: Rgb::default(),
+            align: Align::default(),
+            wrap: false,
+        }
+    }
+}
+
+/// A font at the size the text is drawn at
+enum Sized<'a> {
+    Bitmap {
+        font: &'a BitmapFont,
+        factor: u32,
+    },
+    Outline {
+        font: &'a OutlineFont,
+        size: f32,
+        antialias: bool,
+    },
+}
+
+impl Sized<'_> {
+    /// Rows above and below the baseline
+    fn line_metrics(&self) -> (i32, i32) {
+        match self {
+            Sized::Bitmap { font, factor } => {
+                let factor = *factor as i32;
+                (font.ascent * factor, font.descent * factor)
+            }
+            Sized::Outline { font, size, .. } => font.line_metrics(*size),
+        }
+    }
+
+    fn line_height(&self) -> i32 {
+        let (ascent, descent) = self.line_metrics();
+        ascent + descent
+    }
+
+    fn glyph(&self, c: char) -> Option<Glyph> {
+        match self {
+            Sized::Bitmap { font, factor } => Some(font.glyph(c)?.scaled(*factor)),
+            Sized::Outline {
+                font,
+                size,
+                antialias,
+            } => font.glyph(c, *size, *antialias),
+        }
+    }
+
+    /// Adjustment of the space between `previous` and `c`
+    fn kerning(&self, previous: Option<char>, c: char) -> i32 {
+        match (self, previous) {
+            (Sized::Outline { font, size, .. }, Some(previous)) => font.kerning(previous, c, *size),
+            _ => 0,
+        }
+    }
+
+    /// How far the pen moves after `c`
+    fn advance(&self, c: char) -> i32 {
+        match self {
+            Sized::Bitmap { font, factor } => font
+                .glyph(c)
+                .map_or(0, |glyph| glyph.advance * *factor as i32),
+            Sized::Outline { font, size, .. } => font.advance(c, *size),
+        }
+    }
+
+    /// Right edge of the pixels of `c`, from the pen
+    fn ink_right(&self, c: char) -> i32 {
+        match self {
+            Sized::Bitmap { font, factor } => font.glyph(c).map_or(0, |glyph| {
+                (glyph.left + glyph.width as i32) * *factor as i32
+            }),
+            Sized::Outline { font, size, .. } => font.ink_right(c, *size),
+        }
+    }
+
+    /// Width of a line, up to the pixels of its last character so that
+    /// lines are aligned on what is drawn
+    fn width(&self, line: &[char]) -> i32 {
+        let mut width = 0;
+        let mut previous = None;
+        for (i, &c) in line.iter().enumerate() {
+            width += self.kerning(previous, c);
+            width += if i + 1 == line.len() {
+                self.ink_right(c)
+            } else {
+                self.advance(c)
+            };
+            previous = Some(c);
+        }
+        width
+    }
+
+    /// Break a paragraph into lines at most `width` wide, between words or
+    /// within words wider than that
+    fn wrap(&self, paragraph: &str, width: i32) -> Vec<Vec<char>> {
+        let mut lines = Vec::new();
+        let mut line: Vec<char> = Vec::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() {
+                let mut joined = line.clone();
+                joined.push(' ');
+                joined.extend(word.chars());
+                if self.width(&joined) <= width {
+                    line = joined;
+                    continue;
+                }
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if line.len() > 1 && self.width(&line) > width {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, vec![c]));
+                }
+            }
+        }
+        lines.push(line);
+        lines
+    }
+
+    fn lines(&self, text: &str, width: u32, wrap: bool) -> Vec<Vec<char>> {
+        text.lines()
+            .flat_map(|paragraph| {
+                if wrap {
+                    self.wrap(paragraph, width as i32)
+                } else {
+                    vec![paragraph.chars().collect()]
+                }
+            })
+            .collect()
+    }
+
+    fn fits(&self, lines: &[Vec<char>], width: u32, height: u32) -> bool {
+        self.line_height() * lines.len() as i32 <= height as i32
+            && lines.iter().all(|line| self.width(line) <= width as i32)
+    }
+}
+
+/// The font at the sizes to try, largest first
+fn sizes<'a>(font: &'a Font, style: &TextStyle, height: u32, paragraphs: usize) -> Vec<Sized<'a>> {
+    match font {
+        Font::Bitmap(font) => {
+            let line_height = font.line_height();
+            let factors = match style.size {
+                Some(size) => {
+                    let factor = (size / line_height as f32).round().max(1.0) as u32;
+                    factor..=factor
+                }
+                None => 1..=(height / (line_height * paragraphs as u32)).max(1),
+            };
+            factors
+                .rev()
+                .map(|factor| Sized::Bitmap { font, factor })
+                .collect()
+        }
+        Font::Outline(font) => {
+            let largest = (height as f32 / paragraphs as f32).floor();
+            let sizes = match style.size {
+                Some(size) => size as u32..=size as u32,
+                None => *SIZE_RANGE.start() as u32..=largest.max(*SIZE_RANGE.start()) as u32,
+            };
+            sizes
+                .rev()
+                .map(|size| Sized::Outline {
+                    font,
+                    size: style.size.unwrap_or(size as f32),
+                    antialias: style.antialias,
+                })
+                .collect()
+        }
+    }
+}
+
+fn blend(background: u8, color: u8, coverage: u8) -> u8 {
+    let (background, color, coverage) = (background as u32, color as u32, coverage as u32);
+    ((background * (255 - coverage) + color * coverage + 127) / 255) as u8
+}
+
+fn draw(image: &mut RgbImage, glyph: &Glyph, x: i32, baseline: i32, Rgb(r, g, b): Rgb) {
+    let (left, top) = (x + glyph.left, baseline - glyph.top);
+    for (i, &coverage) in glyph.coverage.iter().enumerate() {
+        if coverage == 0 {
+            continue;
+        }
+        let px = left + (i as u32 % glyph.width) as i32;
+        let py = top + (i as u32 / glyph.width) as i32;
+        if px < 0 || py < 0 || px >= image.width() as i32 || py >= image.height() as i32 {
+            continue;
+        }
+        let pixel = image.get_pixel_mut(px as u32, py as u32);
+        for (channel, color) in pixel.0.iter_mut().zip([r, g, b]) {
+            *channel = blend(*channel, color, coverage);
+        }
+    }
+}
+
+/// Draw text on an image of the panel size, one line per line of `text`
+/// (and more when wrapping), centered vertically. What doesn't fit is cut.
+pub fn render(
+    font: &Font,
+    text: &str,
+    width: u32,
+    height: u32,
+    style: &TextStyle,
+) -> Result<RgbImage, FontError> {
+    if text.is_empty() {
+        return Err(FontError::EmptyText);
+    }
+    if let Some(size) = style.size {
+        if !SIZE_RANGE.contains(&size) {
+            return Err(FontError::InvalidSize(size));
+        }
+    }
+
+    let paragraphs = text.lines().count().max(1);
+    let mut layouts = sizes(font, style, height, paragraphs)
+        .into_iter()
+        .map(|sized| {
+            let lines = sized.lines(text, width, style.wrap);
+            (sized, lines)
+        });
+    // The largest size that fits, or the smallest one
+    let mut layout = layouts.next().expect("at least one size");
+    while !layout.0.fits(&layout.1, width, height) {
+        match layouts.next() {
+            Some(smaller) => layout = smaller,
+            None => break,
+        }
+    }
+    let (sized, lines) = layout;
+
+    let Rgb(r, g, b) = style.background;
+    let mut image = RgbImage::from_pixel(width, height, image::Rgb([r, g, b]));
+    let (ascent, _) = sized.line_metrics();
+    let line_height = sized.line_height();
+    let text_height = line_height * lines.len() as i32;
+    let top = (height as i32 - text_height).max(0) / 2;
+
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = top + i as i32 * line_height + ascent;
+        let line_width = sized.width(line);
+        let mut x = match style.align {
+            Align::Left => 0,
+            Align::Center => (width as i32 - line_width) / 2,
+            Align::Right => width as i32 - line_width,
+        };
+        let mut previous = None;
+        for &c in line {
+            x += sized.kerning(previous, c);
+            if let Some(glyph) = sized.glyph(c) {
+                draw(&mut image, &glyph, x, baseline, style.color);
+            }
+            x += sized.advance(c);
+            previous = Some(c);
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap_font() -> Font {
+        Font::load(super::super::bdf::TEST_FONT.as_bytes()).unwrap()
+    }
+
+    /// Rows of the image, `#` for the text and `.` for the background
+    fn rows(image: &RgbImage) -> Vec<String> {
+        image
+            .rows()
+            .map(|row| {
+                row.map(|pixel| if pixel.0[0] > 127 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn centers_a_line() {
+        let image = render(&bitmap_font(), "A", 7, 8, &TextStyle::default()).unwrap();
+        assert_eq!(
+            rows(&image),
+            [
+                ".......", "...#...", "..#.#..", "..###..", "..#.#..", "..#.#..", ".......",
+                ".......",
+            ]
+        );
+    }
+
+    #[test]
+    fn aligns_lines() {
+        let style = TextStyle {
+            align: Align::Right,
+            ..TextStyle::default()
+        };
+        let image = render(&bitmap_font(), "A\nAA", 8, 12, &style).unwrap();
+        // Up to the last pixels, without the space after the last character
+        assert_eq!(rows(&image)[0], "......#.");
+        assert_eq!(rows(&image)[6], "..#...#.");
+
+        let style = TextStyle {
+            align: Align::Left,
+            ..style
+        };
+        let image = render(&bitmap_font(), "A\nAA", 8, 12, &style).unwrap();
+        assert_eq!(rows(&image)[6], ".#...#..");
+    }
+
+    #[test]
+    fn scales_bitmap_fonts_to_the_panel() {
+        let image = render(&bitmap_font(), "A", 8, 12, &TextStyle::default()).unwrap();
+        assert_eq!(&rows(&image)[..3], ["...##...", "...##...", ".##..##."]);
+        // Unless the text would be wider than the panel
+        let image = render(&bitmap_font(), "AA", 8, 12, &TextStyle::default()).unwrap();
+        assert_eq!(rows(&image)[3], ".#...#..");
+
+        let style = TextStyle {
+            size: Some(18.0),
+            ..TextStyle::default()
+        };
+        let image = render(&bitmap_font(), "A", 12, 18, &style).unwrap();
+        assert_eq!(rows(&image)[0], "....###.....");
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let style = TextStyle {
+            wrap: true,
+            align: Align::Left,
+            ..TextStyle::default()
+        };
+        let image = render(&bitmap_font(), "A A AAAA", 11, 18, &style).unwrap();
+        let rows = rows(&image);
+        // "A A", then "AAA" and "A", the word being wider than the panel
+        assert_eq!(rows[0], ".#.......#.");
+        assert_eq!(rows[6], ".#...#...#.");
+        assert_eq!(rows[12], ".#.........");
+    }
+
+    #[test]
+    fn renders_scalable_fonts() {
+        let font = Font::load(&super::super::outline::test_font()).unwrap();
+        let style = TextStyle {
+            color: Rgb(255, 0, 0),
+            background: Rgb(0, 0, 255),
+            ..TextStyle::default()
+        };
+        // 10 pixels high, the square is half of it
+        let image = render(&font, "#", 12, 10, &style).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 255]);
+        assert_eq!(image.get_pixel(3, 3).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(7, 7).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(3, 8).0, [0, 0, 255]);
+        // Missing characters are left out, the font has no `?`
+        assert_eq!(
+            render(&font, "x#", 12, 10, &style).unwrap(),
+            render(&font, "#", 12, 10, &style).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let font = bitmap_font();
+        assert!(matches!(
+            render(&font, "", 8, 8, &TextStyle::default()),
+            Err(FontError::EmptyText)
+        ));
+        let style = TextStyle {
+            size: Some(1.0),
+            ..TextStyle::default()
+        };
+        assert!(matches!(
+            render(&font, "A", 8, 8, &style),
+            Err(FontError::InvalidSize(_))
+        ));
+    }
+}
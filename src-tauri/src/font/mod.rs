@@ -0,0 +1,203 @@
+//! Text rendered on the computer, in any font, and sent as an image.
+//!
+//! The firmware only knows a few fonts of its own. A [`Font`] is loaded from a
+//! BDF or PCF bitmap font, drawn pixel for pixel, or a TrueType or OpenType
+//! font rasterized at the size of the panel, and [`render`] lays text out on
+//! an image of the panel size, which is then sent like any other image.
+
+mod bdf;
+mod layout;
+mod outline;
+mod pcf;
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+pub use layout::{render, Align, TextStyle};
+use outline::OutlineFont;
+
+/// Accepted text sizes, in pixels
+pub const SIZE_RANGE: std::ops::RangeInclusive<f32> = 4.0..=255.0;
+
+/// Failure to load a font or render text
+#[derive(Debug, Error)]
+pub enum FontError {
+    #[error("Unsupported font format, expected BDF, PCF, TrueType or OpenType")]
+    UnsupportedFormat,
+    #[error("Compressed PCF fonts are not supported, decompress the font first")]
+    Compressed,
+    #[error("Invalid {format} font: {reason}")]
+    Invalid {
+        format: &'static str,
+        reason: String,
+    },
+    #[error(
+        "Text size must be between {} and {} pixels, got {size}",
+        SIZE_RANGE.start(),
+        SIZE_RANGE.end(),
+        size = .0
+    )]
+    InvalidSize(f32),
+    #[error("Text is empty")]
+    EmptyText,
+}
+
+/// A rasterized character, placed relative to the pen on the baseline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    pub width: u32,
+    pub height: u32,
+    /// Offset of the left column from the pen
+    pub left: i32,
+    /// Height of the top row above the baseline
+    pub top: i32,
+    /// How far the pen moves to the next character
+    pub advance: i32,
+    /// Coverage of the pixels row by row, from 0 (background) to 255 (text)
+    pub coverage: Vec<u8>,
+}
+
+impl Glyph {
+    /// Glyph of a bitmap row by row, with the given bits set
+    fn from_bits(
+        width: u32,
+        height: u32,
+        left: i32,
+        top: i32,
+        advance: i32,
+        bit: impl Fn(u32, u32) -> bool,
+    ) -> Self {
+        let coverage = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| if bit(x, y) { 255 } else { 0 })
+            .collect();
+        Self {
+            width,
+            height,
+            left,
+            top,
+            advance,
+            coverage,
+        }
+    }
+
+    /// Every pixel drawn as a `factor` by `factor` square
+    fn scaled(&self, factor: u32) -> Self {
+        if factor == 1 {
+            return self.clone();
+        }
+        let width = self.width * factor;
+        let coverage = (0..self.height * factor)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.coverage[((y / factor) * self.width + x / factor) as usize])
+            .collect();
+        let factor = factor as i32;
+        Self {
+            width,
+            height: self.height * factor as u32,
+            left: self.left * factor,
+            top: self.top * factor,
+            advance: self.advance * factor,
+            coverage,
+        }
+    }
+}
+
+/// A font of a single size, its characters drawn pixel by pixel
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    glyphs: HashMap<char, Glyph>,
+    /// Shown for the characters the font doesn't have
+    default_char: Option<char>,
+    ascent: i32,
+    descent: i32,
+}
+
+impl BitmapFont {
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.glyphs.get(&self.default_char?))
+    }
+
+    /// Height of a line, the size the font is drawn at
+    pub fn line_height(&self) -> u32 {
+        (self.ascent + self.descent).max(1) as u32
+    }
+}
+
+/// A font loaded from a file
+pub enum Font {
+    Bitmap(BitmapFont),
+    Outline(OutlineFont),
+}
+
+impl Font {
+    /// Load a BDF, PCF, TrueType or OpenType font, told apart by their content
+    pub fn load(data: &[u8]) -> Result<Self, FontError> {
+        if data.starts_with(b"STARTFONT") {
+            bdf::parse(data).map(Font::Bitmap)
+        } else if data.starts_with(pcf::MAGIC) {
+            pcf::parse(data).map(Font::Bitmap)
+        } else if data.starts_with(&[0x1F, 0x8B]) {
+            Err(FontError::Compressed)
+        } else if outline::is_outline(data) {
+            OutlineFont::parse(data).map(Font::Outline)
+        } else {
+            Err(FontError::UnsupportedFormat)
+        }
+    }
+
+    /// Whether the font is rasterized at any size, bitmap fonts are only
+    /// scaled by whole factors
+    pub fn is_scalable(&self) -> bool {
+        matches!(self, Font::Outline(_))
+    }
+
+    /// Size of the font, `None` for scalable fonts
+    pub fn bitmap_size(&self) -> Option<u32> {
+        match self {
+            Font::Bitmap(font) => Some(font.line_height()),
+            Font::Outline(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        assert!(matches!(
+            Font::load(b"\x1F\x8B\x08\x00"),
+            Err(FontError::Compressed)
+        ));
+        assert!(matches!(
+            Font::load(b"not a font"),
+            Err(FontError::UnsupportedFormat)
+        ));
+        assert!(matches!(
+            Font::load(b"STARTFONT 2.1\nENDFONT\n"),
+            Err(FontError::Invalid { format: "BDF", .. })
+        ));
+        assert!(matches!(
+            Font::load(b"\x01fcp\x00\x00"),
+            Err(FontError::Invalid { format: "PCF", .. })
+        ));
+        assert!(matches!(
+            Font::load(b"\x00\x01\x00\x00\x00\x00"),
+            Err(FontError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn scales_bitmap_glyphs() {
+        let glyph = Glyph::from_bits(2, 1, 1, 3, 3, |x, _| x == 0);
+        let scaled = glyph.scaled(2);
+        assert_eq!((scaled.width, scaled.height), (4, 2));
+        assert_eq!((scaled.left, scaled.top, scaled.advance), (2, 6, 6));
+        assert_eq!(scaled.coverage, [255, 255, 0, 0, 255, 255, 0, 0]);
+    }
+}
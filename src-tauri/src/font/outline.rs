@@ -0,0 +1,225 @@
+//! TrueType and OpenType fonts, rasterized at the size of the text.
+
+use ab_glyph::{point, Font as _, FontVec, GlyphId, PxScale, ScaleFont};
+
+use super::{FontError, Glyph};
+
+/// Shown for the characters the font doesn't have
+const REPLACEMENT_CHAR: char = '?';
+/// Coverage from which a pixel is drawn when antialiasing is off
+const COVERAGE_THRESHOLD: f32 = 0.5;
+
+/// Whether the data starts like a TrueType or OpenType font or collection
+pub fn is_outline(data: &[u8]) -> bool {
+    [b"\x00\x01\x00\x00", b"OTTO", b"true", b"ttcf"]
+        .iter()
+        .any(|magic| data.starts_with(*magic))
+}
+
+pub struct OutlineFont {
+    font: FontVec,
+}
+
+impl OutlineFont {
+    pub fn parse(data: &[u8]) -> Result<Self, FontError> {
+        let font = FontVec::try_from_vec(data.to_vec()).map_err(|e| FontError::Invalid {
+            format: "TrueType or OpenType",
+            reason: e.to_string(),
+        })?;
+        Ok(Self { font })
+    }
+
+    /// Rows above and below the baseline of text `size` pixels high
+    pub fn line_metrics(&self, size: f32) -> (i32, i32) {
+        let ascent = self.font.as_scaled(PxScale::from(size)).ascent().round() as i32;
+        // Lines exactly `size` high, so that text of the panel height fits
+        (ascent, size.round() as i32 - ascent)
+    }
+
+    /// Glyph of a character, or of the replacement character
+    fn glyph_id(&self, c: char) -> Option<GlyphId> {
+        [c, REPLACEMENT_CHAR]
+            .into_iter()
+            .map(|c| self.font.glyph_id(c))
+            // 0 is the glyph of the missing characters
+            .find(|id| id.0 != 0)
+    }
+
+    /// How far the pen moves after a character
+    pub fn advance(&self, c: char, size: f32) -> i32 {
+        self.glyph_id(c).map_or(0, |id| {
+            self.font
+                .as_scaled(PxScale::from(size))
+                .h_advance(id)
+                .round() as i32
+        })
+    }
+
+    /// Right edge of the pixels of a character, from the pen
+    pub fn ink_right(&self, c: char, size: f32) -> i32 {
+        let scale = self.font.as_scaled(PxScale::from(size)).h_scale_factor();
+        self.glyph_id(c)
+            .and_then(|id| self.font.outline(id))
+            .map_or(0, |outline| (outline.bounds.max.x * scale).ceil() as i32)
+    }
+
+    pub fn glyph(&self, c: char, size: f32, antialias: bool) -> Option<Glyph> {
+        let id = self.glyph_id(c)?;
+        let scale = PxScale::from(size);
+        let advance = self.advance(c, size);
+        // Spaces have no outline
+        let Some(outline) = self
+            .font
+            .outline_glyph(id.with_scale_and_position(scale, point(0.0, 0.0)))
+        else {
+            return Some(Glyph {
+                width: 0,
+                height: 0,
+                left: 0,
+                top: 0,
+                advance,
+                coverage: Vec::new(),
+            });
+        };
+
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let mut coverage = vec![0; (width * height) as usize];
+        outline.draw(|x, y, value| {
+            let value = if antialias {
+                value.clamp(0.0, 1.0)
+            } else if value >= COVERAGE_THRESHOLD {
+                1.0
+            } else {
+                0.0
+            };
+            if let Some(pixel) = coverage.get_mut((y * width + x) as usize) {
+                *pixel = (value * 255.0).round() as u8;
+            }
+        });
+        Some(Glyph {
+            width,
+            height,
+            left: bounds.min.x as i32,
+            top: -bounds.min.y as i32,
+            advance,
+            coverage,
+        })
+    }
+
+    /// Adjustment of the space between two characters
+    pub fn kerning(&self, left: char, right: char, size: f32) -> i32 {
+        match (self.glyph_id(left), self.glyph_id(right)) {
+            (Some(left), Some(right)) => self
+                .font
+                .as_scaled(PxScale::from(size))
+                .kern(left, right)
+                .round() as i32,
+            _ => 0,
+        }
+    }
+}
+
+/// A TrueType font with a single character, `#`: a square of half the em
+/// size on the baseline, ascent 800 and descent 200 units of a 1000 em
+#[cfg(test)]
+pub(super) fn test_font() -> Vec<u8> {
+    fn be16(data: &mut Vec<u8>, values: &[i32]) {
+        values
+            .iter()
+            .for_each(|&value| data.extend((value as u16).to_be_bytes()));
+    }
+    let mut head = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x5F, 0x0F, 0x3C, 0xF5];
+    be16(&mut head, &[0, 1000]);
+    head.extend([0; 16]);
+    // Bounding box, style, lowest size, direction, long offsets, glyph format
+    be16(&mut head, &[0, 0, 500, 500, 0, 8, 2, 1, 0]);
+
+    let mut hhea = vec![0, 1, 0, 0];
+    be16(&mut hhea, &[800, -200, 0, 600, 0, 0, 500, 1, 0, 0]);
+    hhea.extend([0; 8]);
+    be16(&mut hhea, &[0, 2]);
+
+    let mut maxp = vec![0, 0, 0x50, 0];
+    be16(&mut maxp, &[2]);
+
+    // Glyph 0 is empty, glyph 1 the square
+    let mut glyf = Vec::new();
+    be16(&mut glyf, &[1, 0, 0, 500, 500, 3, 0]);
+    glyf.extend([1; 4]);
+    be16(&mut glyf, &[0, 500, 0, -500, 0, 0, 500, 0]);
+    let mut loca = Vec::new();
+    for offset in [0u32, 0, glyf.len() as u32] {
+        loca.extend(offset.to_be_bytes());
+    }
+
+    let mut hmtx = Vec::new();
+    be16(&mut hmtx, &[600, 0, 600, 0]);
+
+    // Format 4 mapping of `#` to glyph 1
+    let mut cmap = Vec::new();
+    be16(&mut cmap, &[0, 1, 3, 1, 0, 12, 4, 32, 0, 4, 4, 1, 0]);
+    be16(&mut cmap, &[0x23, 0xFFFF, 0, 0x23, 0xFFFF, -0x22, 1, 0, 0]);
+
+    let tables: [(&[u8; 4], Vec<u8>); 7] = [
+        (b"cmap", cmap),
+        (b"glyf", glyf),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"loca", loca),
+        (b"maxp", maxp),
+    ];
+    let mut font = vec![0, 1, 0, 0];
+    be16(&mut font, &[tables.len() as i32, 64, 2, 48]);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, table) in &tables {
+        font.extend(*tag);
+        font.extend([0; 4]);
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((table.len() as u32).to_be_bytes());
+        offset += table.len().div_ceil(4) * 4;
+    }
+    for (_, mut table) in tables {
+        table.resize(table.len().div_ceil(4) * 4, 0);
+        font.extend(table);
+    }
+    font
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rasterizes_at_any_size() {
+        let font = OutlineFont::parse(&test_font()).unwrap();
+        assert_eq!(font.line_metrics(10.0), (8, 2));
+        assert!(font.glyph('a', 10.0, true).is_none());
+        assert_eq!((font.advance('#', 10.0), font.ink_right('#', 10.0)), (6, 5));
+
+        let square = font.glyph('#', 10.0, true).unwrap();
+        assert_eq!((square.width, square.height), (5, 5));
+        assert_eq!((square.left, square.top, square.advance), (0, 5, 6));
+        assert!(square.coverage.iter().all(|&value| value == 255));
+
+        let square = font.glyph('#', 20.0, false).unwrap();
+        assert_eq!((square.width, square.height, square.top), (10, 10, 10));
+    }
+
+    #[test]
+    fn antialiasing_can_be_turned_off() {
+        let font = OutlineFont::parse(&test_font()).unwrap();
+        // Edges fall in the middle of pixels at this size
+        let smooth = font.glyph('#', 9.0, true).unwrap();
+        assert!(smooth
+            .coverage
+            .iter()
+            .any(|&value| value != 0 && value != 255));
+        let sharp = font.glyph('#', 9.0, false).unwrap();
+        assert!(sharp
+            .coverage
+            .iter()
+            .all(|&value| value == 0 || value == 255));
+    }
+}
@@ -0,0 +1,457 @@
+//! PCF, the binary format X11 bitmap fonts are installed in.
+//!
+//! A table of contents points to tables of glyph metrics, bitmaps and
+//! encodings, each with its own byte order, bit order and row padding. Like
+//! BDF fonts, characters are looked up by their encoding as Unicode.
+
+use std::collections::HashMap;
+
+use super::{BitmapFont, FontError, Glyph};
+
+pub const MAGIC: &[u8] = b"\x01fcp";
+
+const ACCELERATORS: u32 = 1 << 1;
+const METRICS: u32 = 1 << 2;
+const BITMAPS: u32 = 1 << 3;
+const BDF_ENCODINGS: u32 = 1 << 5;
+const BDF_ACCELERATORS: u32 = 1 << 8;
+
+const GLYPH_PAD_MASK: u32 = 0b11;
+const BYTE_ORDER_MSB: u32 = 1 << 2;
+const BIT_ORDER_MSB: u32 = 1 << 3;
+const SCAN_UNIT_MASK: u32 = 0b11 << 4;
+const COMPRESSED_METRICS: u32 = 0x100;
+
+/// Encoding of the characters missing from the font
+const NO_GLYPH: u16 = 0xFFFF;
+/// Larger fonts are most likely corrupted
+const MAX_GLYPHS: usize = 65_536;
+
+fn invalid(reason: impl Into<String>) -> FontError {
+    FontError::Invalid {
+        format: "PCF",
+        reason: reason.into(),
+    }
+}
+
+/// Reads a table, in the byte order of its format
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    msb_first: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], FontError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + N)
+            .ok_or_else(|| invalid("truncated table"))?;
+        self.position += N;
+        Ok(bytes.try_into().expect("N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, FontError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FontError> {
+        let bytes = self.bytes()?;
+        Ok(if self.msb_first {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn i16(&mut self) -> Result<i16, FontError> {
+        self.u16().map(|value| value as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, FontError> {
+        let bytes = self.bytes()?;
+        Ok(if self.msb_first {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn i32(&mut self) -> Result<i32, FontError> {
+        self.u32().map(|value| value as i32)
+    }
+
+    fn count(&mut self) -> Result<usize, FontError> {
+        let count = self.u32()? as usize;
+        if count > MAX_GLYPHS {
+            return Err(invalid(format!("{} glyphs", count)));
+        }
+        Ok(count)
+    }
+}
+
+/// A table of the font and its format
+struct Table<'a> {
+    format: u32,
+    reader: Reader<'a>,
+}
+
+/// Find a table in the table of contents, positioned after its format
+fn table(data: &[u8], kind: u32) -> Result<Option<Table<'_>>, FontError> {
+    let mut toc = Reader {
+        data,
+        position: MAGIC.len(),
+        msb_first: false,
+    };
+    let count = toc.count()?;
+    for _ in 0..count {
+        let (table_kind, _format, size, offset) = (toc.u32()?, toc.u32()?, toc.u32()?, toc.u32()?);
+        if table_kind != kind {
+            continue;
+        }
+        let start = offset as usize;
+        let table = start
+            .checked_add(size as usize)
+            .and_then(|end| data.get(start..end))
+            .ok_or_else(|| invalid("table out of the file"))?;
+        // The format is repeated at the start of the table, always LSB first
+        let mut reader = Reader {
+            data: table,
+            position: 0,
+            msb_first: false,
+        };
+        let format = reader.u32()?;
+        reader.msb_first = format & BYTE_ORDER_MSB != 0;
+        return Ok(Some(Table { format, reader }));
+    }
+    Ok(None)
+}
+
+fn required_table<'a>(data: &'a [u8], kind: u32, name: &str) -> Result<Table<'a>, FontError> {
+    table(data, kind)?.ok_or_else(|| invalid(format!("missing {} table", name)))
+}
+
+/// Bounds of a glyph: left and right of the bitmap from the pen, advance, and
+/// rows above and below the baseline
+struct Metrics {
+    left: i32,
+    right: i32,
+    advance: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+fn metrics(table: &mut Table) -> Result<Vec<Metrics>, FontError> {
+    let reader = &mut table.reader;
+    if table.format & COMPRESSED_METRICS != 0 {
+        let count = reader.u16()?;
+        (0..count)
+            .map(|_| {
+                let mut value = || reader.u8().map(|value| value as i32 - 0x80);
+                Ok(Metrics {
+                    left: value()?,
+                    right: value()?,
+                    advance: value()?,
+                    ascent: value()?,
+                    descent: value()?,
+                })
+            })
+            .collect()
+    } else {
+        let count = reader.count()?;
+        (0..count)
+            .map(|_| {
+                let metrics = Metrics {
+                    left: reader.i16()?.into(),
+                    right: reader.i16()?.into(),
+                    advance: reader.i16()?.into(),
+                    ascent: reader.i16()?.into(),
+                    descent: reader.i16()?.into(),
+                };
+                // Attributes
+                reader.u16()?;
+                Ok(metrics)
+            })
+            .collect()
+    }
+}
+
+fn glyphs(data: &[u8], metrics: &[Metrics]) -> Result<Vec<Glyph>, FontError> {
+    let mut table = required_table(data, BITMAPS, "bitmaps")?;
+    let format = table.format;
+    let reader = &mut table.reader;
+    let count = reader.count()?;
+    if count != metrics.len() {
+        return Err(invalid("as many bitmaps as metrics expected"));
+    }
+    let offsets: Vec<usize> = (0..count)
+        .map(|_| reader.u32().map(|offset| offset as usize))
+        .collect::<Result<_, _>>()?;
+    // Size of the bitmaps with each of the paddings, only one is stored
+    let mut sizes = [0; 4];
+    for size in &mut sizes {
+        *size = reader.u32()? as usize;
+    }
+    let padding = format & GLYPH_PAD_MASK;
+    let bitmaps = reader.data[reader.position..]
+        .get(..sizes[padding as usize])
+        .ok_or_else(|| invalid("truncated bitmaps"))?;
+
+    // Rows are stored as scan units, bytes swapped when their byte order
+    // isn't the bit order, then bits in each byte
+    let lsb_bits = format & BIT_ORDER_MSB == 0;
+    let swap_unit = if (format & BYTE_ORDER_MSB != 0) != (format & BIT_ORDER_MSB != 0) {
+        1usize << ((format & SCAN_UNIT_MASK) >> 4)
+    } else {
+        1
+    };
+    let pad_bytes = 1usize << padding;
+    let byte = |offset: usize| -> Option<u8> {
+        let unit = offset / swap_unit * swap_unit;
+        let byte = *bitmaps.get(unit + swap_unit - 1 - offset % swap_unit)?;
+        Some(if lsb_bits { byte.reverse_bits() } else { byte })
+    };
+
+    metrics
+        .iter()
+        .zip(offsets)
+        .map(|(metrics, offset)| {
+            let width = (metrics.right - metrics.left).max(0) as usize;
+            let height = (metrics.ascent + metrics.descent).max(0) as usize;
+            let row_bytes = width.div_ceil(8).div_ceil(pad_bytes) * pad_bytes;
+            let end = offset + row_bytes * height;
+            if end > bitmaps.len() {
+                return Err(invalid("glyph bitmap out of the bitmaps"));
+            }
+            let glyph = Glyph::from_bits(
+                width as u32,
+                height as u32,
+                metrics.left,
+                metrics.ascent,
+                metrics.advance,
+                |x, y| {
+                    let offset = offset + y as usize * row_bytes + x as usize / 8;
+                    byte(offset).unwrap_or(0) & (0x80 >> (x % 8)) != 0
+                },
+            );
+            Ok(glyph)
+        })
+        .collect()
+}
+
+pub fn parse(data: &[u8]) -> Result<BitmapFont, FontError> {
+    let metrics = metrics(&mut required_table(data, METRICS, "metrics")?)?;
+    let glyphs = glyphs(data, &metrics)?;
+
+    let mut encodings = required_table(data, BDF_ENCODINGS, "encodings")?;
+    let reader = &mut encodings.reader;
+    let (first_col, last_col) = (reader.u16()?, reader.u16()?);
+    let (first_row, last_row) = (reader.u16()?, reader.u16()?);
+    let default_code = reader.u16()?;
+    if first_col > last_col || first_row > last_row {
+        return Err(invalid("invalid encoding range"));
+    }
+
+    let mut by_char = HashMap::new();
+    let mut default_char = None;
+    for row in first_row..=last_row {
+        for col in first_col..=last_col {
+            let index = reader.u16()?;
+            if index == NO_GLYPH {
+                continue;
+            }
+            let glyph = glyphs
+                .get(index as usize)
+                .ok_or_else(|| invalid("encoding of a missing glyph"))?;
+            let code = (row as u32) << 8 | col as u32;
+            if let Some(c) = char::from_u32(code) {
+                by_char.insert(c, glyph.clone());
+                if code == default_code as u32 {
+                    default_char = Some(c);
+                }
+            }
+        }
+    }
+    if by_char.is_empty() {
+        return Err(invalid("no characters"));
+    }
+
+    // The BDF accelerators are more accurate when both are there
+    let accelerators = match table(data, BDF_ACCELERATORS)? {
+        Some(table) => Some(table),
+        None => table(data, ACCELERATORS)?,
+    };
+    let (ascent, descent) = match accelerators {
+        Some(mut table) => {
+            // Flags, then the ascent and descent
+            table.reader.bytes::<8>()?;
+            (table.reader.i32()?, table.reader.i32()?)
+        }
+        None => (
+            metrics.iter().map(|m| m.ascent).max().unwrap_or(0),
+            metrics.iter().map(|m| m.descent).max().unwrap_or(0),
+        ),
+    };
+
+    Ok(BitmapFont {
+        glyphs: by_char,
+        default_char,
+        ascent,
+        descent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PCF of the test font: `A` (3x5) and `-` (2x1), with compressed or full
+    /// metrics, bitmaps in the given format
+    fn test_font(compressed: bool, bitmap_format: u32) -> Vec<u8> {
+        let msb = bitmap_format & BYTE_ORDER_MSB != 0;
+        let u16 = |value: u16| -> Vec<u8> {
+            if msb {
+                value.to_be_bytes().to_vec()
+            } else {
+                value.to_le_bytes().to_vec()
+            }
+        };
+        let u32 = |value: u32| -> Vec<u8> {
+            if msb {
+                value.to_be_bytes().to_vec()
+            } else {
+                value.to_le_bytes().to_vec()
+            }
+        };
+
+        // (left, right, advance, ascent, descent)
+        let glyph_metrics = [(0i16, 3i16, 4i16, 5i16, 0i16), (1, 3, 4, 3, -2)];
+        let metrics_format = (if compressed { COMPRESSED_METRICS } else { 0 }) | bitmap_format;
+        let mut metrics = metrics_format.to_le_bytes().to_vec();
+        if compressed {
+            metrics.extend(u16(2));
+            for (left, right, advance, ascent, descent) in glyph_metrics {
+                for value in [left, right, advance, ascent, descent] {
+                    metrics.push((value + 0x80) as u8);
+                }
+            }
+        } else {
+            metrics.extend(u32(2));
+            for (left, right, advance, ascent, descent) in glyph_metrics {
+                for value in [left, right, advance, ascent, descent, 0] {
+                    metrics.extend(u16(value as u16));
+                }
+            }
+        }
+
+        // Rows of 1 byte padded to the glyph padding, MSB first
+        let pad = 1usize << (bitmap_format & GLYPH_PAD_MASK);
+        let rows: [&[u8]; 2] = [&[0x40, 0xA0, 0xE0, 0xA0, 0xA0], &[0xC0]];
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        for glyph in rows {
+            offsets.push(data.len() as u32);
+            for &row in glyph {
+                data.push(row);
+                data.extend(vec![0; pad - 1]);
+            }
+        }
+        if bitmap_format & BIT_ORDER_MSB == 0 {
+            data.iter_mut().for_each(|byte| *byte = byte.reverse_bits());
+        }
+        let unit = 1usize << ((bitmap_format & SCAN_UNIT_MASK) >> 4);
+        if (bitmap_format & BYTE_ORDER_MSB != 0) != (bitmap_format & BIT_ORDER_MSB != 0) {
+            data.chunks_mut(unit).for_each(|chunk| chunk.reverse());
+        }
+        let mut bitmaps = bitmap_format.to_le_bytes().to_vec();
+        bitmaps.extend(u32(2));
+        offsets
+            .iter()
+            .for_each(|&offset| bitmaps.extend(u32(offset)));
+        for _ in 0..4 {
+            bitmaps.extend(u32(data.len() as u32));
+        }
+        bitmaps.extend(data);
+
+        // Columns 0x2D to 0x41 of row 0, default to `A`
+        let mut encodings = bitmap_format.to_le_bytes().to_vec();
+        for value in [0x2D, 0x41, 0, 0, 0x41] {
+            encodings.extend(u16(value));
+        }
+        for code in 0x2D..=0x41 {
+            encodings.extend(u16(match code {
+                0x41 => 0,
+                0x2D => 1,
+                _ => NO_GLYPH,
+            }));
+        }
+
+        let mut accelerators = bitmap_format.to_le_bytes().to_vec();
+        accelerators.extend([0; 8]);
+        accelerators.extend(u32(5));
+        accelerators.extend(u32(1));
+
+        let tables = [
+            (ACCELERATORS, accelerators),
+            (METRICS, metrics),
+            (BITMAPS, bitmaps),
+            (BDF_ENCODINGS, encodings),
+        ];
+        let mut font = MAGIC.to_vec();
+        font.extend((tables.len() as u32).to_le_bytes());
+        let mut offset = 8 + tables.len() * 16;
+        for (kind, table) in &tables {
+            for value in [*kind, 0, table.len() as u32, offset as u32] {
+                font.extend(value.to_le_bytes());
+            }
+            offset += table.len();
+        }
+        for (_, table) in tables {
+            font.extend(table);
+        }
+        font
+    }
+
+    fn check(font: &BitmapFont) {
+        assert_eq!((font.ascent, font.descent), (5, 1));
+        let a = font.glyph('A').unwrap();
+        assert_eq!((a.width, a.height, a.top, a.advance), (3, 5, 5, 4));
+        assert_eq!(&a.coverage[..6], [0, 255, 0, 255, 0, 255]);
+        let dash = font.glyph('-').unwrap();
+        assert_eq!((dash.width, dash.height, dash.left, dash.top), (2, 1, 1, 3));
+        assert_eq!(dash.coverage, [255, 255]);
+        // Missing characters show the default one
+        assert_eq!(font.glyph('0'), Some(a));
+    }
+
+    #[test]
+    fn parses_fonts_in_every_format() {
+        let formats = [
+            0,
+            BYTE_ORDER_MSB | BIT_ORDER_MSB,
+            // Padded rows in 32 bit units of swapped bytes
+            BYTE_ORDER_MSB | 0b10 | 0b10 << 4,
+            BIT_ORDER_MSB | 0b10 | 0b10 << 4,
+            BIT_ORDER_MSB | 0b01 | 0b01 << 4,
+        ];
+        for format in formats {
+            for compressed in [true, false] {
+                let font = parse(&test_font(compressed, format))
+                    .unwrap_or_else(|e| panic!("format {:#x}: {}", format, e));
+                check(&font);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_broken_fonts() {
+        let font = test_font(true, 0);
+        assert!(parse(&font[..font.len() - 10]).is_err());
+        assert!(parse(&font[..40]).is_err());
+        let mut font = test_font(true, 0);
+        // Encode `-` as a glyph that doesn't exist
+        let encodings = font.len() - 2 * 21;
+        font[encodings] = 7;
+        assert!(parse(&font).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! Fonts imported by the user, for text drawn on the computer.
+//!
+//! Imported fonts are copied to the app data directory. Text in one of them is
+//! laid out at the panel size by [`font::render`] and sent as an image, so it
+//! is color corrected like any other image.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::ipc::{Request, Response};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::warn;
+
+use crate::font::{self, Align, Font, TextStyle};
+use crate::imaging::{self, ColorTransform};
+use crate::ipixel::protocol::Rgb;
+use crate::panel::commands::{header, panel_api, raw_body, FILE_NAME_HEADER};
+use crate::panel::types::ApiResponse;
+use crate::panel::{ApiError, PanelApi};
+
+const FONTS_DIR_NAME: &str = "fonts";
+/// Larger files are not fonts meant for a few dozen pixels
+const MAX_FONT_BYTES: usize = 32 * 1024 * 1024;
+
+/// An imported font
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FontInfo {
+    /// File name, which the font is selected by
+    pub name: String,
+    pub scalable: bool,
+    /// Line height of bitmap fonts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
+}
+
+impl FontInfo {
+    fn new(name: String, font: &Font) -> Self {
+        Self {
+            name,
+            scalable: font.is_scalable(),
+            size: font.bitmap_size(),
+        }
+    }
+}
+
+/// Fonts imported in the app data directory
+#[derive(Clone)]
+pub struct FontLibrary {
+    dir: PathBuf,
+}
+
+impl FontLibrary {
+    pub fn new(data_dir: &Path) -> Self {
+        let dir = data_dir.join(FONTS_DIR_NAME);
+        let _ = fs::create_dir_all(&dir);
+        Self { dir }
+    }
+
+    /// Path of a font, rejecting names that would point out of the library
+    fn path(&self, name: &str) -> Result<PathBuf, ApiError> {
+        let is_file_name = Path::new(name).file_name() == Some(name.as_ref());
+        if !is_file_name || name.starts_with('.') {
+            return Err(ApiError::Invalid(format!("Invalid font name: {}", name)));
+        }
+        Ok(self.dir.join(name))
+    }
+
+    /// Imported fonts by name, skipping the files that are no longer valid
+    pub fn list(&self) -> Vec<FontInfo> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut fonts: Vec<FontInfo> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                match self.load(&name) {
+                    Ok(font) => Some(FontInfo::new(name, &font)),
+                    Err(e) => {
+                        warn!(error = %e, font = %name, "Ignoring invalid font");
+                        None
+                    }
+                }
+            })
+            .collect();
+        fonts.sort_by(|a, b| a.name.cmp(&b.name));
+        fonts
+    }
+
+    /// Copy a font to the library, replacing the one of the same name
+    pub fn import(&self, file_name: &str, data: &[u8]) -> Result<FontInfo, ApiError> {
+        let name = Path::new(file_name)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let path = self.path(&name)?;
+        if data.len() > MAX_FONT_BYTES {
+            return Err(ApiError::Invalid(format!(
+                "Fonts are at most {} MiB",
+                MAX_FONT_BYTES / 1024 / 1024
+            )));
+        }
+        let font = Font::load(data)?;
+        fs::write(path, data).map_err(|e| ApiError::Storage(e.to_string()))?;
+        Ok(FontInfo::new(name, &font))
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), ApiError> {
+        match fs::remove_file(self.path(name)?) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(ApiError::Storage(e.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn load(&self, name: &str) -> Result<Font, ApiError> {
+        let data = fs::read(self.path(name)?).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => ApiError::Invalid(format!("Unknown font: {}", name)),
+            _ => ApiError::Storage(e.to_string()),
+        })?;
+        Ok(Font::load(&data)?)
+    }
+}
+
+/// Text to draw in an imported font
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderTextRequest {
+    pub text: String,
+    /// Name of the font in the library
+    pub font: String,
+    /// Line height in pixels, as large as fits the panel when missing
+    #[serde(default)]
+    pub size: Option<f32>,
+    #[serde(default)]
+    pub antialias: Option<bool>,
+    /// `RRGGBB`, white when missing
+    #[serde(default)]
+    pub color: Option<String>,
+    /// `RRGGBB`, black when missing
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default)]
+    pub align: Align,
+    #[serde(default)]
+    pub wrap: bool,
+}
+
+impl RenderTextRequest {
+    fn style(&self) -> Result<TextStyle, ApiError> {
+        let color = |hex: &Option<String>, default: Rgb| match hex {
+            Some(hex) => Rgb::from_hex(hex).map_err(|e| ApiError::Invalid(e.to_string())),
+            None => Ok(default),
+        };
+        let defaults = TextStyle::default();
+        Ok(TextStyle {
+            size: self.size,
+            antialias: self.antialias.unwrap_or(defaults.antialias),
+            color: color(&self.color, defaults.color)?,
+            background: color(&self.background, defaults.background)?,
+            align: self.align,
+            wrap: self.wrap,
+        })
+    }
+}
+
+/// Draw text as a PNG of the size of the connected panel, with its colors
+/// corrected for it
+pub fn render_for_panel(
+    api: &dyn PanelApi,
+    font: &Font,
+    text: &str,
+    style: &TextStyle,
+) -> Result<Vec<u8>, ApiError> {
+    let info = api.device_info()?;
+    let mut image = font::render(font, text, info.width, info.height, style)?;
+    ColorTransform::new(&api.color_profile(&info)?).apply_image(&mut image);
+    Ok(imaging::encode_png(&image)?)
+}
+
+/// Run a blocking call on the font library, off the async runtime
+async fn blocking<R, T, F>(app: &AppHandle<R>, call: F) -> Result<T, ApiError>
+where
+    R: Runtime,
+    T: Send + 'static,
+    F: FnOnce(&FontLibrary) -> Result<T, ApiError> + Send + 'static,
+{
+    let library = app.state::<FontLibrary>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || call(&library))
+        .await
+        .map_err(|e| ApiError::Unreachable(e.to_string()))?
+}
+
+#[tauri::command]
+pub async fn list_fonts<R: Runtime>(app: AppHandle<R>) -> Result<Vec<FontInfo>, ApiError> {
+    blocking(&app, |library| Ok(library.list())).await
+}
+
+/// Import a font file, sent as the raw invoke body with its file name in the
+/// `x-file-name` header
+#[tauri::command]
+pub async fn import_font<R: Runtime>(
+    app: AppHandle<R>,
+    request: Request<'_>,
+) -> Result<FontInfo, ApiError> {
+    let data = raw_body(&request)?;
+    let file_name = header(&request, FILE_NAME_HEADER)
+        .ok_or_else(|| ApiError::Invalid("Missing font file name".to_string()))?
+        .to_string();
+    blocking(&app, move |library| library.import(&file_name, &data)).await
+}
+
+#[tauri::command]
+pub async fn remove_font<R: Runtime>(app: AppHandle<R>, name: String) -> Result<(), ApiError> {
+    blocking(&app, move |library| library.remove(&name)).await
+}
+
+/// Draw text in an imported font the way `send_rendered_text` would, e.g. for
+/// a preview. Returns the PNG as raw bytes.
+#[tauri::command]
+pub async fn render_text<R: Runtime>(
+    app: AppHandle<R>,
+    request: RenderTextRequest,
+) -> Result<Response, ApiError> {
+    let api = panel_api(&app)?;
+    blocking(&app, move |library| {
+        let font = library.load(&request.font)?;
+        render_for_panel(api.as_ref(), &font, &request.text, &request.style()?)
+    })
+    .await
+    .map(Response::new)
+}
+
+/// Show text in an imported font, sent to the panel as an image
+#[tauri::command]
+pub async fn send_rendered_text<R: Runtime>(
+    app: AppHandle<R>,
+    request: RenderTextRequest,
+) -> Result<ApiResponse, ApiError> {
+    let api = panel_api(&app)?;
+    blocking(&app, move |library| {
+        let font = library.load(&request.font)?;
+        let png = render_for_panel(api.as_ref(), &font, &request.text, &request.style()?)?;
+        api.send_image("text.png", &png)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BDF_FONT: &str = "\
+STARTFONT 2.1
+FONTBOUNDINGBOX 1 1 0 0
+CHARS 1
+STARTCHAR period
+ENCODING 46
+DWIDTH 2 0
+BBX 1 1 0 0
+BITMAP
+80
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn imports_fonts() {
+        let dir = std::env::temp_dir().join(format!("font-library-test-{}", std::process::id()));
+        let library = FontLibrary::new(&dir);
+
+        let info = library
+            .import("/some/where/dot.bdf", BDF_FONT.as_bytes())
+            .unwrap();
+        assert_eq!(
+            info,
+            FontInfo {
+                name: "dot.bdf".to_string(),
+                scalable: false,
+                size: Some(1),
+            }
+        );
+        assert!(library.import("bad.ttf", b"not a font").is_err());
+        fs::write(dir.join(FONTS_DIR_NAME).join("stale.bdf"), "STARTFONT").unwrap();
+        assert_eq!(library.list(), [info]);
+
+        assert!(library.load("dot.bdf").is_ok());
+        for name in ["../calibration.json", ".", "", "a/b.bdf"] {
+            assert!(library.load(name).is_err(), "{}", name);
+        }
+        library.remove("dot.bdf").unwrap();
+        assert!(matches!(library.load("dot.bdf"), Err(ApiError::Invalid(_))));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_render_requests() {
+        let request: RenderTextRequest = serde_json::from_str(
+            r#"{"text": "Hi", "font": "dot.bdf", "color": "FF0000", "align": "left"}"#,
+        )
+        .unwrap();
+        let style = request.style().unwrap();
+        assert_eq!(style.color, Rgb(255, 0, 0));
+        assert_eq!(style.background, Rgb(0, 0, 0));
+        assert_eq!(style.align, Align::Left);
+        assert!(style.antialias && !style.wrap);
+    }
+}
@@ -0,0 +1,612 @@
+//! Animations converted for the panel.
+//!
+//! GIF, APNG and animated WebP files are decoded frame by frame, each frame
+//! converted like a still image, and the result re-encoded as a GIF, the only
+//! animation format the panel plays. Uploads are slow over Bluetooth and the
+//! panel memory is small: the GIF is then reduced until it fits a [`Budget`].
+
+use std::io::Cursor;
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{
+    AnimationDecoder, Delay, DynamicImage, Frames, ImageDecoder, Limits, RgbImage, RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    check, flatten, process, ConvertOptions, Dither, ImageError, Palette, MAX_INPUT_DIMENSION,
+};
+use crate::ipixel::protocol::{Rgb, UPLOAD_CHUNK_SIZE};
+
+/// Most frames decoded from a file
+pub const MAX_FRAMES: usize = 1024;
+/// Shortest frame delay sent, the panel doesn't redraw faster
+pub const MIN_DELAY_MS: u32 = 20;
+/// Longest frame delay a GIF can hold (65535 hundredths of a second)
+pub const MAX_DELAY_MS: u32 = 655_350;
+/// Delay of the frames that have none. Browsers play frames of 10 ms or less
+/// at this speed, and files are made to look right in browsers.
+const DEFAULT_DELAY_MS: u32 = 100;
+/// GIF delays are counted in hundredths of a second
+const DELAY_UNIT_MS: u32 = 10;
+/// Fewest colors the budget reduces an animation to before dropping frames
+const MIN_BUDGET_COLORS: u16 = 16;
+/// Bytes added to every upload frame: length, command, flag, size, CRC and slot
+const UPLOAD_FRAME_OVERHEAD: usize = 14;
+
+/// Limits of an upload to the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Budget {
+    /// Largest file the panel accepts
+    pub max_bytes: usize,
+    /// Measured Bluetooth throughput, for the upload time estimate
+    pub bytes_per_second: usize,
+}
+
+impl Default for Budget {
+    /// Safe for the smallest panels: 64 KiB, at the ~5 KB/s of 244 byte
+    /// writes acknowledged one by one
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024,
+            bytes_per_second: 5_000,
+        }
+    }
+}
+
+impl Budget {
+    /// Estimated time to upload `bytes`, framing included
+    pub fn upload_ms(&self, bytes: usize) -> u64 {
+        let frames = bytes.div_ceil(UPLOAD_CHUNK_SIZE);
+        let sent = (bytes + frames * UPLOAD_FRAME_OVERHEAD) as u64;
+        sent * 1000 / self.bytes_per_second.max(1) as u64
+    }
+}
+
+/// What a conversion produced, shown before uploading
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UploadReport {
+    /// Frames in the file
+    pub source_frames: usize,
+    /// Frames sent, after merging duplicates and fitting the budget
+    pub frames: usize,
+    /// Colors the budget reduced the animation to, if it had to
+    pub reduced_colors: Option<u16>,
+    pub bytes: usize,
+    /// Estimated upload time
+    pub upload_ms: u64,
+    /// Length of one loop of the animation, 0 for a still image
+    pub duration_ms: u64,
+}
+
+impl UploadReport {
+    /// Report of a still image, an error when it is over the budget
+    pub fn still(bytes: usize, budget: &Budget) -> Result<Self, ImageError> {
+        if bytes > budget.max_bytes {
+            return Err(ImageError::OverBudget {
+                bytes,
+                max_bytes: budget.max_bytes,
+            });
+        }
+        Ok(Self {
+            source_frames: 1,
+            frames: 1,
+            reduced_colors: None,
+            bytes,
+            upload_ms: budget.upload_ms(bytes),
+            duration_ms: 0,
+        })
+    }
+}
+
+/// One converted frame and how long it is shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub image: RgbImage,
+    pub delay_ms: u32,
+}
+
+/// Whether the data is a GIF, an APNG or an animated WebP. GIFs always are,
+/// the panel shows them with its animation command even with one frame.
+pub fn is_animation(data: &[u8]) -> bool {
+    data.starts_with(b"GIF8") || is_apng(data) || is_animated_webp(data)
+}
+
+/// PNG with an animation control chunk, which comes before the image data
+fn is_apng(data: &[u8]) -> bool {
+    let Some(mut chunks) = data.strip_prefix(b"\x89PNG\r\n\x1a\n") else {
+        return false;
+    };
+    while chunks.len() >= 8 {
+        let length = u32::from_be_bytes([chunks[0], chunks[1], chunks[2], chunks[3]]) as usize;
+        match &chunks[4..8] {
+            b"acTL" => return true,
+            b"IDAT" => return false,
+            _ => {}
+        }
+        // Length, type, data and CRC
+        let Some(rest) = chunks.get(12 + length..) else {
+            return false;
+        };
+        chunks = rest;
+    }
+    false
+}
+
+/// Extended WebP with the animation flag set
+fn is_animated_webp(data: &[u8]) -> bool {
+    data.len() > 20 && &data[..4] == b"RIFF" && &data[8..16] == b"WEBPVP8X" && data[20] & 0x02 != 0
+}
+
+fn limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_INPUT_DIMENSION);
+    limits.max_image_height = Some(MAX_INPUT_DIMENSION);
+    limits
+}
+
+/// Decoded frames of an animation, composited on the full canvas
+fn decode(data: &[u8]) -> Result<Frames<'_>, ImageError> {
+    let error = |e: image::ImageError| ImageError::Decode(e.to_string());
+    if data.starts_with(b"GIF8") {
+        let mut decoder = GifDecoder::new(Cursor::new(data)).map_err(error)?;
+        decoder.set_limits(limits()).map_err(error)?;
+        Ok(decoder.into_frames())
+    } else if is_apng(data) {
+        let mut decoder = PngDecoder::new(Cursor::new(data)).map_err(error)?;
+        decoder.set_limits(limits()).map_err(error)?;
+        Ok(decoder.apng().map_err(error)?.into_frames())
+    } else if is_animated_webp(data) {
+        let mut decoder = WebPDecoder::new(Cursor::new(data)).map_err(error)?;
+        decoder.set_limits(limits()).map_err(error)?;
+        Ok(decoder.into_frames())
+    } else {
+        Err(ImageError::UnsupportedFormat)
+    }
+}
+
+/// Delay sent for a frame delay read from a file
+pub fn normalize_delay(delay: Delay) -> u32 {
+    let (numerator, denominator) = delay.numer_denom_ms();
+    let ms = numerator / denominator.max(1);
+    if ms <= DELAY_UNIT_MS {
+        return DEFAULT_DELAY_MS;
+    }
+    let rounded = (ms + DELAY_UNIT_MS / 2) / DELAY_UNIT_MS * DELAY_UNIT_MS;
+    rounded.clamp(MIN_DELAY_MS, MAX_DELAY_MS)
+}
+
+/// Decode every frame of the animation, converting each as soon as it is
+/// decoded so only one is kept at the size of the file
+fn each_frame(
+    data: &[u8],
+    mut convert: impl FnMut(RgbaImage) -> Result<RgbImage, ImageError>,
+) -> Result<Vec<Frame>, ImageError> {
+    let mut frames = Vec::new();
+    for frame in decode(data)? {
+        if frames.len() == MAX_FRAMES {
+            return Err(ImageError::TooManyFrames(MAX_FRAMES));
+        }
+        let frame = frame.map_err(|e| ImageError::Decode(e.to_string()))?;
+        let delay_ms = normalize_delay(frame.delay());
+        let image = convert(frame.into_buffer())?;
+        frames.push(Frame { image, delay_ms });
+    }
+    if frames.is_empty() {
+        return Err(ImageError::Decode(
+            "the animation has no frames".to_string(),
+        ));
+    }
+    Ok(frames)
+}
+
+/// Decode the animation and resize every frame to the panel, without
+/// reducing its colors yet
+pub fn frames(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Result<Vec<Frame>, ImageError> {
+    let full_color = ConvertOptions {
+        palette: Palette::Full,
+        dither: Dither::None,
+        ..options.clone()
+    };
+    each_frame(data, |image| process(&image, width, height, &full_color))
+}
+
+/// Decode the frames of an animation as they are, on a black background
+pub fn decode_frames(data: &[u8]) -> Result<Vec<Frame>, ImageError> {
+    each_frame(data, |image| Ok(flatten(&image, Rgb::default())))
+}
+
+/// Merge consecutive identical frames, which resizing often produces. The
+/// merged frame is shown as long as the ones it replaces.
+pub fn merge_duplicates(frames: Vec<Frame>) -> Vec<Frame> {
+    let mut merged: Vec<Frame> = Vec::with_capacity(frames.len());
+    for frame in frames {
+        match merged.last_mut() {
+            Some(last) if last.image == frame.image => {
+                last.delay_ms = (last.delay_ms + frame.delay_ms).min(MAX_DELAY_MS);
+            }
+            _ => merged.push(frame),
+        }
+    }
+    merged
+}
+
+/// Drop every other frame, the ones kept are shown longer so the animation
+/// runs at the same speed
+pub fn halve_frames(frames: &[Frame]) -> Vec<Frame> {
+    frames
+        .chunks(2)
+        .map(|pair| Frame {
+            image: pair[0].image.clone(),
+            delay_ms: pair
+                .iter()
+                .map(|frame| frame.delay_ms)
+                .sum::<u32>()
+                .min(MAX_DELAY_MS),
+        })
+        .collect()
+}
+
+/// Reduce all the frames to one palette, so colors don't flicker from one
+/// frame to the next
+fn reduce(frames: &[Frame], palette: &Palette, dither: Dither) -> Vec<Frame> {
+    let Some(first) = frames.first() else {
+        return Vec::new();
+    };
+    let (width, height) = first.image.dimensions();
+    // The palette is picked from all the frames at once, stacked vertically
+    let mut stacked = RgbImage::new(width, height * frames.len() as u32);
+    for (index, frame) in frames.iter().enumerate() {
+        image::imageops::replace(&mut stacked, &frame.image, 0, height as i64 * index as i64);
+    }
+    let Some(colors) = palette.resolve(&stacked) else {
+        return frames.to_vec();
+    };
+    frames
+        .iter()
+        .map(|frame| Frame {
+            image: dither.apply(&frame.image, &colors),
+            delay_ms: frame.delay_ms,
+        })
+        .collect()
+}
+
+/// Encode the frames as a GIF looping forever
+pub fn encode_gif(frames: &[Frame]) -> Result<Vec<u8>, ImageError> {
+    let error = |e: image::ImageError| ImageError::Encode(e.to_string());
+    let mut gif = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut gif, 10);
+        encoder.set_repeat(Repeat::Infinite).map_err(error)?;
+        for frame in frames {
+            let rgba = DynamicImage::ImageRgb8(frame.image.clone()).into_rgba8();
+            let delay = Delay::from_numer_denom_ms(frame.delay_ms, 1);
+            encoder
+                .encode_frame(image::Frame::from_parts(rgba, 0, 0, delay))
+                .map_err(error)?;
+        }
+    }
+    Ok(gif)
+}
+
+/// How an animation over budget is made smaller
+enum Reduction {
+    Colors(Palette),
+    Frames,
+}
+
+/// Next reduction when the GIF is over budget: fewer colors first, as long as
+/// the user didn't pick the palette, then fewer frames
+fn next_reduction(palette: &Palette, picked: bool, frames: usize) -> Option<Reduction> {
+    match palette {
+        _ if picked => {}
+        Palette::Full => return Some(Reduction::Colors(Palette::Colors(256))),
+        Palette::Colors(count) if *count > MIN_BUDGET_COLORS => {
+            return Some(Reduction::Colors(Palette::Colors(
+                (count / 2).max(MIN_BUDGET_COLORS),
+            )))
+        }
+        _ => {}
+    }
+    (frames > 1).then_some(Reduction::Frames)
+}
+
+/// Encode the frames as a GIF within the budget, reducing colors then frames
+/// until it fits
+pub fn fit(
+    frames: Vec<Frame>,
+    options: &ConvertOptions,
+) -> Result<(Vec<u8>, UploadReport), ImageError> {
+    let budget = options.budget;
+    let source_frames = frames.len();
+    let mut frames = merge_duplicates(frames);
+    let mut palette = options.palette.clone();
+    let picked = palette != Palette::Full;
+    loop {
+        let gif = encode_gif(&reduce(&frames, &palette, options.dither))?;
+        if gif.len() <= budget.max_bytes {
+            let report = UploadReport {
+                source_frames,
+                frames: frames.len(),
+                reduced_colors: match palette {
+                    Palette::Colors(count) if palette != options.palette => Some(count),
+                    _ => None,
+                },
+                bytes: gif.len(),
+                upload_ms: budget.upload_ms(gif.len()),
+                duration_ms: frames.iter().map(|frame| frame.delay_ms as u64).sum(),
+            };
+            return Ok((gif, report));
+        }
+        match next_reduction(&palette, picked, frames.len()) {
+            Some(Reduction::Colors(fewer)) => palette = fewer,
+            Some(Reduction::Frames) => frames = halve_frames(&frames),
+            None => {
+                return Err(ImageError::OverBudget {
+                    bytes: gif.len(),
+                    max_bytes: budget.max_bytes,
+                })
+            }
+        }
+    }
+}
+
+/// Convert an animation to a GIF of exactly `width` x `height` pixels within
+/// the budget of the options
+pub fn convert(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Result<(Vec<u8>, UploadReport), ImageError> {
+    check(width, height, options)?;
+    fit(frames(data, width, height, options)?, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::png::PngEncoder;
+    use image::codecs::webp::WebPEncoder;
+    use image::ImageEncoder;
+
+    /// Red square moving right over black, one frame per position
+    fn sliding(width: u32, frames: u32) -> Vec<image::Frame> {
+        (0..frames)
+            .map(|index| {
+                let image = RgbaImage::from_fn(width, width, |x, y| {
+                    if x / 4 == index % (width / 4) && y < 4 {
+                        image::Rgba([255, 0, 0, 255])
+                    } else {
+                        image::Rgba([0, 0, 0, 255])
+                    }
+                });
+                image::Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(50, 1))
+            })
+            .collect()
+    }
+
+    fn gif(frames: Vec<image::Frame>) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            encoder.encode_frames(frames).unwrap();
+        }
+        data
+    }
+
+    fn frame(color: u8, delay_ms: u32) -> Frame {
+        Frame {
+            image: RgbImage::from_pixel(4, 4, image::Rgb([color; 3])),
+            delay_ms,
+        }
+    }
+
+    #[test]
+    fn detects_animations() {
+        assert!(is_animation(&gif(sliding(16, 2))));
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(&[0; 4], 1, 1, image::ExtendedColorType::Rgba8)
+            .unwrap();
+        assert!(!is_animation(&png));
+        let mut webp = Vec::new();
+        WebPEncoder::new_lossless(&mut webp)
+            .write_image(&[0; 4], 1, 1, image::ExtendedColorType::Rgba8)
+            .unwrap();
+        assert!(!is_animation(&webp));
+        assert!(!is_animation(b"not an image"));
+    }
+
+    #[test]
+    fn detects_apng() {
+        // Signature, IHDR then acTL: 1 frame, loop forever
+        let mut apng = b"\x89PNG\r\n\x1a\n".to_vec();
+        apng.extend_from_slice(&[0, 0, 0, 13]);
+        apng.extend_from_slice(b"IHDR");
+        apng.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        apng.extend_from_slice(&[0; 4]);
+        apng.extend_from_slice(&[0, 0, 0, 8]);
+        apng.extend_from_slice(b"acTL");
+        apng.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0]);
+        assert!(is_apng(&apng));
+        assert!(!is_apng(&apng[..33]));
+    }
+
+    #[test]
+    fn resizes_every_frame_to_the_panel() {
+        let data = gif(sliding(64, 4));
+        let (output, report) = convert(&data, 16, 16, &ConvertOptions::default()).unwrap();
+        assert_eq!(report.source_frames, 4);
+        assert_eq!(report.frames, 4);
+        assert_eq!(report.duration_ms, 200);
+
+        let decoded = GifDecoder::new(Cursor::new(&output))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(decoded.len(), 4);
+        for frame in &decoded {
+            assert_eq!(frame.buffer().dimensions(), (16, 16));
+            assert_eq!(frame.delay().numer_denom_ms(), (50, 1));
+        }
+    }
+
+    #[test]
+    fn merges_duplicate_frames() {
+        let frames = vec![frame(0, 50), frame(0, 50), frame(255, 100), frame(0, 30)];
+        let merged = merge_duplicates(frames);
+        assert_eq!(
+            merged.iter().map(|f| f.delay_ms).collect::<Vec<_>>(),
+            [100, 100, 30]
+        );
+    }
+
+    #[test]
+    fn normalizes_delays() {
+        let delay = |ms| normalize_delay(Delay::from_numer_denom_ms(ms, 1));
+        assert_eq!(delay(0), DEFAULT_DELAY_MS);
+        assert_eq!(delay(10), DEFAULT_DELAY_MS);
+        assert_eq!(delay(14), MIN_DELAY_MS);
+        assert_eq!(delay(44), 40);
+        assert_eq!(delay(45), 50);
+        assert_eq!(delay(1_000_000), MAX_DELAY_MS);
+    }
+
+    #[test]
+    fn halving_keeps_the_speed() {
+        let frames = vec![frame(0, 50), frame(85, 50), frame(170, 60)];
+        let halved = halve_frames(&frames);
+        assert_eq!(halved.len(), 2);
+        assert_eq!(halved[0].delay_ms, 100);
+        assert_eq!(halved[1], frames[2]);
+    }
+
+    #[test]
+    fn fits_the_budget() {
+        // Noise compresses badly: 16 frames of it are far over 2 KiB
+        let mut seed = 1u32;
+        let frames: Vec<Frame> = (0..16)
+            .map(|_| Frame {
+                image: RgbImage::from_fn(16, 16, |_, _| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    image::Rgb(seed.to_be_bytes()[..3].try_into().unwrap())
+                }),
+                delay_ms: 50,
+            })
+            .collect();
+        let options = ConvertOptions {
+            budget: Budget {
+                max_bytes: 2 * 1024,
+                ..Budget::default()
+            },
+            ..ConvertOptions::default()
+        };
+        let (gif, report) = fit(frames.clone(), &options).unwrap();
+        assert!(gif.len() <= 2 * 1024, "{}", gif.len());
+        assert_eq!(report.bytes, gif.len());
+        assert_eq!(report.reduced_colors, Some(MIN_BUDGET_COLORS));
+        assert!(report.frames < 16);
+        assert_eq!(report.duration_ms, 16 * 50);
+
+        let options = ConvertOptions {
+            budget: Budget {
+                max_bytes: 64,
+                ..Budget::default()
+            },
+            ..ConvertOptions::default()
+        };
+        assert!(matches!(
+            fit(frames.clone(), &options),
+            Err(ImageError::OverBudget { max_bytes: 64, .. })
+        ));
+
+        // A palette picked by the user is kept, only frames are dropped
+        let options = ConvertOptions {
+            palette: Palette::Colors(64),
+            budget: Budget {
+                max_bytes: 4 * 1024,
+                ..Budget::default()
+            },
+            ..ConvertOptions::default()
+        };
+        let (gif, report) = fit(frames, &options).unwrap();
+        assert!(gif.len() <= 4 * 1024, "{}", gif.len());
+        assert_eq!(report.reduced_colors, None);
+        assert!(report.frames < 16);
+    }
+
+    #[test]
+    fn keeps_picked_palettes() {
+        assert!(matches!(
+            next_reduction(&Palette::Colors(64), true, 4),
+            Some(Reduction::Frames)
+        ));
+        assert!(next_reduction(&Palette::Colors(64), true, 1).is_none());
+        assert!(matches!(
+            next_reduction(&Palette::Colors(256), false, 4),
+            Some(Reduction::Colors(Palette::Colors(128)))
+        ));
+    }
+
+    #[test]
+    fn estimates_the_upload_time() {
+        let budget = Budget {
+            max_bytes: 64 * 1024,
+            bytes_per_second: 1_000,
+        };
+        assert_eq!(budget.upload_ms(986), 1_000);
+        assert_eq!(budget.upload_ms(UPLOAD_CHUNK_SIZE + 1), 12_317);
+    }
+
+    #[test]
+    fn converts_animated_webp() {
+        // The WebP encoder has no animation, an animated file is built from
+        // its chunks: VP8X with the animation flag, ANIM, then one ANMF
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still)
+            .write_image(
+                &[255, 0, 0, 255].repeat(4),
+                2,
+                2,
+                image::ExtendedColorType::Rgba8,
+            )
+            .unwrap();
+        let vp8l = &still[12..];
+
+        let chunk = |name: &[u8], payload: &[u8]| {
+            let mut chunk = name.to_vec();
+            chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            chunk.extend_from_slice(payload);
+            if payload.len() % 2 == 1 {
+                chunk.push(0);
+            }
+            chunk
+        };
+        let mut anmf = vec![0; 6];
+        anmf.extend_from_slice(&[1, 0, 0, 1, 0, 0]);
+        anmf.extend_from_slice(&[100, 0, 0, 0]);
+        anmf.extend_from_slice(vp8l);
+        let mut body = b"WEBP".to_vec();
+        body.extend(chunk(b"VP8X", &[0x12, 0, 0, 0, 1, 0, 0, 1, 0, 0]));
+        body.extend(chunk(b"ANIM", &[0, 0, 0, 0, 0, 0]));
+        body.extend(chunk(b"ANMF", &anmf));
+        let webp = chunk(b"RIFF", &body);
+
+        assert!(is_animation(&webp));
+        let (gif, report) = convert(&webp, 8, 8, &ConvertOptions::default()).unwrap();
+        assert_eq!(report.frames, 1);
+        let decoded = image::load_from_memory(&gif).unwrap().into_rgb8();
+        assert_eq!(decoded.dimensions(), (8, 8));
+        let [r, g, b] = decoded.get_pixel(4, 4).0;
+        assert!(r > 240 && g < 16 && b < 16, "{:?}", (r, g, b));
+    }
+}
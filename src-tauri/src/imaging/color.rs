@@ -0,0 +1,277 @@
+//! Color calibration of what is sent to the panel.
+//!
+//! Colors are picked in sRGB, but the LED drivers are linear: sent as they
+//! are, mid-tones come out too bright and washed out. A [`ColorProfile`]
+//! applies a gamma curve, then a white balance matrix correcting the tint of
+//! the LEDs, to every outgoing color. Images get it while they are converted,
+//! before their palette is picked.
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+use super::ImageError;
+use crate::ipixel::protocol::Rgb;
+
+/// Accepted gamma range, 1 sends the colors unchanged
+pub const GAMMA_RANGE: std::ops::RangeInclusive<f32> = 1.0..=3.0;
+/// Accepted range of the white balance coefficients
+pub const WHITE_BALANCE_RANGE: std::ops::RangeInclusive<f32> = -1.0..=2.0;
+
+const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// How colors are corrected for a panel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorProfile {
+    /// Exponent turning color values into LED drive levels
+    pub gamma: f32,
+    /// Applied to the linear colors, one row per LED channel: the red LED is
+    /// driven at `white_balance[0] · [r, g, b]`
+    pub white_balance: [[f32; 3]; 3],
+}
+
+/// Panels that were never calibrated get their colors unchanged, the LED
+/// drivers differ too much between models for a guess to help
+impl Default for ColorProfile {
+    fn default() -> Self {
+        Self::UNCHANGED
+    }
+}
+
+impl ColorProfile {
+    /// Profile sending the colors unchanged
+    pub const UNCHANGED: ColorProfile = ColorProfile {
+        gamma: 1.0,
+        white_balance: IDENTITY,
+    };
+
+    pub fn validate(&self) -> Result<(), ImageError> {
+        if !GAMMA_RANGE.contains(&self.gamma) {
+            return Err(ImageError::InvalidColorProfile(format!(
+                "gamma must be between {} and {}, got {}",
+                GAMMA_RANGE.start(),
+                GAMMA_RANGE.end(),
+                self.gamma
+            )));
+        }
+        if let Some(value) = self
+            .white_balance
+            .iter()
+            .flatten()
+            .find(|value| !WHITE_BALANCE_RANGE.contains(value))
+        {
+            return Err(ImageError::InvalidColorProfile(format!(
+                "white balance coefficients must be between {} and {}, got {}",
+                WHITE_BALANCE_RANGE.start(),
+                WHITE_BALANCE_RANGE.end(),
+                value
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A [`ColorProfile`] ready to be applied to many colors
+pub struct ColorTransform {
+    /// Linear value of each 8 bit value
+    linear: [f32; 256],
+    white_balance: [[f32; 3]; 3],
+    unchanged: bool,
+}
+
+impl ColorTransform {
+    pub fn new(profile: &ColorProfile) -> Self {
+        Self {
+            linear: std::array::from_fn(|value| (value as f32 / 255.0).powf(profile.gamma)),
+            white_balance: profile.white_balance,
+            unchanged: *profile == ColorProfile::UNCHANGED,
+        }
+    }
+
+    /// Whether colors go through unchanged, so there's nothing to apply
+    pub fn is_unchanged(&self) -> bool {
+        self.unchanged
+    }
+
+    pub fn apply(&self, color: [u8; 3]) -> [u8; 3] {
+        if self.unchanged {
+            return color;
+        }
+        let linear = color.map(|value| self.linear[value as usize]);
+        self.white_balance.map(|row| {
+            let level: f32 = row.iter().zip(linear).map(|(k, value)| k * value).sum();
+            let value = (level.clamp(0.0, 1.0) * 255.0).round() as u8;
+            // Dark colors would round to off, keep them lit
+            if level > 0.0 {
+                value.max(1)
+            } else {
+                value
+            }
+        })
+    }
+
+    pub fn apply_rgb(&self, Rgb(r, g, b): Rgb) -> Rgb {
+        let [r, g, b] = self.apply([r, g, b]);
+        Rgb(r, g, b)
+    }
+
+    pub fn apply_image(&self, image: &mut RgbImage) {
+        if self.unchanged {
+            return;
+        }
+        for pixel in image.pixels_mut() {
+            pixel.0 = self.apply(pixel.0);
+        }
+    }
+}
+
+/// Images for tuning a profile on the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestPattern {
+    /// Gray, red, green and blue ramps, to tune the gamma
+    #[default]
+    Ramps,
+    /// White, the primaries and their mixes, to tune the white balance
+    ColorBars,
+    /// Skin tones from light to dark over a mid gray, the first to show a tint
+    SkinTones,
+    /// Full white, which should not look tinted
+    White,
+}
+
+/// Skin tones of the [`TestPattern::SkinTones`] pattern
+const SKIN_TONES: [Rgb; 6] = [
+    Rgb(0xFF, 0xDB, 0xAC),
+    Rgb(0xF1, 0xC2, 0x7D),
+    Rgb(0xE0, 0xAC, 0x69),
+    Rgb(0xC6, 0x86, 0x42),
+    Rgb(0x8D, 0x55, 0x24),
+    Rgb(0x5C, 0x3A, 0x21),
+];
+
+/// Colors of the [`TestPattern::ColorBars`] pattern, from left to right
+const COLOR_BARS: [Rgb; 8] = [
+    Rgb(255, 255, 255),
+    Rgb(255, 255, 0),
+    Rgb(0, 255, 255),
+    Rgb(0, 255, 0),
+    Rgb(255, 0, 255),
+    Rgb(255, 0, 0),
+    Rgb(0, 0, 255),
+    Rgb(0, 0, 0),
+];
+
+impl TestPattern {
+    /// Render the pattern at the panel size
+    pub fn render(self, width: u32, height: u32) -> RgbImage {
+        let column = |x: u32, count: usize| x as usize * count / width.max(1) as usize;
+        let ramp = |x: u32| (x * 255 / width.saturating_sub(1).max(1)) as u8;
+        RgbImage::from_fn(width, height, |x, y| {
+            let Rgb(r, g, b) = match self {
+                TestPattern::Ramps => {
+                    let value = ramp(x);
+                    match y * 4 / height {
+                        0 => Rgb(value, value, value),
+                        1 => Rgb(value, 0, 0),
+                        2 => Rgb(0, value, 0),
+                        _ => Rgb(0, 0, value),
+                    }
+                }
+                TestPattern::ColorBars => COLOR_BARS[column(x, COLOR_BARS.len())],
+                // Gray on the bottom quarter, for reference
+                TestPattern::SkinTones if y >= height - height / 4 => Rgb(128, 128, 128),
+                TestPattern::SkinTones => SKIN_TONES[column(x, SKIN_TONES.len())],
+                TestPattern::White => Rgb(255, 255, 255),
+            };
+            image::Rgb([r, g, b])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_darkens_mid_tones() {
+        let transform = ColorTransform::new(&ColorProfile {
+            gamma: 2.2,
+            ..ColorProfile::UNCHANGED
+        });
+        assert_eq!(transform.apply([255, 128, 0]), [255, 56, 0]);
+        assert_eq!(transform.apply([64, 32, 16]), [12, 3, 1]);
+        // Too dark for 8 bits once linear, but not off
+        assert_eq!(transform.apply([8, 8, 8]), [1, 1, 1]);
+    }
+
+    #[test]
+    fn white_balance_mixes_the_channels() {
+        let profile = ColorProfile {
+            gamma: 1.0,
+            white_balance: [[1.0, 0.0, 0.0], [0.0, 0.8, 0.0], [0.0, 0.1, 1.0]],
+        };
+        let transform = ColorTransform::new(&profile);
+        assert!(!transform.is_unchanged());
+        assert_eq!(transform.apply([255, 255, 255]), [255, 204, 255]);
+        assert_eq!(transform.apply([0, 200, 0]), [0, 160, 20]);
+        assert_eq!(
+            transform.apply_rgb(Rgb(0x10, 0x20, 0x30)),
+            Rgb(0x10, 0x1A, 0x33)
+        );
+    }
+
+    #[test]
+    fn unchanged_profile_keeps_the_colors() {
+        let transform = ColorTransform::new(&ColorProfile::default());
+        assert!(transform.is_unchanged());
+        assert_eq!(transform.apply([12, 128, 250]), [12, 128, 250]);
+    }
+
+    #[test]
+    fn validates_profiles() {
+        assert!(ColorProfile::default().validate().is_ok());
+        let profile = ColorProfile {
+            gamma: 0.5,
+            ..ColorProfile::default()
+        };
+        assert!(profile.validate().is_err());
+        let profile = ColorProfile {
+            white_balance: [[1.0, 0.0, 0.0], [0.0, f32::NAN, 0.0], [0.0, 0.0, 1.0]],
+            ..ColorProfile::default()
+        };
+        assert!(profile.validate().is_err());
+        assert_eq!(
+            serde_json::from_str::<ColorProfile>(
+                r#"{"gamma": 1.8, "white_balance": [[1, 0, 0], [0, 0.9, 0], [0, 0, 1]]}"#
+            )
+            .unwrap()
+            .white_balance[1][1],
+            0.9
+        );
+    }
+
+    #[test]
+    fn renders_test_patterns() {
+        let ramps = TestPattern::Ramps.render(16, 8);
+        assert_eq!(ramps.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(ramps.get_pixel(15, 0).0, [255, 255, 255]);
+        assert_eq!(ramps.get_pixel(15, 2).0, [255, 0, 0]);
+        assert_eq!(ramps.get_pixel(15, 4).0, [0, 255, 0]);
+        assert_eq!(ramps.get_pixel(15, 7).0, [0, 0, 255]);
+
+        let bars = TestPattern::ColorBars.render(32, 8);
+        assert_eq!(bars.get_pixel(0, 0).0, [255, 255, 255]);
+        assert_eq!(bars.get_pixel(16, 0).0, [255, 0, 255]);
+        assert_eq!(bars.get_pixel(31, 7).0, [0, 0, 0]);
+
+        let skin = TestPattern::SkinTones.render(12, 16);
+        assert_eq!(skin.get_pixel(0, 0).0, [0xFF, 0xDB, 0xAC]);
+        assert_eq!(skin.get_pixel(11, 0).0, [0x5C, 0x3A, 0x21]);
+        assert_eq!(skin.get_pixel(5, 15).0, [128, 128, 128]);
+
+        assert!(TestPattern::White
+            .render(4, 4)
+            .pixels()
+            .all(|pixel| pixel.0 == [255; 3]));
+    }
+}
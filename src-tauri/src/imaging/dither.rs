@@ -0,0 +1,234 @@
+//! Dithering of an image to a palette.
+//!
+//! Mapping each pixel to the closest palette color flattens gradients into
+//! bands. Dithering mixes the colors around them instead, which the eye
+//! blends back at LED distances.
+
+use std::fmt;
+use std::str::FromStr;
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+use super::quantize::{nearest, remap};
+use crate::ipixel::protocol::Rgb;
+
+/// 4x4 Bayer threshold matrix of the ordered dithering
+const BAYER_4X4: [[i32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// How an image is mapped to a palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    /// Closest color, flat areas stay flat
+    #[default]
+    None,
+    /// Error diffusion to the four next pixels, smooth gradients
+    FloydSteinberg,
+    /// Error diffusion of three quarters of the error, more contrast
+    Atkinson,
+    /// Bayer pattern, stable between the frames of an animation
+    Ordered,
+}
+
+impl Dither {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::FloydSteinberg => "floyd-steinberg",
+            Dither::Atkinson => "atkinson",
+            Dither::Ordered => "ordered",
+        }
+    }
+
+    /// Map every pixel of the image to a palette color
+    pub fn apply(self, image: &RgbImage, palette: &[Rgb]) -> RgbImage {
+        if palette.is_empty() {
+            return image.clone();
+        }
+        match self {
+            Dither::None => remap(image, palette),
+            Dither::FloydSteinberg => diffuse(
+                image,
+                palette,
+                16,
+                &[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)],
+            ),
+            Dither::Atkinson => diffuse(
+                image,
+                palette,
+                8,
+                &[
+                    (1, 0, 1),
+                    (2, 0, 1),
+                    (-1, 1, 1),
+                    (0, 1, 1),
+                    (1, 1, 1),
+                    (0, 2, 1),
+                ],
+            ),
+            Dither::Ordered => ordered(image, palette),
+        }
+    }
+}
+
+impl fmt::Display for Dither {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(dither: &str) -> Result<Self, Self::Err> {
+        match dither {
+            "none" => Ok(Dither::None),
+            "floyd-steinberg" => Ok(Dither::FloydSteinberg),
+            "atkinson" => Ok(Dither::Atkinson),
+            "ordered" => Ok(Dither::Ordered),
+            other => Err(format!(
+                "Invalid dithering \"{}\" (expected none, floyd-steinberg, atkinson or ordered)",
+                other
+            )),
+        }
+    }
+}
+
+fn channels(color: Rgb) -> [i32; 3] {
+    [color.0 as i32, color.1 as i32, color.2 as i32]
+}
+
+fn clamp(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+/// Error diffusion: the difference between a pixel and its palette color is
+/// spread to the following pixels, `weight / divisor` each. Integer maths
+/// keep the output identical on every platform.
+fn diffuse(
+    image: &RgbImage,
+    palette: &[Rgb],
+    divisor: i32,
+    neighbours: &[(i32, i32, i32)],
+) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut work: Vec<[i32; 3]> = image
+        .pixels()
+        .map(|pixel| pixel.0.map(|channel| channel as i32))
+        .collect();
+    let mut output = RgbImage::new(width, height);
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let index = (y * width as i32 + x) as usize;
+            let wanted = work[index].map(clamp);
+            let color = channels(palette[nearest(palette, wanted)]);
+            output.put_pixel(x as u32, y as u32, image::Rgb(color.map(clamp)));
+
+            let error: [i32; 3] = std::array::from_fn(|c| work[index][c] - color[c]);
+            for &(dx, dy, weight) in neighbours {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+                let neighbour = &mut work[(ny * width as i32 + nx) as usize];
+                for c in 0..3 {
+                    neighbour[c] += error[c] * weight / divisor;
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Ordered dithering: a Bayer pattern offsets each pixel before it is mapped,
+/// by up to half the step between the palette levels
+fn ordered(image: &RgbImage, palette: &[Rgb]) -> RgbImage {
+    // Levels per channel of a palette spread evenly over the color cube
+    let levels = ((palette.len() as f64).cbrt().round() as i32).max(2);
+    let step = 255 / (levels - 1);
+
+    let mut output = image.clone();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let threshold = BAYER_4X4[(y % 4) as usize][(x % 4) as usize];
+        let offset = (threshold * 2 + 1 - 16) * step / 32;
+        let wanted = pixel.0.map(|channel| clamp(channel as i32 + offset));
+        pixel.0 = channels(palette[nearest(palette, wanted)]).map(clamp);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK_AND_WHITE: [Rgb; 2] = [Rgb(0, 0, 0), Rgb(255, 255, 255)];
+
+    /// Gray ramp from black on the left to white on the right
+    fn ramp() -> RgbImage {
+        RgbImage::from_fn(8, 4, |x, _| image::Rgb([(x * 255 / 7) as u8; 3]))
+    }
+
+    /// Image as rows of `#` (white) and `.` (black), for snapshots
+    fn render(image: &RgbImage) -> Vec<String> {
+        image
+            .rows()
+            .map(|row| {
+                row.map(|pixel| if pixel.0[0] > 127 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn snapshot(dither: Dither) -> Vec<String> {
+        let output = dither.apply(&ramp(), &BLACK_AND_WHITE);
+        assert!(output
+            .pixels()
+            .all(|pixel| pixel.0 == [0; 3] || pixel.0 == [255; 3]));
+        render(&output)
+    }
+
+    #[test]
+    fn nearest_color_snapshot() {
+        assert_eq!(
+            snapshot(Dither::None),
+            ["....####", "....####", "....####", "....####"]
+        );
+    }
+
+    #[test]
+    fn floyd_steinberg_snapshot() {
+        assert_eq!(
+            snapshot(Dither::FloydSteinberg),
+            ["...#.###", "...#.###", "...#.###", "..#.####"]
+        );
+    }
+
+    #[test]
+    fn atkinson_snapshot() {
+        assert_eq!(
+            snapshot(Dither::Atkinson),
+            ["....####", "...#####", ".....###", "...#####"]
+        );
+    }
+
+    #[test]
+    fn ordered_snapshot() {
+        assert_eq!(
+            snapshot(Dither::Ordered),
+            ["...#.###", "..#.#.##", "...#.#.#", "..#.####"]
+        );
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!("atkinson".parse(), Ok(Dither::Atkinson));
+        assert_eq!("floyd-steinberg".parse(), Ok(Dither::FloydSteinberg));
+        assert!("random".parse::<Dither>().is_err());
+        assert_eq!(
+            serde_json::to_string(&Dither::FloydSteinberg).unwrap(),
+            "\"floyd-steinberg\""
+        );
+    }
+}
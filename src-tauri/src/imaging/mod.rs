@@ -0,0 +1,317 @@
+//! Conversion of images to what the panel shows.
+//!
+//! Panels are a few dozen LEDs per side and don't scale what they receive: an
+//! image is decoded, flattened onto a background color, resized to the panel
+//! dimensions with a [`ResizeMethod`], corrected for the panel LEDs by a
+//! [`ColorProfile`], optionally reduced to a [`Palette`] with [`Dither`]ing,
+//! and re-encoded as PNG, the format both the backend and the panel accept.
+//! Animations go through [`animation`] and become GIFs.
+
+pub mod animation;
+mod color;
+mod dither;
+mod quantize;
+mod resize;
+
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageReader, Limits, RgbImage, RgbaImage};
+use thiserror::Error;
+
+use crate::ipixel::protocol::Rgb;
+pub use animation::{Budget, UploadReport};
+pub use color::{ColorProfile, ColorTransform, TestPattern};
+pub use dither::Dither;
+pub use quantize::Palette;
+pub use resize::{resize, ResampleFilter, ResizeMethod};
+
+/// Largest accepted image side. Photos are far below it, bigger files are
+/// more likely to be decompression bombs than pictures.
+const MAX_INPUT_DIMENSION: u32 = 16_384;
+/// Largest panel side, the protocol sends sizes as a single byte
+const MAX_PANEL_DIMENSION: u32 = 255;
+
+/// Failure of an image conversion
+#[derive(Debug, Error)]
+pub enum ImageError {
+    #[error("Unsupported image format, expected PNG, JPEG, BMP or WebP")]
+    UnsupportedFormat,
+    #[error("Failed to decode the image: {0}")]
+    Decode(String),
+    #[error("Failed to encode the image: {0}")]
+    Encode(String),
+    #[error("Invalid panel size {width}x{height}")]
+    InvalidSize { width: u32, height: u32 },
+    #[error("Dithering needs a palette, give a number of colors or a list of colors")]
+    DitherWithoutPalette,
+    #[error("Animations have at most {0} frames")]
+    TooManyFrames(usize),
+    #[error("The converted file is {bytes} bytes, over the budget of {max_bytes} bytes")]
+    OverBudget { bytes: usize, max_bytes: usize },
+    #[error("Invalid color profile: {0}")]
+    InvalidColorProfile(String),
+}
+
+/// How an image is converted for the panel, the size comes from the panel
+/// itself
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConvertOptions {
+    pub method: ResizeMethod,
+    /// Color of the letterbox bars and of transparent areas
+    pub background: Rgb,
+    pub filter: ResampleFilter,
+    pub palette: Palette,
+    pub dither: Dither,
+    /// Color correction of the panel, applied before the palette is picked
+    pub color: ColorProfile,
+    /// Upload limits, animations are reduced to fit them
+    pub budget: Budget,
+}
+
+/// A file converted for the panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upload {
+    /// PNG, or GIF when `animated`
+    pub data: Vec<u8>,
+    pub animated: bool,
+    pub report: UploadReport,
+}
+
+/// Decode a PNG, JPEG, BMP or WebP image
+pub fn decode(data: &[u8]) -> Result<RgbaImage, ImageError> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| ImageError::Decode(e.to_string()))?;
+    match reader.format() {
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Bmp | ImageFormat::WebP) => {}
+        _ => return Err(ImageError::UnsupportedFormat),
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_INPUT_DIMENSION);
+    limits.max_image_height = Some(MAX_INPUT_DIMENSION);
+    reader.limits(limits);
+
+    reader
+        .decode()
+        .map(|image| image.into_rgba8())
+        .map_err(|e| ImageError::Decode(e.to_string()))
+}
+
+/// Blend the image onto the background color, LEDs have no transparency
+pub fn flatten(image: &RgbaImage, background: Rgb) -> RgbImage {
+    let Rgb(br, bg, bb) = background;
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let blend = |channel: u8, back: u8| {
+            ((channel as u32 * a as u32 + back as u32 * (255 - a as u32) + 127) / 255) as u8
+        };
+        image::Rgb([blend(r, br), blend(g, bg), blend(b, bb)])
+    })
+}
+
+pub fn encode_png(image: &RgbImage) -> Result<Vec<u8>, ImageError> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    Ok(png)
+}
+
+/// Check the panel size and options before decoding anything
+fn check(width: u32, height: u32, options: &ConvertOptions) -> Result<(), ImageError> {
+    if !(1..=MAX_PANEL_DIMENSION).contains(&width) || !(1..=MAX_PANEL_DIMENSION).contains(&height) {
+        return Err(ImageError::InvalidSize { width, height });
+    }
+    if options.palette == Palette::Full && options.dither != Dither::None {
+        return Err(ImageError::DitherWithoutPalette);
+    }
+    options.color.validate()
+}
+
+/// Resize the image to the panel, correct its colors and reduce it to the
+/// palette
+pub fn process(
+    image: &RgbaImage,
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Result<RgbImage, ImageError> {
+    check(width, height, options)?;
+    let mut image = resize(&flatten(image, options.background), width, height, options);
+    ColorTransform::new(&options.color).apply_image(&mut image);
+    // The palette is picked from the resized image, only its colors are shown
+    Ok(match options.palette.resolve(&image) {
+        Some(palette) => options.dither.apply(&image, &palette),
+        None => image,
+    })
+}
+
+/// Convert an image file to a PNG of exactly `width` x `height` pixels
+pub fn convert(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>, ImageError> {
+    encode_png(&process(&decode(data)?, width, height, options)?)
+}
+
+/// Convert an image to a PNG or an animation to a GIF for the panel
+pub fn convert_upload(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Result<Upload, ImageError> {
+    if animation::is_animation(data) {
+        let (gif, report) = animation::convert(data, width, height, options)?;
+        return Ok(Upload {
+            data: gif,
+            animated: true,
+            report,
+        });
+    }
+    let png = convert(data, width, height, options)?;
+    let report = UploadReport::still(png.len(), &options.budget)?;
+    Ok(Upload {
+        data: png,
+        animated: false,
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageEncoder;
+
+    fn encode(image: &RgbaImage, format: ImageFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        match format {
+            // The JPEG encoder has no alpha channel
+            ImageFormat::Jpeg => image::DynamicImage::ImageRgba8(image.clone())
+                .into_rgb8()
+                .write_to(&mut Cursor::new(&mut data), format)
+                .unwrap(),
+            ImageFormat::WebP => image::codecs::webp::WebPEncoder::new_lossless(&mut data)
+                .write_image(
+                    image.as_raw(),
+                    image.width(),
+                    image.height(),
+                    image::ExtendedColorType::Rgba8,
+                )
+                .unwrap(),
+            _ => image.write_to(&mut Cursor::new(&mut data), format).unwrap(),
+        }
+        data
+    }
+
+    #[test]
+    fn converts_every_supported_format_to_the_panel_size() {
+        let image = RgbaImage::from_pixel(64, 48, image::Rgba([200, 40, 10, 255]));
+        for format in [
+            ImageFormat::Png,
+            ImageFormat::Jpeg,
+            ImageFormat::Bmp,
+            ImageFormat::WebP,
+        ] {
+            let png = convert(&encode(&image, format), 32, 16, &ConvertOptions::default()).unwrap();
+            let output = image::load_from_memory_with_format(&png, ImageFormat::Png)
+                .unwrap()
+                .into_rgb8();
+            assert_eq!(output.dimensions(), (32, 16), "{:?}", format);
+            let [r, g, b] = output.get_pixel(16, 8).0;
+            assert!(r > 180 && g < 60 && b < 40, "{:?}: {:?}", format, (r, g, b));
+        }
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_convert() {
+        assert!(matches!(
+            decode(b"GIF89a\x01\x00\x01\x00"),
+            Err(ImageError::UnsupportedFormat)
+        ));
+        assert!(matches!(
+            decode(b"not an image"),
+            Err(ImageError::UnsupportedFormat)
+        ));
+        let png = encode(&RgbaImage::new(4, 4), ImageFormat::Png);
+        assert!(matches!(
+            decode(&png[..png.len() / 2]),
+            Err(ImageError::Decode(_))
+        ));
+        assert!(matches!(
+            convert(&png, 0, 16, &ConvertOptions::default()),
+            Err(ImageError::InvalidSize { .. })
+        ));
+    }
+
+    #[test]
+    fn reduces_to_the_palette() {
+        let image = RgbaImage::from_fn(16, 16, |x, y| {
+            image::Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255])
+        });
+        let options = ConvertOptions {
+            palette: Palette::Colors(4),
+            dither: Dither::FloydSteinberg,
+            ..ConvertOptions::default()
+        };
+        let output = process(&image, 8, 8, &options).unwrap();
+        let mut colors: Vec<_> = output.pixels().map(|pixel| pixel.0).collect();
+        colors.sort_unstable();
+        colors.dedup();
+        assert!(colors.len() <= 4, "{:?}", colors);
+
+        let options = ConvertOptions {
+            dither: Dither::Ordered,
+            ..ConvertOptions::default()
+        };
+        assert!(matches!(
+            process(&image, 8, 8, &options),
+            Err(ImageError::DitherWithoutPalette)
+        ));
+    }
+
+    #[test]
+    fn corrects_colors_before_the_palette() {
+        let image = RgbaImage::from_pixel(4, 4, image::Rgba([128, 128, 128, 255]));
+        let options = ConvertOptions {
+            color: ColorProfile {
+                gamma: 2.2,
+                ..ColorProfile::UNCHANGED
+            },
+            ..ConvertOptions::default()
+        };
+        let output = process(&image, 2, 2, &options).unwrap();
+        assert_eq!(output.get_pixel(1, 1).0, [56, 56, 56]);
+
+        // Corrected to 56 first, the closest of the palette is then 40
+        let options = ConvertOptions {
+            palette: Palette::Custom(vec![Rgb(128, 128, 128), Rgb(40, 40, 40)]),
+            ..options
+        };
+        let output = process(&image, 2, 2, &options).unwrap();
+        assert_eq!(output.get_pixel(1, 1).0, [40, 40, 40]);
+
+        let options = ConvertOptions {
+            color: ColorProfile {
+                gamma: 0.5,
+                ..ColorProfile::UNCHANGED
+            },
+            ..ConvertOptions::default()
+        };
+        assert!(matches!(
+            process(&image, 2, 2, &options),
+            Err(ImageError::InvalidColorProfile(_))
+        ));
+    }
+
+    #[test]
+    fn transparency_shows_the_background() {
+        let mut image = RgbaImage::from_pixel(2, 1, image::Rgba([255, 255, 255, 0]));
+        image.put_pixel(1, 0, image::Rgba([255, 0, 0, 128]));
+        let flat = flatten(&image, Rgb(0, 0, 255));
+        assert_eq!(flat.get_pixel(0, 0).0, [0, 0, 255]);
+        assert_eq!(flat.get_pixel(1, 0).0, [128, 0, 127]);
+    }
+}
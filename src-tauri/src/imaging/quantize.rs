@@ -0,0 +1,244 @@
+//! Reduction of an image to a few colors.
+//!
+//! LEDs render smooth gradients poorly at 16x16 or 32x32: a small palette,
+//! computed from the image or given by the user, often reads better.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use image::RgbImage;
+
+use crate::ipixel::protocol::Rgb;
+
+/// Fewest colors of a computed palette
+pub const MIN_COLORS: u16 = 2;
+/// Most colors of a computed or custom palette
+pub const MAX_COLORS: u16 = 256;
+
+/// Colors the image is reduced to
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Palette {
+    /// No reduction
+    #[default]
+    Full,
+    /// The given number of colors, picked from the image
+    Colors(u16),
+    /// Colors given by the user
+    Custom(Vec<Rgb>),
+}
+
+impl Palette {
+    /// Colors to reduce this image to, `None` to keep them all
+    pub fn resolve(&self, image: &RgbImage) -> Option<Vec<Rgb>> {
+        match self {
+            Palette::Full => None,
+            Palette::Colors(count) => Some(median_cut(image, *count as usize)),
+            Palette::Custom(colors) => Some(colors.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Palette::Full => f.write_str("full"),
+            Palette::Colors(count) => write!(f, "{}", count),
+            Palette::Custom(colors) => {
+                let colors: Vec<String> = colors.iter().map(|color| color.to_hex()).collect();
+                f.write_str(&colors.join(","))
+            }
+        }
+    }
+}
+
+/// `full`, a number of colors, or comma separated `RRGGBB` colors
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(palette: &str) -> Result<Self, Self::Err> {
+        if palette == "full" {
+            return Ok(Palette::Full);
+        }
+        if let Ok(count) = palette.parse::<u16>() {
+            if !(MIN_COLORS..=MAX_COLORS).contains(&count) {
+                return Err(format!(
+                    "A palette has {} to {} colors, got {}",
+                    MIN_COLORS, MAX_COLORS, count
+                ));
+            }
+            return Ok(Palette::Colors(count));
+        }
+        let colors = palette
+            .split(',')
+            .map(|color| Rgb::from_hex(color.trim()).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.len() > MAX_COLORS as usize {
+            return Err(format!(
+                "A palette has at most {} colors, got {}",
+                MAX_COLORS,
+                colors.len()
+            ));
+        }
+        Ok(Palette::Custom(colors))
+    }
+}
+
+fn channels(color: Rgb) -> [u8; 3] {
+    [color.0, color.1, color.2]
+}
+
+/// Squared distance between two colors
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Index of the palette color closest to `color`, the first one on ties
+pub fn nearest(palette: &[Rgb], color: [u8; 3]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(index, candidate)| (distance(channels(**candidate), color), *index))
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+/// Replace every pixel with the closest palette color
+pub fn remap(image: &RgbImage, palette: &[Rgb]) -> RgbImage {
+    let mut output = image.clone();
+    for pixel in output.pixels_mut() {
+        pixel.0 = channels(palette[nearest(palette, pixel.0)]);
+    }
+    output
+}
+
+/// Pick up to `count` colors representing the image (median cut): the set of
+/// colors is split in two at the median of its widest channel until there
+/// are enough sets, each gives its average color.
+pub fn median_cut(image: &RgbImage, count: usize) -> Vec<Rgb> {
+    let mut colors: Vec<[u8; 3]> = image.pixels().map(|pixel| pixel.0).collect();
+    colors.sort_unstable();
+
+    let mut distinct = colors.clone();
+    distinct.dedup();
+    if distinct.len() <= count {
+        return distinct.into_iter().map(|[r, g, b]| Rgb(r, g, b)).collect();
+    }
+
+    let mut boxes = vec![colors];
+    while boxes.len() < count {
+        // Widest box first, the earliest one on ties so the result is stable
+        let Some((index, channel, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (index, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by(|a, b| a.2.cmp(&b.2).then(b.0.cmp(&a.0)))
+        else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by(|a, b| match a[channel].cmp(&b[channel]) {
+            Ordering::Equal => a.cmp(b),
+            order => order,
+        });
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+        // swap_remove moved the last box, restore a stable order
+        boxes.sort_by(|a, b| a[0].cmp(&b[0]));
+    }
+
+    boxes.iter().map(|colors| average(colors)).collect()
+}
+
+/// Channel with the widest range of values, and that range
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = colors.iter().fold((u8::MAX, u8::MIN), |(min, max), color| {
+                (min.min(color[channel]), max.max(color[channel]))
+            });
+            (channel, max - min)
+        })
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .unwrap_or((0, 0))
+}
+
+fn average(colors: &[[u8; 3]]) -> Rgb {
+    let mut sum = [0u64; 3];
+    for color in colors {
+        for channel in 0..3 {
+            sum[channel] += color[channel] as u64;
+        }
+    }
+    let count = colors.len().max(1) as u64;
+    let mean = |total: u64| ((total + count / 2) / count) as u8;
+    Rgb(mean(sum[0]), mean(sum[1]), mean(sum[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Horizontal red gradient over a vertical blue one
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(8, 8, |x, y| image::Rgb([(x * 36) as u8, 0, (y * 36) as u8]))
+    }
+
+    fn hex(palette: &[Rgb]) -> Vec<String> {
+        palette.iter().map(|color| color.to_hex()).collect()
+    }
+
+    #[test]
+    fn median_cut_snapshot() {
+        assert_eq!(
+            hex(&median_cut(&gradient(), 4)),
+            ["360036", "3600C6", "C60036", "C600C6"]
+        );
+        assert_eq!(hex(&median_cut(&gradient(), 2)), ["36007E", "C6007E"]);
+    }
+
+    #[test]
+    fn keeps_images_with_few_colors_as_they_are() {
+        let image = RgbImage::from_fn(4, 4, |x, _| {
+            if x < 2 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        assert_eq!(hex(&median_cut(&image, 16)), ["0000FF", "FF0000"]);
+        assert_eq!(remap(&image, &median_cut(&image, 16)), image);
+    }
+
+    #[test]
+    fn remaps_to_the_nearest_color() {
+        let palette = [Rgb(0, 0, 0), Rgb(255, 255, 255), Rgb(255, 0, 0)];
+        assert_eq!(nearest(&palette, [30, 20, 10]), 0);
+        assert_eq!(nearest(&palette, [200, 40, 40]), 2);
+        assert_eq!(nearest(&palette, [200, 200, 180]), 1);
+    }
+
+    #[test]
+    fn parses_palettes() {
+        assert_eq!("full".parse(), Ok(Palette::Full));
+        assert_eq!("16".parse(), Ok(Palette::Colors(16)));
+        assert_eq!(
+            "000000, #FFFFFF".parse(),
+            Ok(Palette::Custom(vec![Rgb(0, 0, 0), Rgb(255, 255, 255)]))
+        );
+        assert!("1".parse::<Palette>().is_err());
+        assert!("300".parse::<Palette>().is_err());
+        assert!("red,blue".parse::<Palette>().is_err());
+        assert_eq!(Palette::Custom(vec![Rgb(1, 2, 3)]).to_string(), "010203");
+    }
+}
@@ -0,0 +1,304 @@
+//! Fitting an image to the panel dimensions.
+
+use std::fmt;
+use std::str::FromStr;
+
+use image::imageops::{self, FilterType};
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+use super::ConvertOptions;
+use crate::ipixel::protocol::Rgb;
+
+/// How an image of another aspect ratio is fitted to the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMethod {
+    /// Fill the panel and cut what overflows, centered
+    Crop,
+    /// Show the whole image, with bars of the background color around it
+    #[default]
+    Fit,
+    /// Fill the panel, distorting the image
+    Stretch,
+}
+
+impl ResizeMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResizeMethod::Crop => "crop",
+            ResizeMethod::Fit => "fit",
+            ResizeMethod::Stretch => "stretch",
+        }
+    }
+}
+
+impl fmt::Display for ResizeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ResizeMethod {
+    type Err = String;
+
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        match method {
+            "crop" => Ok(ResizeMethod::Crop),
+            "fit" => Ok(ResizeMethod::Fit),
+            "stretch" => Ok(ResizeMethod::Stretch),
+            other => Err(format!(
+                "Invalid resize method \"{}\" (expected crop, fit or stretch)",
+                other
+            )),
+        }
+    }
+}
+
+/// Resampling used to scale the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleFilter {
+    /// Nearest when enlarging, to keep pixel art sharp, Lanczos otherwise
+    #[default]
+    Auto,
+    /// Closest source pixel, hard edges
+    Nearest,
+    /// Average of the source pixels covered, no ringing
+    Box,
+    /// Lanczos (3 lobes), the sharpest for photos
+    Lanczos,
+}
+
+impl ResampleFilter {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResampleFilter::Auto => "auto",
+            ResampleFilter::Nearest => "nearest",
+            ResampleFilter::Box => "box",
+            ResampleFilter::Lanczos => "lanczos",
+        }
+    }
+}
+
+impl fmt::Display for ResampleFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ResampleFilter {
+    type Err = String;
+
+    fn from_str(filter: &str) -> Result<Self, Self::Err> {
+        match filter {
+            "auto" => Ok(ResampleFilter::Auto),
+            "nearest" => Ok(ResampleFilter::Nearest),
+            "box" => Ok(ResampleFilter::Box),
+            "lanczos" => Ok(ResampleFilter::Lanczos),
+            other => Err(format!(
+                "Invalid filter \"{}\" (expected auto, nearest, box or lanczos)",
+                other
+            )),
+        }
+    }
+}
+
+/// Size of the whole image scaled to fit in the target, keeping its aspect
+/// ratio, before letterboxing
+fn fit_size(source: (u32, u32), target: (u32, u32)) -> (u32, u32) {
+    let (sw, sh) = (source.0 as f64, source.1 as f64);
+    let scale = (target.0 as f64 / sw).min(target.1 as f64 / sh);
+    let side = |length: f64, bound: u32| ((length * scale).round() as u32).clamp(1, bound.max(1));
+    (side(sw, target.0), side(sh, target.1))
+}
+
+/// Centered part of the source with the aspect ratio of the target, as
+/// `(x, y, width, height)`. Cropping before scaling keeps thin images from
+/// being scaled up whole first.
+fn crop_rect(source: (u32, u32), target: (u32, u32)) -> (u32, u32, u32, u32) {
+    let (sw, sh) = source;
+    let (tw, th) = (target.0.max(1) as f64, target.1.max(1) as f64);
+    let (width, height) = if sw as f64 * th > sh as f64 * tw {
+        let width = (sh as f64 * tw / th).round() as u32;
+        (width.clamp(1, sw), sh)
+    } else {
+        let height = (sw as f64 * th / tw).round() as u32;
+        (sw, height.clamp(1, sh))
+    };
+    ((sw - width) / 2, (sh - height) / 2, width, height)
+}
+
+/// Scale with the average of the source pixels whose centers fall in each
+/// target pixel, or the closest one when enlarging
+fn box_resize(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let (source_width, source_height) = image.dimensions();
+    let span = |target: u32, source: u32, length: u32| {
+        let start = (target as u64 * source as u64 / length as u64) as u32;
+        let end = ((target as u64 + 1) * source as u64 / length as u64) as u32;
+        start..end.max(start + 1)
+    };
+    RgbImage::from_fn(width, height, |x, y| {
+        let mut sum = [0u64; 3];
+        let mut count = 0u64;
+        for sy in span(y, source_height, height) {
+            for sx in span(x, source_width, width) {
+                let pixel = image.get_pixel(sx, sy).0;
+                for c in 0..3 {
+                    sum[c] += pixel[c] as u64;
+                }
+                count += 1;
+            }
+        }
+        image::Rgb(sum.map(|total| ((total + count / 2) / count) as u8))
+    })
+}
+
+fn scale(image: &RgbImage, width: u32, height: u32, filter: ResampleFilter) -> RgbImage {
+    let filter = match filter {
+        // Small sources are mostly pixel art, which smoothing would blur
+        ResampleFilter::Auto if width >= image.width() && height >= image.height() => {
+            FilterType::Nearest
+        }
+        ResampleFilter::Auto | ResampleFilter::Lanczos => FilterType::Lanczos3,
+        ResampleFilter::Nearest => FilterType::Nearest,
+        ResampleFilter::Box => return box_resize(image, width, height),
+    };
+    imageops::resize(image, width, height, filter)
+}
+
+/// Resize to exactly `width` x `height` pixels
+pub fn resize(image: &RgbImage, width: u32, height: u32, options: &ConvertOptions) -> RgbImage {
+    match options.method {
+        ResizeMethod::Stretch => scale(image, width, height, options.filter),
+        ResizeMethod::Crop => {
+            let (x, y, crop_width, crop_height) = crop_rect(image.dimensions(), (width, height));
+            let cropped = imageops::crop_imm(image, x, y, crop_width, crop_height).to_image();
+            scale(&cropped, width, height, options.filter)
+        }
+        ResizeMethod::Fit => {
+            let (scaled_width, scaled_height) = fit_size(image.dimensions(), (width, height));
+            let scaled = scale(image, scaled_width, scaled_height, options.filter);
+            let Rgb(r, g, b) = options.background;
+            let mut canvas = RgbImage::from_pixel(width, height, image::Rgb([r, g, b]));
+            let x = (width - scaled_width) / 2;
+            let y = (height - scaled_height) / 2;
+            imageops::replace(&mut canvas, &scaled, x.into(), y.into());
+            canvas
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: image::Rgb<u8> = image::Rgb([255, 0, 0]);
+    const BLUE: image::Rgb<u8> = image::Rgb([0, 0, 255]);
+
+    fn options(method: ResizeMethod) -> ConvertOptions {
+        ConvertOptions {
+            method,
+            background: Rgb(0, 255, 0),
+            ..ConvertOptions::default()
+        }
+    }
+
+    /// Wide image: red on the left third, blue elsewhere
+    fn banner() -> RgbImage {
+        RgbImage::from_fn(6, 2, |x, _| if x < 2 { RED } else { BLUE })
+    }
+
+    #[test]
+    fn computes_the_fit_size() {
+        assert_eq!(fit_size((640, 480), (32, 32)), (32, 24));
+        assert_eq!(fit_size((10, 10), (64, 16)), (16, 16));
+        // A very thin image keeps at least one pixel
+        assert_eq!(fit_size((1000, 1), (32, 32)), (32, 1));
+    }
+
+    #[test]
+    fn computes_the_crop_rect() {
+        assert_eq!(crop_rect((640, 480), (32, 32)), (80, 0, 480, 480));
+        assert_eq!(crop_rect((10, 10), (64, 16)), (0, 3, 10, 3));
+        assert_eq!(crop_rect((64, 32), (64, 32)), (0, 0, 64, 32));
+        // A very thin image is cropped to a single pixel, not scaled up whole
+        assert_eq!(crop_rect((16384, 1), (64, 64)), (8191, 0, 1, 1));
+    }
+
+    #[test]
+    fn fit_letterboxes_with_the_background() {
+        let output = resize(&banner(), 6, 6, &options(ResizeMethod::Fit));
+        assert_eq!(output.dimensions(), (6, 6));
+        for x in 0..6 {
+            assert_eq!(*output.get_pixel(x, 0), image::Rgb([0, 255, 0]));
+            assert_eq!(*output.get_pixel(x, 5), image::Rgb([0, 255, 0]));
+        }
+        assert_eq!(*output.get_pixel(0, 2), RED);
+        assert_eq!(*output.get_pixel(5, 3), BLUE);
+    }
+
+    #[test]
+    fn crop_keeps_the_center() {
+        let output = resize(&banner(), 2, 2, &options(ResizeMethod::Crop));
+        assert_eq!(output.dimensions(), (2, 2));
+        // The red third is cut off entirely
+        assert!(output.pixels().all(|pixel| *pixel == BLUE));
+
+        let thin = RgbImage::from_fn(16384, 1, |x, _| if x < 8000 { RED } else { BLUE });
+        let output = resize(&thin, 64, 64, &options(ResizeMethod::Crop));
+        assert_eq!(output.dimensions(), (64, 64));
+        assert!(output.pixels().all(|pixel| *pixel == BLUE));
+    }
+
+    #[test]
+    fn stretch_fills_the_panel() {
+        let output = resize(&banner(), 3, 3, &options(ResizeMethod::Stretch));
+        assert_eq!(output.dimensions(), (3, 3));
+        // Downscaling blends the edges, each side keeps its color
+        for y in 0..3 {
+            let [r, _, b] = output.get_pixel(0, y).0;
+            assert!(r > b, "{:?}", (r, b));
+            let [r, _, b] = output.get_pixel(2, y).0;
+            assert!(b > r, "{:?}", (r, b));
+        }
+    }
+
+    #[test]
+    fn upscaling_keeps_hard_edges() {
+        let checker = RgbImage::from_fn(2, 2, |x, y| if (x + y) % 2 == 0 { RED } else { BLUE });
+        let output = resize(&checker, 8, 8, &options(ResizeMethod::Fit));
+        assert!(output.pixels().all(|pixel| *pixel == RED || *pixel == BLUE));
+        assert_eq!(*output.get_pixel(3, 3), RED);
+        assert_eq!(*output.get_pixel(4, 3), BLUE);
+    }
+
+    #[test]
+    fn filters_snapshot() {
+        let row = RgbImage::from_fn(8, 1, |x, _| {
+            image::Rgb([[255, 0, 255, 255, 0, 0, 0, 255][x as usize]; 3])
+        });
+        let scaled = |filter| {
+            scale(&row, 4, 1, filter)
+                .pixels()
+                .map(|pixel| pixel.0[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(scaled(ResampleFilter::Nearest), [0, 255, 0, 255]);
+        assert_eq!(scaled(ResampleFilter::Box), [128, 255, 0, 128]);
+        assert_eq!(scaled(ResampleFilter::Lanczos), [139, 212, 4, 115]);
+    }
+
+    #[test]
+    fn parses_method_names() {
+        assert_eq!("crop".parse(), Ok(ResizeMethod::Crop));
+        assert_eq!("stretch".parse(), Ok(ResizeMethod::Stretch));
+        assert!("zoom".parse::<ResizeMethod>().is_err());
+        assert_eq!(
+            serde_json::from_str::<ResizeMethod>("\"fit\"").unwrap(),
+            ResizeMethod::Fit
+        );
+        assert_eq!("box".parse(), Ok(ResampleFilter::Box));
+        assert!("bicubic".parse::<ResampleFilter>().is_err());
+    }
+}
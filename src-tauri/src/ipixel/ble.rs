@@ -0,0 +1,181 @@
+//! Bluetooth LE transport, backed by btleplug
+
+use std::pin::Pin;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use btleplug::api::{
+    Central, Characteristic, Manager as _, Peripheral as _, ScanFilter, ValueNotification,
+    WriteType,
+};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures_util::{Stream, StreamExt};
+use uuid::Uuid;
+
+use super::transport::{Transport, TransportError};
+
+/// Service advertised by iPixel Color panels
+pub const IPIXEL_SERVICE_UUID: Uuid = Uuid::from_u128(0x0000fff0_0000_1000_8000_00805f9b34fb);
+/// Characteristic frames are written to
+const WRITE_CHARACTERISTIC_UUID: Uuid = Uuid::from_u128(0x0000fa02_0000_1000_8000_00805f9b34fb);
+/// Characteristic the panel answers on
+const NOTIFY_CHARACTERISTIC_UUID: Uuid = Uuid::from_u128(0x0000fa03_0000_1000_8000_00805f9b34fb);
+/// Largest write accepted by the panel in one go
+const MAX_WRITE_SIZE: usize = 244;
+
+/// A panel found by [`scan`]
+#[derive(Debug, Clone)]
+pub struct DiscoveredPanel {
+    pub name: String,
+    pub address: String,
+    pub rssi: Option<i16>,
+}
+
+fn ble_error(e: btleplug::Error) -> TransportError {
+    TransportError::Io(e.to_string())
+}
+
+async fn adapter() -> Result<Adapter, TransportError> {
+    let manager = Manager::new().await.map_err(ble_error)?;
+    manager
+        .adapters()
+        .await
+        .map_err(ble_error)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| TransportError::Io("no Bluetooth adapter found".to_string()))
+}
+
+/// Address shown to the user. macOS hides MAC addresses, use the peripheral id there.
+fn peripheral_address(peripheral: &Peripheral) -> String {
+    let address = peripheral.address();
+    if address.into_inner() == [0; 6] {
+        peripheral.id().to_string()
+    } else {
+        address.to_string()
+    }
+}
+
+/// Same name heuristics as the Python backend's `BLEManager`
+fn looks_like_ipixel(name: &str, services: &[Uuid]) -> bool {
+    let name = name.to_lowercase();
+    if name.contains("thermobeacon") || name.contains("thermometer") || name == "sps" {
+        return false;
+    }
+    name.contains("pixel")
+        || name.contains("led_ble")
+        || name.contains("led-ble")
+        || name.starts_with("led ")
+        || services.contains(&IPIXEL_SERVICE_UUID)
+}
+
+async fn discover(adapter: &Adapter, timeout: Duration) -> Result<Vec<Peripheral>, TransportError> {
+    adapter
+        .start_scan(ScanFilter::default())
+        .await
+        .map_err(ble_error)?;
+    tokio::time::sleep(timeout).await;
+    let peripherals = adapter.peripherals().await.map_err(ble_error)?;
+    let _ = adapter.stop_scan().await;
+    Ok(peripherals)
+}
+
+/// Scan for iPixel panels during `timeout`
+pub async fn scan(timeout: Duration) -> Result<Vec<DiscoveredPanel>, TransportError> {
+    let adapter = adapter().await?;
+    let mut panels = Vec::new();
+
+    for peripheral in discover(&adapter, timeout).await? {
+        let Ok(Some(properties)) = peripheral.properties().await else {
+            continue;
+        };
+        let name = properties
+            .local_name
+            .unwrap_or_else(|| "Unknown".to_string());
+        if looks_like_ipixel(&name, &properties.services) {
+            panels.push(DiscoveredPanel {
+                name,
+                address: peripheral_address(&peripheral),
+                rssi: properties.rssi,
+            });
+        }
+    }
+
+    Ok(panels)
+}
+
+type Notifications = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+/// Connection to a panel over Bluetooth LE
+pub struct BleTransport {
+    peripheral: Peripheral,
+    write_characteristic: Characteristic,
+    notifications: Notifications,
+}
+
+impl BleTransport {
+    /// Connect to the panel with the given address, scanning for it first
+    pub async fn connect(address: &str, scan_timeout: Duration) -> Result<Self, TransportError> {
+        let adapter = adapter().await?;
+        let peripheral = discover(&adapter, scan_timeout)
+            .await?
+            .into_iter()
+            .find(|p| peripheral_address(p).eq_ignore_ascii_case(address))
+            .ok_or_else(|| TransportError::NotFound(address.to_string()))?;
+
+        peripheral.connect().await.map_err(ble_error)?;
+        peripheral.discover_services().await.map_err(ble_error)?;
+
+        let characteristics = peripheral.characteristics();
+        let find = |uuid: Uuid| {
+            characteristics
+                .iter()
+                .find(|c| c.uuid == uuid)
+                .cloned()
+                .ok_or_else(|| TransportError::Io(format!("characteristic {} not found", uuid)))
+        };
+        let write_characteristic = find(WRITE_CHARACTERISTIC_UUID)?;
+        let notify_characteristic = find(NOTIFY_CHARACTERISTIC_UUID)?;
+
+        peripheral
+            .subscribe(&notify_characteristic)
+            .await
+            .map_err(ble_error)?;
+        let notifications = peripheral.notifications().await.map_err(ble_error)?;
+
+        Ok(Self {
+            peripheral,
+            write_characteristic,
+            notifications,
+        })
+    }
+
+    pub fn address(&self) -> String {
+        peripheral_address(&self.peripheral)
+    }
+}
+
+#[async_trait]
+impl Transport for BleTransport {
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        for chunk in frame.chunks(MAX_WRITE_SIZE) {
+            self.peripheral
+                .write(&self.write_characteristic, chunk, WriteType::WithResponse)
+                .await
+                .map_err(ble_error)?;
+        }
+        Ok(())
+    }
+
+    async fn read_notification(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        match tokio::time::timeout(timeout, self.notifications.next()).await {
+            Ok(Some(notification)) => Ok(notification.value),
+            Ok(None) => Err(TransportError::NotConnected),
+            Err(_) => Err(TransportError::Timeout),
+        }
+    }
+
+    async fn disconnect(&mut self) -> Result<(), TransportError> {
+        self.peripheral.disconnect().await.map_err(ble_error)
+    }
+}
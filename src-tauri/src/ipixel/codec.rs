@@ -0,0 +1,519 @@
+//! Typed view of protocol frames.
+//!
+//! [`Command`] lists the operations [`protocol`](super::protocol) knows how to
+//! encode, and [`decode`] turns a frame back into one, so frames captured from
+//! the panel link can be inspected and encoders checked by round trip.
+
+use thiserror::Error;
+
+use super::protocol::{self, command, ClockDate, ProtocolError, Rgb};
+
+/// A single protocol frame, decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    SetBrightness(u8),
+    SetOrientation(u8),
+    SetPower(bool),
+    SetFunMode(bool),
+    SetPixel {
+        x: u8,
+        y: u8,
+        color: Rgb,
+    },
+    SetTime {
+        hour: u8,
+        minute: u8,
+        second: u8,
+    },
+    ClockMode {
+        style: u8,
+        format_24: bool,
+        show_date: bool,
+        date: ClockDate,
+    },
+    RhythmMode {
+        style: u8,
+        levels: [u8; 11],
+    },
+    RhythmMode2 {
+        style: u8,
+        time: u8,
+    },
+    /// One frame of a text, image or GIF upload
+    UploadChunk {
+        command: [u8; 2],
+        first: bool,
+        total_size: u32,
+        crc32: u32,
+        header: Vec<u8>,
+        chunk: Vec<u8>,
+    },
+}
+
+impl Command {
+    /// Encode the command as a frame
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        match self {
+            Command::SetBrightness(brightness) => protocol::set_brightness(*brightness),
+            Command::SetOrientation(orientation) => protocol::set_orientation(*orientation),
+            Command::SetPower(on) => protocol::set_power(*on),
+            Command::SetFunMode(enable) => protocol::set_fun_mode(*enable),
+            Command::SetPixel { x, y, color } => protocol::set_pixel(*x, *y, *color),
+            Command::SetTime {
+                hour,
+                minute,
+                second,
+            } => protocol::set_time(*hour, *minute, *second),
+            Command::ClockMode {
+                style,
+                format_24,
+                show_date,
+                date,
+            } => protocol::set_clock_mode(*style, *format_24, *show_date, *date),
+            Command::RhythmMode { style, levels } => protocol::set_rhythm_mode(*style, levels),
+            Command::RhythmMode2 { style, time } => protocol::set_rhythm_mode_2(*style, *time),
+            Command::UploadChunk {
+                command,
+                first,
+                total_size,
+                crc32,
+                header,
+                chunk,
+            } => {
+                let mut payload = Vec::with_capacity(9 + header.len() + chunk.len());
+                payload.push(if *first {
+                    protocol::UPLOAD_FIRST
+                } else {
+                    protocol::UPLOAD_CONTINUATION
+                });
+                payload.extend_from_slice(&total_size.to_le_bytes());
+                payload.extend_from_slice(&crc32.to_le_bytes());
+                payload.extend_from_slice(header);
+                payload.extend_from_slice(chunk);
+                protocol::frame(*command, &payload)
+            }
+        }
+    }
+}
+
+/// A frame that could not be decoded
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("frame of {0} bytes is too short")]
+    Truncated(usize),
+    #[error("frame declares {declared} bytes but has {actual}")]
+    LengthMismatch { declared: usize, actual: usize },
+    #[error("unknown command {:02x} {:02x}", .0[0], .0[1])]
+    UnknownCommand([u8; 2]),
+    #[error("invalid payload for command {:02x} {:02x}", .0[0], .0[1])]
+    InvalidPayload([u8; 2]),
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+}
+
+/// Commands whose payload fits in a single frame
+const SINGLE_FRAME_COMMANDS: [[u8; 2]; 9] = [
+    command::BRIGHTNESS,
+    command::ORIENTATION,
+    command::POWER,
+    command::FUN_MODE,
+    command::PIXEL,
+    command::SET_TIME,
+    command::CLOCK_MODE,
+    command::RHYTHM_MODE,
+    command::RHYTHM_MODE_2,
+];
+
+/// Size of the header repeated in every frame of an upload
+fn upload_header_len(command: [u8; 2]) -> Option<usize> {
+    match command {
+        command::IMAGE | command::GIF => Some(1),
+        command::TEXT => Some(8),
+        _ => None,
+    }
+}
+
+fn flag(value: u8, command: [u8; 2]) -> Result<bool, DecodeError> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(DecodeError::InvalidPayload(command)),
+    }
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Decode a frame.
+///
+/// Values are checked against the same ranges as the encoders, so any frame
+/// accepted here encodes back to the same bytes.
+pub fn decode(frame: &[u8]) -> Result<Command, DecodeError> {
+    if frame.len() < 4 {
+        return Err(DecodeError::Truncated(frame.len()));
+    }
+    let declared = u16::from_le_bytes([frame[0], frame[1]]) as usize;
+    if declared != frame.len() {
+        return Err(DecodeError::LengthMismatch {
+            declared,
+            actual: frame.len(),
+        });
+    }
+    let cmd = [frame[2], frame[3]];
+    let payload = &frame[4..];
+    let invalid = || DecodeError::InvalidPayload(cmd);
+
+    let decoded = match (cmd, payload) {
+        (command::BRIGHTNESS, &[brightness]) => Command::SetBrightness(brightness),
+        (command::ORIENTATION, &[orientation]) => Command::SetOrientation(orientation),
+        (command::POWER, &[on]) => Command::SetPower(flag(on, cmd)?),
+        (command::FUN_MODE, &[enable]) => Command::SetFunMode(flag(enable, cmd)?),
+        (command::PIXEL, &[0x00, r, g, b, x, y]) => Command::SetPixel {
+            x,
+            y,
+            color: Rgb(r, g, b),
+        },
+        (command::SET_TIME, &[hour, minute, second, 0x00]) => Command::SetTime {
+            hour,
+            minute,
+            second,
+        },
+        (command::CLOCK_MODE, &[style, format_24, show_date, year, month, day, weekday]) => {
+            Command::ClockMode {
+                style,
+                format_24: flag(format_24, cmd)?,
+                show_date: flag(show_date, cmd)?,
+                date: ClockDate {
+                    year: 2000 + year as u16,
+                    month,
+                    day,
+                    weekday,
+                },
+            }
+        }
+        (command::RHYTHM_MODE, [style, levels @ ..]) => Command::RhythmMode {
+            style: *style,
+            levels: levels.try_into().map_err(|_| invalid())?,
+        },
+        (command::RHYTHM_MODE_2, &[time, style]) => Command::RhythmMode2 { style, time },
+        (_, _) => {
+            let Some(header_len) = upload_header_len(cmd) else {
+                return Err(if SINGLE_FRAME_COMMANDS.contains(&cmd) {
+                    invalid()
+                } else {
+                    DecodeError::UnknownCommand(cmd)
+                });
+            };
+            if payload.len() < 9 + header_len {
+                return Err(invalid());
+            }
+            let first = match payload[0] {
+                protocol::UPLOAD_FIRST => true,
+                protocol::UPLOAD_CONTINUATION => false,
+                _ => return Err(invalid()),
+            };
+            Command::UploadChunk {
+                command: cmd,
+                first,
+                total_size: u32_le(&payload[1..5]),
+                crc32: u32_le(&payload[5..9]),
+                header: payload[9..9 + header_len].to_vec(),
+                chunk: payload[9 + header_len..].to_vec(),
+            }
+        }
+    };
+
+    // Reject values the encoders would refuse, e.g. a brightness above 100
+    decoded.encode()?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn hex(frame: &str) -> Vec<u8> {
+        frame
+            .split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+            .collect()
+    }
+
+    const DATE: ClockDate = ClockDate {
+        year: 2025,
+        month: 3,
+        day: 14,
+        weekday: 5,
+    };
+
+    /// Frames this encoder produces for each operation, decoded by hand.
+    /// What goes over the air is checked against pypixelcolor with
+    /// [`PYPIXELCOLOR_FRAMES`].
+    fn reference_frames() -> Vec<(Command, &'static str)> {
+        vec![
+            (Command::SetBrightness(0), "05 00 04 80 00"),
+            (Command::SetBrightness(50), "05 00 04 80 32"),
+            (Command::SetBrightness(100), "05 00 04 80 64"),
+            (Command::SetOrientation(0), "05 00 06 80 00"),
+            (Command::SetOrientation(3), "05 00 06 80 03"),
+            (Command::SetPower(true), "05 00 07 01 01"),
+            (Command::SetPower(false), "05 00 07 01 00"),
+            (Command::SetFunMode(true), "05 00 04 01 01"),
+            (Command::SetFunMode(false), "05 00 04 01 00"),
+            (
+                Command::SetPixel {
+                    x: 0,
+                    y: 0,
+                    color: Rgb(0xff, 0x00, 0x00),
+                },
+                "0a 00 05 01 00 ff 00 00 00 00",
+            ),
+            (
+                Command::SetPixel {
+                    x: 31,
+                    y: 15,
+                    color: Rgb(0x12, 0x34, 0x56),
+                },
+                "0a 00 05 01 00 12 34 56 1f 0f",
+            ),
+            (
+                Command::SetTime {
+                    hour: 12,
+                    minute: 30,
+                    second: 45,
+                },
+                "08 00 01 80 0c 1e 2d 00",
+            ),
+            (
+                Command::ClockMode {
+                    style: 1,
+                    format_24: true,
+                    show_date: true,
+                    date: DATE,
+                },
+                "0b 00 06 01 01 01 01 19 03 0e 05",
+            ),
+            (
+                Command::ClockMode {
+                    style: 8,
+                    format_24: false,
+                    show_date: false,
+                    date: DATE,
+                },
+                "0b 00 06 01 08 00 00 19 03 0e 05",
+            ),
+            (
+                Command::RhythmMode {
+                    style: 2,
+                    levels: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 15],
+                },
+                "10 00 01 02 02 00 01 02 03 04 05 06 07 08 09 0f",
+            ),
+            (
+                Command::RhythmMode2 { style: 1, time: 7 },
+                "06 00 00 02 07 01",
+            ),
+        ]
+    }
+
+    #[test]
+    fn encodes_reference_frames() {
+        for (command, expected) in reference_frames() {
+            assert_eq!(command.encode().unwrap(), hex(expected), "{:?}", command);
+        }
+    }
+
+    #[test]
+    fn decodes_reference_frames() {
+        for (command, frame) in reference_frames() {
+            assert_eq!(decode(&hex(frame)).unwrap(), command, "{}", frame);
+        }
+    }
+
+    /// Frames pypixelcolor writes for each call, recorded by
+    /// `python-backend/capture_reference_frames.py`. Its `generator` tells
+    /// which pypixelcolor version they come from.
+    const PYPIXELCOLOR_FRAMES: &str = include_str!("../../tests/fixtures/pypixelcolor_frames.json");
+
+    #[derive(Deserialize)]
+    struct Capture {
+        generator: String,
+        /// Clock of the capture, `DATE` at 12:30:45
+        now: String,
+        calls: Vec<Call>,
+    }
+
+    #[derive(Deserialize)]
+    struct Call {
+        call: String,
+        args: serde_json::Value,
+        frames: Vec<String>,
+    }
+
+    fn capture() -> Capture {
+        let capture: Capture = serde_json::from_str(PYPIXELCOLOR_FRAMES).unwrap();
+        assert_eq!(capture.now, "2025-03-14T12:30:45");
+        capture
+    }
+
+    /// Frames of this encoder for a pypixelcolor call
+    fn encode_call(call: &Call) -> Vec<Vec<u8>> {
+        let number = |name: &str| call.args[name].as_u64().unwrap() as u8;
+        let flag = |name: &str| call.args[name].as_bool().unwrap();
+        let frame = match call.call.as_str() {
+            // Connecting sets the clock
+            "connect" => protocol::set_time(12, 30, 45),
+            "set_brightness" => protocol::set_brightness(number("brightness")),
+            "set_orientation" => protocol::set_orientation(number("orientation")),
+            "set_power" => protocol::set_power(flag("on")),
+            "set_fun_mode" => protocol::set_fun_mode(flag("enable")),
+            "set_pixel" => {
+                let color = Rgb::from_hex(call.args["color"].as_str().unwrap()).unwrap();
+                protocol::set_pixel(number("x"), number("y"), color)
+            }
+            "set_clock_mode" => protocol::set_clock_mode(
+                number("style"),
+                flag("format_24"),
+                flag("show_date"),
+                DATE,
+            ),
+            "set_rhythm_mode" => {
+                let levels: [u8; 11] = serde_json::from_value(call.args["levels"].clone()).unwrap();
+                protocol::set_rhythm_mode(number("style"), &levels)
+            }
+            "set_rhythm_mode_2" => protocol::set_rhythm_mode_2(number("style"), number("time")),
+            other => panic!("no encoder for the {} call", other),
+        };
+        vec![frame.unwrap()]
+    }
+
+    #[test]
+    fn encodes_like_pypixelcolor() {
+        let capture = capture();
+        for call in &capture.calls {
+            let expected: Vec<Vec<u8>> = call.frames.iter().map(|frame| hex(frame)).collect();
+            assert_eq!(
+                encode_call(call),
+                expected,
+                "{} {} ({})",
+                call.call,
+                call.args,
+                capture.generator
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_pypixelcolor_frames() {
+        for call in capture().calls {
+            for frame in call.frames.iter().map(|frame| hex(frame)) {
+                let command = decode(&frame).unwrap();
+                assert_eq!(command.encode().unwrap(), frame, "{:?}", command);
+            }
+        }
+    }
+
+    #[test]
+    fn uploads_round_trip() {
+        let png = vec![0x5a; protocol::UPLOAD_CHUNK_SIZE + 3];
+        let frames = protocol::send_image(&png).unwrap();
+
+        let chunks: Vec<Command> = frames.iter().map(|f| decode(f).unwrap()).collect();
+        let mut data = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let Command::UploadChunk {
+                command,
+                first,
+                total_size,
+                crc32,
+                header,
+                chunk,
+            } = chunk
+            else {
+                panic!("not an upload chunk: {:?}", chunk);
+            };
+            assert_eq!(*command, command::IMAGE);
+            assert_eq!(*first, index == 0);
+            assert_eq!(*total_size as usize, png.len());
+            assert_eq!(*crc32, crc32fast::hash(&png));
+            assert_eq!(header, &[0x00]);
+            data.extend_from_slice(chunk);
+        }
+        assert_eq!(data, png);
+
+        for (frame, chunk) in frames.iter().zip(&chunks) {
+            assert_eq!(&chunk.encode().unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn text_header_is_split_from_glyph_data() {
+        let glyph = protocol::Glyph {
+            width: 2,
+            height: 8,
+            columns: vec![0xff, 0x81],
+        };
+        let options = protocol::TextOptions {
+            color: Rgb(1, 2, 3),
+            animation: 1,
+            speed: 50,
+            rainbow_mode: 0,
+            slot: 0,
+        };
+        let frames = protocol::send_text(&[glyph], options).unwrap();
+
+        assert_eq!(
+            decode(&frames[0]).unwrap(),
+            Command::UploadChunk {
+                command: command::TEXT,
+                first: true,
+                total_size: 4,
+                crc32: crc32fast::hash(&[2, 8, 0xff, 0x81]),
+                header: vec![0, 1, 1, 50, 0, 1, 2, 3],
+                chunk: vec![2, 8, 0xff, 0x81],
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_frames() {
+        assert_eq!(decode(&[0x05, 0x00]), Err(DecodeError::Truncated(2)));
+        assert_eq!(
+            decode(&hex("06 00 04 80 32")),
+            Err(DecodeError::LengthMismatch {
+                declared: 6,
+                actual: 5
+            })
+        );
+        assert_eq!(
+            decode(&hex("05 00 99 99 00")),
+            Err(DecodeError::UnknownCommand([0x99, 0x99]))
+        );
+        assert_eq!(
+            decode(&hex("06 00 04 80 32 00")),
+            Err(DecodeError::InvalidPayload(command::BRIGHTNESS))
+        );
+        assert_eq!(
+            decode(&hex("05 00 07 01 02")),
+            Err(DecodeError::InvalidPayload(command::POWER))
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        assert!(matches!(
+            decode(&hex("05 00 04 80 65")),
+            Err(DecodeError::Protocol(ProtocolError::OutOfRange {
+                field: "brightness",
+                ..
+            }))
+        ));
+        assert!(matches!(
+            decode(&hex("06 00 00 02 08 01")),
+            Err(DecodeError::Protocol(ProtocolError::OutOfRange { .. }))
+        ));
+    }
+}
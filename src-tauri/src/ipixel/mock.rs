@@ -0,0 +1,98 @@
+//! In-memory transport for tests, recording every frame written to it
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::transport::{Transport, TransportError};
+
+#[derive(Default)]
+struct MockState {
+    frames: Vec<Vec<u8>>,
+    notifications: VecDeque<Vec<u8>>,
+    fail_after: Option<usize>,
+    connected: bool,
+}
+
+/// Transport that keeps frames in memory.
+///
+/// Clones share the same state, so a test can keep one handle to inspect what
+/// the panel received while the other one is owned by a [`super::Panel`].
+#[derive(Clone)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockState {
+                connected: true,
+                ..MockState::default()
+            })),
+        }
+    }
+
+    /// Frames written so far
+    pub fn frames(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().frames.clone()
+    }
+
+    /// Queue a notification returned by the next `read_notification`
+    pub fn push_notification(&self, notification: Vec<u8>) {
+        self.state
+            .lock()
+            .unwrap()
+            .notifications
+            .push_back(notification);
+    }
+
+    /// Make every write fail once `count` frames have been written
+    pub fn fail_after(&self, count: usize) {
+        self.state.lock().unwrap().fail_after = Some(count);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state.lock().unwrap().connected
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.connected {
+            return Err(TransportError::NotConnected);
+        }
+        if state
+            .fail_after
+            .is_some_and(|count| state.frames.len() >= count)
+        {
+            return Err(TransportError::Io("simulated write failure".to_string()));
+        }
+        state.frames.push(frame.to_vec());
+        Ok(())
+    }
+
+    async fn read_notification(&mut self, _timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        self.state
+            .lock()
+            .unwrap()
+            .notifications
+            .pop_front()
+            .ok_or(TransportError::Timeout)
+    }
+
+    async fn disconnect(&mut self) -> Result<(), TransportError> {
+        self.state.lock().unwrap().connected = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! Native implementation of the iPixel Color protocol.
+//!
+//! [`protocol`] turns panel operations into byte frames and [`codec`] decodes
+//! them back, a [`Transport`] carries them to the panel and [`Panel`] ties
+//! both together. The Bluetooth transport is only built with the `native-ble`
+//! feature; [`mock`] provides an in-memory transport for tests.
+
+#[cfg(feature = "native-ble")]
+pub mod ble;
+pub mod codec;
+pub mod mock;
+pub mod protocol;
+pub mod transport;
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use protocol::{ClockDate, DeviceInfoReport, Glyph, ProtocolError, Rgb, TextOptions};
+pub use transport::{Transport, TransportError};
+
+/// How long to wait for the panel to answer a query
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Failure of a panel operation
+#[derive(Debug, Error)]
+pub enum PanelError {
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+}
+
+/// A connected panel
+pub struct Panel<T: Transport> {
+    transport: T,
+    fun_mode: bool,
+}
+
+impl<T: Transport> Panel<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            fun_mode: false,
+        }
+    }
+
+    async fn send(&mut self, frame: Vec<u8>) -> Result<(), PanelError> {
+        self.transport.write_frame(&frame).await?;
+        Ok(())
+    }
+
+    async fn send_all(&mut self, frames: Vec<Vec<u8>>) -> Result<(), PanelError> {
+        for frame in frames {
+            self.transport.write_frame(&frame).await?;
+        }
+        Ok(())
+    }
+
+    /// Set the panel clock and read the device info it answers with
+    pub async fn device_info(
+        &mut self,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<DeviceInfoReport, PanelError> {
+        self.send(protocol::set_time(hour, minute, second)?).await?;
+
+        loop {
+            let notification = self
+                .transport
+                .read_notification(NOTIFICATION_TIMEOUT)
+                .await?;
+            if let Some(info) = protocol::parse_device_info(&notification) {
+                return Ok(info);
+            }
+        }
+    }
+
+    pub async fn set_brightness(&mut self, brightness: u8) -> Result<(), PanelError> {
+        self.send(protocol::set_brightness(brightness)?).await
+    }
+
+    pub async fn set_orientation(&mut self, orientation: u8) -> Result<(), PanelError> {
+        self.send(protocol::set_orientation(orientation)?).await
+    }
+
+    pub async fn set_power(&mut self, on: bool) -> Result<(), PanelError> {
+        self.send(protocol::set_power(on)?).await
+    }
+
+    pub async fn set_clock_mode(
+        &mut self,
+        style: u8,
+        format_24: bool,
+        show_date: bool,
+        date: ClockDate,
+    ) -> Result<(), PanelError> {
+        self.fun_mode = false;
+        self.send(protocol::set_clock_mode(style, format_24, show_date, date)?)
+            .await
+    }
+
+    pub async fn set_rhythm_mode(
+        &mut self,
+        style: u8,
+        levels: &[u8; 11],
+    ) -> Result<(), PanelError> {
+        self.fun_mode = false;
+        self.send(protocol::set_rhythm_mode(style, levels)?).await
+    }
+
+    pub async fn set_rhythm_mode_2(&mut self, style: u8, time: u8) -> Result<(), PanelError> {
+        self.fun_mode = false;
+        self.send(protocol::set_rhythm_mode_2(style, time)?).await
+    }
+
+    pub async fn set_fun_mode(&mut self, enable: bool) -> Result<(), PanelError> {
+        self.send(protocol::set_fun_mode(enable)?).await?;
+        self.fun_mode = enable;
+        Ok(())
+    }
+
+    /// Draw pixels, switching to DIY mode first if needed
+    pub async fn set_pixels(&mut self, pixels: &[(u8, u8, Rgb)]) -> Result<(), PanelError> {
+        if !self.fun_mode {
+            self.set_fun_mode(true).await?;
+        }
+        for &(x, y, color) in pixels {
+            self.send(protocol::set_pixel(x, y, color)?).await?;
+        }
+        Ok(())
+    }
+
+    /// Show a PNG image
+    pub async fn send_image(&mut self, png: &[u8]) -> Result<(), PanelError> {
+        self.fun_mode = false;
+        self.send_all(protocol::send_image(png)?).await
+    }
+
+    /// Play an animated GIF
+    pub async fn send_gif(&mut self, gif: &[u8], slot: u8) -> Result<(), PanelError> {
+        self.fun_mode = false;
+        self.send_all(protocol::send_gif(gif, slot)?).await
+    }
+
+    /// Show text rendered as glyph bitmaps
+    pub async fn send_text(
+        &mut self,
+        glyphs: &[Glyph],
+        options: TextOptions,
+    ) -> Result<(), PanelError> {
+        self.fun_mode = false;
+        self.send_all(protocol::send_text(glyphs, options)?).await
+    }
+
+    pub async fn disconnect(&mut self) -> Result<(), PanelError> {
+        self.transport.disconnect().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockTransport;
+    use super::*;
+
+    #[tokio::test]
+    async fn pixels_enable_diy_mode_once() {
+        let mock = MockTransport::new();
+        let mut panel = Panel::new(mock.clone());
+
+        panel.set_pixels(&[(0, 0, Rgb(255, 0, 0))]).await.unwrap();
+        panel.set_pixels(&[(1, 2, Rgb(0, 255, 0))]).await.unwrap();
+
+        assert_eq!(
+            mock.frames(),
+            vec![
+                vec![0x05, 0x00, 0x04, 0x01, 0x01],
+                vec![0x0a, 0x00, 0x05, 0x01, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00],
+                vec![0x0a, 0x00, 0x05, 0x01, 0x00, 0x00, 0xff, 0x00, 0x01, 0x02],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_parameters_are_not_sent() {
+        let mock = MockTransport::new();
+        let mut panel = Panel::new(mock.clone());
+
+        assert!(matches!(
+            panel.set_brightness(101).await,
+            Err(PanelError::Protocol(ProtocolError::OutOfRange { .. }))
+        ));
+        assert!(mock.frames().is_empty());
+    }
+
+    #[tokio::test]
+    async fn device_info_is_read_from_notification() {
+        let mock = MockTransport::new();
+        mock.push_notification(vec![0x09, 0x00, 0x01, 0x80, 0x82, 0x01, 0x02, 0x03, 0x00]);
+        let mut panel = Panel::new(mock.clone());
+
+        let info = panel.device_info(12, 30, 0).await.unwrap();
+
+        assert_eq!((info.width, info.height), (32, 32));
+        assert_eq!(info.led_type, 3);
+        assert!(!info.has_wifi);
+        assert_eq!(
+            mock.frames(),
+            vec![vec![0x08, 0x00, 0x01, 0x80, 12, 30, 0, 0]]
+        );
+    }
+
+    #[tokio::test]
+    async fn large_images_are_split_into_chunks() {
+        let mock = MockTransport::new();
+        let mut panel = Panel::new(mock.clone());
+        let png = vec![0xab; protocol::UPLOAD_CHUNK_SIZE + 10];
+
+        panel.send_image(&png).await.unwrap();
+
+        let frames = mock.frames();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0][4], 0x00);
+        assert_eq!(frames[1][4], 0x02);
+        assert_eq!(&frames[0][5..9], &(png.len() as u32).to_le_bytes());
+    }
+
+    #[tokio::test]
+    async fn transport_failures_are_reported() {
+        let mock = MockTransport::new();
+        mock.fail_after(0);
+        let mut panel = Panel::new(mock.clone());
+
+        assert!(matches!(
+            panel.set_power(true).await,
+            Err(PanelError::Transport(TransportError::Io(_)))
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+//! iPixel Color command framing.
+//!
+//! Every command is a single frame:
+//!
+//! ```text
+//! [len_lo, len_hi, cmd_lo, cmd_hi, payload...]
+//! ```
+//!
+//! where `len` is the little-endian length of the whole frame, length bytes
+//! included. Larger uploads (text, images, GIFs) are split into several frames
+//! by [`encode_upload`], each carrying the total size and CRC32 of the data.
+//!
+//! This module only builds bytes, it has no knowledge of the link to the
+//! panel; [`super::codec`] decodes frames back.
+
+use thiserror::Error;
+
+/// Command identifiers, as sent in bytes 2-3 of a frame
+pub mod command {
+    pub const TEXT: [u8; 2] = [0x00, 0x01];
+    pub const IMAGE: [u8; 2] = [0x02, 0x00];
+    pub const GIF: [u8; 2] = [0x03, 0x00];
+    pub const SET_TIME: [u8; 2] = [0x01, 0x80];
+    pub const BRIGHTNESS: [u8; 2] = [0x04, 0x80];
+    pub const ORIENTATION: [u8; 2] = [0x06, 0x80];
+    pub const FUN_MODE: [u8; 2] = [0x04, 0x01];
+    pub const PIXEL: [u8; 2] = [0x05, 0x01];
+    pub const CLOCK_MODE: [u8; 2] = [0x06, 0x01];
+    pub const POWER: [u8; 2] = [0x07, 0x01];
+    pub const RHYTHM_MODE_2: [u8; 2] = [0x00, 0x02];
+    pub const RHYTHM_MODE: [u8; 2] = [0x01, 0x02];
+}
+
+/// Maximum amount of upload data carried by a single frame
+pub const UPLOAD_CHUNK_SIZE: usize = 12 * 1024;
+/// Flag of the first frame of an upload
+pub const UPLOAD_FIRST: u8 = 0x00;
+/// Flag of the following frames of an upload
+pub const UPLOAD_CONTINUATION: u8 = 0x02;
+
+/// Invalid parameters for a panel command
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProtocolError {
+    #[error("{field} must be between {min} and {max}, got {value}")]
+    OutOfRange {
+        field: &'static str,
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    #[error("invalid color \"{0}\", expected RRGGBB")]
+    InvalidColor(String),
+    #[error("{0} is empty")]
+    Empty(&'static str),
+    #[error("frame of {0} bytes is too large")]
+    TooLarge(usize),
+}
+
+fn check_range(field: &'static str, value: u8, min: u8, max: u8) -> Result<u8, ProtocolError> {
+    if value < min || value > max {
+        return Err(ProtocolError::OutOfRange {
+            field,
+            value: value.into(),
+            min: min.into(),
+            max: max.into(),
+        });
+    }
+    Ok(value)
+}
+
+/// RGB color as sent to the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parse a `RRGGBB` or `#RRGGBB` hex string
+    pub fn from_hex(hex: &str) -> Result<Self, ProtocolError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let invalid = || ProtocolError::InvalidColor(hex.to_string());
+        if digits.len() != 6 || !digits.is_ascii() {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    pub fn to_hex(self) -> String {
+        format!("{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+/// Calendar date sent along with the clock mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockDate {
+    /// Full year, only the last two digits are sent
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    /// 1 (Monday) to 7 (Sunday)
+    pub weekday: u8,
+}
+
+/// Build a frame from a command and its payload
+pub fn frame(command: [u8; 2], payload: &[u8]) -> Result<Vec<u8>, ProtocolError> {
+    let len = payload.len() + 4;
+    let len_bytes = u16::try_from(len)
+        .map_err(|_| ProtocolError::TooLarge(len))?
+        .to_le_bytes();
+
+    let mut frame = Vec::with_capacity(len);
+    frame.extend_from_slice(&len_bytes);
+    frame.extend_from_slice(&command);
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Set the brightness (0-100)
+pub fn set_brightness(brightness: u8) -> Result<Vec<u8>, ProtocolError> {
+    frame(
+        command::BRIGHTNESS,
+        &[check_range("brightness", brightness, 0, 100)?],
+    )
+}
+
+/// Set the orientation (0-3: 0°, 90°, 180°, 270°)
+pub fn set_orientation(orientation: u8) -> Result<Vec<u8>, ProtocolError> {
+    frame(
+        command::ORIENTATION,
+        &[check_range("orientation", orientation, 0, 3)?],
+    )
+}
+
+/// Turn the panel on or off
+pub fn set_power(on: bool) -> Result<Vec<u8>, ProtocolError> {
+    frame(command::POWER, &[on as u8])
+}
+
+/// Enable or disable the DIY (fun) mode used for pixel writes
+pub fn set_fun_mode(enable: bool) -> Result<Vec<u8>, ProtocolError> {
+    frame(command::FUN_MODE, &[enable as u8])
+}
+
+/// Set a single pixel, the panel must be in DIY mode
+pub fn set_pixel(x: u8, y: u8, color: Rgb) -> Result<Vec<u8>, ProtocolError> {
+    frame(command::PIXEL, &[0x00, color.0, color.1, color.2, x, y])
+}
+
+/// Set the panel clock, also used to query the device info
+pub fn set_time(hour: u8, minute: u8, second: u8) -> Result<Vec<u8>, ProtocolError> {
+    frame(
+        command::SET_TIME,
+        &[
+            check_range("hour", hour, 0, 23)?,
+            check_range("minute", minute, 0, 59)?,
+            check_range("second", second, 0, 59)?,
+            0x00,
+        ],
+    )
+}
+
+/// Show the clock with the given style (0-8)
+pub fn set_clock_mode(
+    style: u8,
+    format_24: bool,
+    show_date: bool,
+    date: ClockDate,
+) -> Result<Vec<u8>, ProtocolError> {
+    frame(
+        command::CLOCK_MODE,
+        &[
+            check_range("clock style", style, 0, 8)?,
+            format_24 as u8,
+            show_date as u8,
+            (date.year % 100) as u8,
+            check_range("month", date.month, 1, 12)?,
+            check_range("day", date.day, 1, 31)?,
+            check_range("weekday", date.weekday, 1, 7)?,
+        ],
+    )
+}
+
+/// Rhythm mode v1 with a style (0-4) and 11 levels (0-15)
+pub fn set_rhythm_mode(style: u8, levels: &[u8; 11]) -> Result<Vec<u8>, ProtocolError> {
+    let mut payload = Vec::with_capacity(12);
+    payload.push(check_range("rhythm style", style, 0, 4)?);
+    for level in levels {
+        payload.push(check_range("rhythm level", *level, 0, 15)?);
+    }
+    frame(command::RHYTHM_MODE, &payload)
+}
+
+/// Rhythm mode v2 with a style (0-1) and an animation time (0-7)
+pub fn set_rhythm_mode_2(style: u8, time: u8) -> Result<Vec<u8>, ProtocolError> {
+    frame(
+        command::RHYTHM_MODE_2,
+        &[
+            check_range("rhythm time", time, 0, 7)?,
+            check_range("rhythm style", style, 0, 1)?,
+        ],
+    )
+}
+
+/// Split an upload into frames.
+///
+/// Each frame is `[len(2), command(2), flag(1), total_size(4), crc32(4),
+/// header..., chunk...]`, the flag being `0x00` for the first frame and `0x02`
+/// for the following ones. `header` is repeated in every frame.
+pub fn encode_upload(
+    command: [u8; 2],
+    header: &[u8],
+    data: &[u8],
+) -> Result<Vec<Vec<u8>>, ProtocolError> {
+    if data.is_empty() {
+        return Err(ProtocolError::Empty("upload"));
+    }
+    let total = u32::try_from(data.len()).map_err(|_| ProtocolError::TooLarge(data.len()))?;
+    let crc = crc32fast::hash(data);
+
+    data.chunks(UPLOAD_CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut payload = Vec::with_capacity(9 + header.len() + chunk.len());
+            payload.push(if index == 0 {
+                UPLOAD_FIRST
+            } else {
+                UPLOAD_CONTINUATION
+            });
+            payload.extend_from_slice(&total.to_le_bytes());
+            payload.extend_from_slice(&crc.to_le_bytes());
+            payload.extend_from_slice(header);
+            payload.extend_from_slice(chunk);
+            frame(command, &payload)
+        })
+        .collect()
+}
+
+/// Upload a static image (PNG data, decoded by the panel)
+pub fn send_image(png: &[u8]) -> Result<Vec<Vec<u8>>, ProtocolError> {
+    encode_upload(command::IMAGE, &[0x00], png)
+}
+
+/// Upload an animated GIF to the given save slot
+pub fn send_gif(gif: &[u8], slot: u8) -> Result<Vec<Vec<u8>>, ProtocolError> {
+    encode_upload(command::GIF, &[slot], gif)
+}
+
+/// One rendered character: `width` columns of `height` pixels, packed
+/// column-major with 8 vertical pixels per byte (least significant bit on top)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    pub width: u8,
+    pub height: u8,
+    pub columns: Vec<u8>,
+}
+
+/// Display options of a text upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextOptions {
+    pub color: Rgb,
+    /// 0-7
+    pub animation: u8,
+    /// 0-100
+    pub speed: u8,
+    /// 0-9
+    pub rainbow_mode: u8,
+    pub slot: u8,
+}
+
+/// Upload text as pre-rendered glyphs.
+///
+/// The firmware has no fonts, characters are rasterized on the host and sent
+/// as bitmaps after a header describing how to animate them.
+pub fn send_text(glyphs: &[Glyph], options: TextOptions) -> Result<Vec<Vec<u8>>, ProtocolError> {
+    if glyphs.is_empty() {
+        return Err(ProtocolError::Empty("text"));
+    }
+    let char_count =
+        u8::try_from(glyphs.len()).map_err(|_| ProtocolError::TooLarge(glyphs.len()))?;
+
+    let header = [
+        options.slot,
+        char_count,
+        check_range("animation", options.animation, 0, 7)?,
+        check_range("speed", options.speed, 0, 100)?,
+        check_range("rainbow mode", options.rainbow_mode, 0, 9)?,
+        options.color.0,
+        options.color.1,
+        options.color.2,
+    ];
+
+    let mut data = Vec::new();
+    for glyph in glyphs {
+        let expected = glyph.width as usize * (glyph.height as usize).div_ceil(8);
+        if glyph.columns.len() != expected {
+            return Err(ProtocolError::OutOfRange {
+                field: "glyph bitmap size",
+                value: glyph.columns.len() as i64,
+                min: expected as i64,
+                max: expected as i64,
+            });
+        }
+        data.push(glyph.width);
+        data.push(glyph.height);
+        data.extend_from_slice(&glyph.columns);
+    }
+
+    encode_upload(command::TEXT, &header, &data)
+}
+
+/// Device information reported by the panel after a `set_time` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfoReport {
+    pub device_type: u8,
+    pub led_type: u8,
+    pub width: u16,
+    pub height: u16,
+    pub has_wifi: bool,
+}
+
+/// Panel size for a device type code, unknown models are assumed to be the
+/// common 32x32 panel
+fn panel_size(device_type: u8) -> (u16, u16) {
+    match device_type {
+        0x80 => (64, 64),
+        0x81 => (96, 16),
+        0x82 => (32, 32),
+        0x83 => (64, 16),
+        0x84 => (32, 16),
+        0x85 => (16, 16),
+        _ => (32, 32),
+    }
+}
+
+/// Parse the notification answering a `set_time` command:
+/// `[len(2), 0x01, 0x80, device_type, mcu_major, mcu_minor, led_type, wifi]`
+pub fn parse_device_info(notification: &[u8]) -> Option<DeviceInfoReport> {
+    if notification.len() < 9 || notification[2..4] != command::SET_TIME {
+        return None;
+    }
+    let device_type = notification[4];
+    let (width, height) = panel_size(device_type);
+
+    Some(DeviceInfoReport {
+        device_type,
+        led_type: notification[7],
+        width,
+        height,
+        has_wifi: notification[8] != 0,
+    })
+}
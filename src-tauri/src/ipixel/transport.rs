@@ -0,0 +1,32 @@
+//! Transport abstraction between the protocol and the physical link
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+/// Failure of the link to the panel
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("not connected to a panel")]
+    NotConnected,
+    #[error("no panel found with address {0}")]
+    NotFound(String),
+    #[error("timed out waiting for the panel")]
+    Timeout,
+    #[error("transport error: {0}")]
+    Io(String),
+}
+
+/// A link able to carry protocol frames to a panel and notifications back
+#[async_trait]
+pub trait Transport: Send {
+    /// Write one protocol frame, splitting it as the link requires
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError>;
+
+    /// Wait for the next notification sent by the panel
+    async fn read_notification(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError>;
+
+    /// Close the link
+    async fn disconnect(&mut self) -> Result<(), TransportError>;
+}
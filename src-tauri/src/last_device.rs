@@ -0,0 +1,34 @@
+//! Address of the last panel connected, so the app can reconnect to it without
+//! a scan, e.g. when running headless as a status display.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+const LAST_DEVICE_FILE_NAME: &str = "last-device";
+
+pub struct LastDevice {
+    path: PathBuf,
+}
+
+impl LastDevice {
+    pub fn new(dir: &Path) -> Self {
+        let _ = fs::create_dir_all(dir);
+        Self {
+            path: dir.join(LAST_DEVICE_FILE_NAME),
+        }
+    }
+
+    pub fn read(&self) -> Option<String> {
+        let address = fs::read_to_string(&self.path).ok()?;
+        let address = address.trim();
+        (!address.is_empty()).then(|| address.to_string())
+    }
+
+    pub fn write(&self, address: &str) {
+        if let Err(e) = fs::write(&self.path, address) {
+            warn!(error = %e, path = %self.path.display(), "Failed to remember the connected device");
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Content to show on the panel, given on the command line.
+//!
+//! `pixelart-controller <image>` or `pixelart-controller --text "Hello"` sends
+//! it to the panel once the app is ready. Only one instance runs at a time: a
+//! second launch forwards its arguments to the running one and exits.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{info, warn};
+
+use crate::imaging::ConvertOptions;
+use crate::panel::commands::panel_api;
+use crate::panel::types::TextRequest;
+use crate::panel::{send_image_resized, ApiError};
+use crate::{tray, HEADLESS_ARG};
+
+/// Number of requests kept for the tray "Recent" menu
+const MAX_RECENT: usize = 5;
+
+/// Something a launch asks to show on the panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaunchRequest {
+    /// Image or GIF file
+    Image(PathBuf),
+    Text(String),
+}
+
+impl LaunchRequest {
+    /// Parse the arguments following the executable path, relative paths are
+    /// resolved from `cwd`. Arguments that are not for us, e.g. added by the
+    /// OS or `tauri dev`, are skipped.
+    pub fn parse(args: &[String], cwd: &Path) -> Vec<LaunchRequest> {
+        let mut requests = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--text" => match args.next() {
+                    Some(text) => requests.push(LaunchRequest::Text(text.clone())),
+                    None => warn!("--text expects a value"),
+                },
+                HEADLESS_ARG => {}
+                arg if arg.starts_with('-') => warn!(arg, "Ignoring unknown argument"),
+                path => requests.push(LaunchRequest::Image(cwd.join(path))),
+            }
+        }
+        requests
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            LaunchRequest::Image(path) => path.display().to_string(),
+            LaunchRequest::Text(text) => format!("\"{}\"", text),
+        }
+    }
+
+    fn send<R: Runtime>(&self, app: &AppHandle<R>) -> Result<(), ApiError> {
+        let api = panel_api(app)?;
+        match self {
+            LaunchRequest::Image(path) => {
+                let data = std::fs::read(path).map_err(|e| {
+                    ApiError::Invalid(format!("Failed to read {}: {}", path.display(), e))
+                })?;
+                let filename = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "image".to_string());
+                send_image_resized(api.as_ref(), &filename, &data, &ConvertOptions::default())?;
+            }
+            LaunchRequest::Text(text) => {
+                api.send_text(&TextRequest {
+                    text: text.clone(),
+                    color: None,
+                    font: None,
+                    animation: None,
+                    speed: None,
+                    rainbow_mode: None,
+                    char_height: None,
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of a launch request, emitted as `app://launch-request`
+#[derive(Clone, Serialize)]
+struct LaunchOutcome {
+    request: String,
+    error: Option<String>,
+}
+
+/// Content sent successfully, most recent first
+#[derive(Default)]
+pub struct RecentContent(Mutex<VecDeque<LaunchRequest>>);
+
+impl RecentContent {
+    pub fn list(&self) -> Vec<LaunchRequest> {
+        self.0
+            .lock()
+            .map(|recent| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn push(&self, request: LaunchRequest) {
+        if let Ok(mut recent) = self.0.lock() {
+            recent.retain(|r| *r != request);
+            recent.push_front(request);
+            recent.truncate(MAX_RECENT);
+        }
+    }
+}
+
+/// Requests received before the panel can be reached, `None` once it can
+pub struct PendingLaunchRequests(Mutex<Option<Vec<LaunchRequest>>>);
+
+impl PendingLaunchRequests {
+    pub fn new(requests: Vec<LaunchRequest>) -> Self {
+        Self(Mutex::new(Some(requests)))
+    }
+}
+
+/// Send the requests now if the panel can be reached, otherwise once it can
+pub fn submit<R: Runtime>(app: &AppHandle<R>, requests: Vec<LaunchRequest>) {
+    let state = app.state::<PendingLaunchRequests>();
+    let Ok(mut pending) = state.0.lock() else {
+        return;
+    };
+    match pending.as_mut() {
+        Some(queue) => queue.extend(requests),
+        None => send_all(app, requests),
+    }
+}
+
+/// Send the queued requests, and the later ones right away. Called once the
+/// panel can be reached.
+pub fn flush<R: Runtime>(app: &AppHandle<R>) {
+    let queued = app
+        .state::<PendingLaunchRequests>()
+        .0
+        .lock()
+        .ok()
+        .and_then(|mut pending| pending.take());
+    if let Some(requests) = queued {
+        send_all(app, requests);
+    }
+}
+
+fn send_all<R: Runtime>(app: &AppHandle<R>, requests: Vec<LaunchRequest>) {
+    if requests.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        for request in requests {
+            info!(request = %request.describe(), "Sending content from the command line");
+            let error = match request.send(&app) {
+                Ok(()) => {
+                    app.state::<RecentContent>().push(request.clone());
+                    tray::refresh(&app);
+                    None
+                }
+                Err(e) => {
+                    warn!(request = %request.describe(), error = %e, "Failed to send content from the command line");
+                    Some(e.to_string())
+                }
+            };
+            let _ = app.emit(
+                "app://launch-request",
+                LaunchOutcome {
+                    request: request.describe(),
+                    error,
+                },
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Vec<LaunchRequest> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        LaunchRequest::parse(&args, Path::new("/home/user"))
+    }
+
+    #[test]
+    fn parses_images_and_text() {
+        assert_eq!(
+            parse(&[
+                "cat.gif",
+                "--headless",
+                "--text",
+                "Hello world",
+                "--unknown",
+                "/tmp/logo.png"
+            ]),
+            vec![
+                LaunchRequest::Image(PathBuf::from("/home/user/cat.gif")),
+                LaunchRequest::Text("Hello world".to_string()),
+                LaunchRequest::Image(PathBuf::from("/tmp/logo.png")),
+            ]
+        );
+        assert_eq!(parse(&["--text"]), vec![]);
+    }
+}
@@ -1,16 +1,283 @@
-use std::process::{Child, Command};
+mod backend_lock;
+mod backend_log;
+mod calibration;
+pub mod cli;
+pub mod font;
+mod font_library;
+pub mod imaging;
+pub mod ipixel;
+mod last_device;
+mod launch_request;
+mod logging;
+#[cfg(feature = "mock-backend")]
+pub mod mock_backend;
+mod panel;
+pub mod process_tree;
+#[cfg(target_os = "linux")]
+mod service;
+mod startup_error;
+mod status_relay;
+#[cfg(desktop)]
+mod tray;
+
+/// No tray off the desktop, the app is never headless there
+#[cfg(not(desktop))]
+mod tray {
+    use tauri::{AppHandle, Runtime};
+
+    pub struct Headless;
+
+    pub fn is_headless<R: Runtime>(_app: &AppHandle<R>) -> bool {
+        false
+    }
+
+    pub fn create<R: Runtime>(_app: &AppHandle<R>) -> tauri::Result<()> {
+        Ok(())
+    }
+
+    pub fn refresh<R: Runtime>(_app: &AppHandle<R>) {}
+
+    pub fn reconnect<R: Runtime>(_app: &AppHandle<R>) {}
+
+    pub fn show_startup_status<R: Runtime>(_app: &AppHandle<R>, _status: &crate::StartupStatus) {}
+}
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
-use tauri::{AppHandle, Manager, Runtime};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State, WebviewWindow, WebviewWindowBuilder};
+use tracing::{debug, error, info, info_span, warn};
+
+use backend_lock::BackendLock;
+use panel::types::{Capability, HealthResponse};
+use panel::{ApiError, BackendClient, PanelApi};
+use backend_log::{BackendLog, BackendLogLine};
+use calibration::CalibrationStore;
+use font_library::FontLibrary;
+use last_device::LastDevice;
+use launch_request::{LaunchRequest, PendingLaunchRequests, RecentContent};
+use startup_error::StartupError;
+use status_relay::ConnectionState;
+
+/// Environment variable used to force a specific backend port
+const BACKEND_PORT_ENV: &str = "PIXELART_BACKEND_PORT";
+/// Environment variable passing the session token the backend must require
+const BACKEND_TOKEN_ENV: &str = "PIXELART_BACKEND_TOKEN";
+/// Environment variable selecting how the panel is reached, see [`BackendMode`]
+const BACKEND_MODE_ENV: &str = "PIXELART_BACKEND";
+/// Environment variable pointing the app at a backend it doesn't start itself
+const BACKEND_URL_ENV: &str = "PIXELART_BACKEND_URL";
+/// Sources of the Python backend, used by [`BackendMode::Source`] in development
+const BACKEND_SOURCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../python-backend");
+/// Argument running the app without window, from the system tray
+const HEADLESS_ARG: &str = "--headless";
+/// Argument of the app binary that runs the mock backend instead of the app
+pub const MOCK_BACKEND_ARG: &str = "mock-backend";
+
+/// Maximum number of consecutive restarts before the supervisor gives up
+const MAX_BACKEND_RESTARTS: u32 = 5;
+/// How often the supervisor checks whether the backend is still alive
+const SUPERVISOR_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Delay before the first restart, doubled after each consecutive crash
+const RESTART_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound for the restart delay
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(30);
+/// A backend running longer than this is considered stable again
+const BACKEND_STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// Timeout of each HTTP request sent to the backend during shutdown
+const SHUTDOWN_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long the backend gets to exit on its own before being killed
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Label of the main application window
+const MAIN_WINDOW: &str = "main";
+/// Label of the splash window shown while the backend starts
+const SPLASH_WINDOW: &str = "splashscreen";
 
 /// Backend process wrapper for lifecycle management
-struct BackendProcess(Mutex<Option<Child>>);
+#[derive(Default)]
+struct BackendProcess {
+    child: Mutex<Option<Child>>,
+    /// Set once the app is shutting down, so the supervisor stops restarting
+    shutting_down: AtomicBool,
+}
+
+impl BackendProcess {
+    fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+}
+
+/// Payload of the `backend://crashed` event
+#[derive(Clone, Serialize)]
+struct BackendCrashed {
+    exit_code: Option<i32>,
+    restarts: u32,
+    will_restart: bool,
+}
+
+/// Payload of the `backend://restarted` event
+#[derive(Clone, Serialize)]
+struct BackendRestarted {
+    pid: u32,
+    restarts: u32,
+}
+
+/// Network configuration of the running backend, shared with the webview
+#[derive(Default)]
+struct BackendConfig {
+    /// Base URL of the backend, `None` until startup has begun
+    url: Mutex<Option<String>>,
+    /// Random token of this session, required by the backend on every request
+    token: Mutex<Option<String>>,
+}
+
+impl BackendConfig {
+    fn url(&self) -> Option<String> {
+        self.url.lock().ok()?.clone()
+    }
+
+    fn token(&self) -> Option<String> {
+        self.token.lock().ok()?.clone()
+    }
+
+    /// Client for the running backend, authenticated with the session token
+    fn client(&self) -> Option<BackendClient> {
+        Some(BackendClient::new(self.url()?).with_token(self.token()))
+    }
+}
+
+/// Generate the token shared with the backend for this session.
+///
+/// Any local process can reach the backend port, the token keeps them (and
+/// web pages) from driving the panel.
+fn generate_backend_token() -> Result<String, StartupError> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| StartupError::Internal(format!("Failed to generate the backend token: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Startup progress, emitted as `backend://startup` and shown by the splash window
+#[derive(Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum StartupStatus {
+    Starting { attempt: u32, max_attempts: u32 },
+    Ready,
+    Failed {
+        /// [`StartupError::kind`]
+        kind: &'static str,
+        message: String,
+        hint: String,
+    },
+}
+
+impl From<&StartupError> for StartupStatus {
+    fn from(error: &StartupError) -> Self {
+        StartupStatus::Failed {
+            kind: error.kind(),
+            message: error.to_string(),
+            hint: error.hint(),
+        }
+    }
+}
+
+/// Last startup status, so a freshly loaded splash window can catch up
+struct StartupState(Mutex<StartupStatus>);
+
+/// Capabilities announced by the backend in its health check
+#[derive(Default)]
+struct BackendCapabilities(Mutex<Vec<Capability>>);
+
+/// Pick the loopback port for the backend.
+///
+/// Honours `PIXELART_BACKEND_PORT` when set, otherwise asks the OS for a free port.
+fn pick_backend_port() -> Result<u16, StartupError> {
+    if let Ok(value) = std::env::var(BACKEND_PORT_ENV) {
+        return value.parse().map_err(|_| StartupError::InvalidConfig {
+            variable: BACKEND_PORT_ENV,
+            value,
+        });
+    }
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map_err(StartupError::NoFreePort)?
+        .port();
+
+    Ok(port)
+}
+
+/// How the app reaches the panel, selected with `PIXELART_BACKEND`, or
+/// `PIXELART_BACKEND_URL` for a backend started by hand
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BackendMode {
+    /// The Python backend bundled in the resources directory (default)
+    Bundled,
+    /// The Python backend run from `python-backend/src` with its venv, for
+    /// development without building the bundled executable
+    Source,
+    /// The mock backend built into the app binary, no panel needed
+    Mock,
+    /// The native Bluetooth transport, no backend process
+    Native,
+    /// A backend already running at this URL, e.g. `python src/main.py` in
+    /// another terminal. The app neither starts nor stops it.
+    External(String),
+}
+
+impl BackendMode {
+    fn from_env() -> Result<Self, StartupError> {
+        if let Some(url) = std::env::var(BACKEND_URL_ENV).ok().filter(|url| !url.is_empty()) {
+            return Ok(BackendMode::External(url.trim_end_matches('/').to_string()));
+        }
 
-/// Start the Python backend executable from the resources directory
-fn start_backend<R: Runtime>(app: &AppHandle<R>) -> Result<Child, String> {
-    let resource_dir = app
-        .path()
-        .resource_dir()
-        .map_err(|e| format!("Failed to get resource directory: {}", e))?;
+        let Ok(value) = std::env::var(BACKEND_MODE_ENV) else {
+            return Ok(BackendMode::Bundled);
+        };
+        match value.to_lowercase().as_str() {
+            "" | "bundled" => Ok(BackendMode::Bundled),
+            "source" => Ok(BackendMode::Source),
+            "mock" => Ok(BackendMode::Mock),
+            "native" => Ok(BackendMode::Native),
+            _ => Err(StartupError::InvalidConfig {
+                variable: BACKEND_MODE_ENV,
+                value: format!("{} (expected bundled, source, mock or native)", value),
+            }),
+        }
+    }
+}
+
+/// Command running the backend for the selected mode. `resource_dir` is only
+/// called for the bundled backend.
+fn backend_command(
+    resource_dir: impl FnOnce() -> Result<PathBuf, StartupError>,
+) -> Result<Command, StartupError> {
+    match BackendMode::from_env()? {
+        BackendMode::Mock => {
+            if !cfg!(feature = "mock-backend") {
+                return Err(StartupError::FeatureMissing {
+                    setting: format!("{}=mock", BACKEND_MODE_ENV),
+                    feature: "mock-backend",
+                });
+            }
+            let exe = std::env::current_exe().map_err(|e| {
+                StartupError::Internal(format!("Failed to locate the app executable: {}", e))
+            })?;
+            let mut command = Command::new(exe);
+            command.arg(MOCK_BACKEND_ARG);
+            return Ok(command);
+        }
+        BackendMode::Source => return source_backend_command(),
+        _ => {}
+    }
+
+    let resource_dir = resource_dir()?;
 
     let backend_name = if cfg!(windows) {
         "resources/backend.exe"
@@ -21,89 +288,756 @@ fn start_backend<R: Runtime>(app: &AppHandle<R>) -> Result<Child, String> {
     let backend_path = resource_dir.join(backend_name);
 
     if !backend_path.exists() {
-        return Err(format!(
-            "Backend executable not found at: {}",
-            backend_path.display()
-        ));
+        return Err(StartupError::BinaryMissing(backend_path));
+    }
+    if !is_executable(&backend_path) {
+        return Err(StartupError::NotExecutable(backend_path));
+    }
+
+    Ok(Command::new(backend_path))
+}
+
+/// Command running `python-backend/src/main.py` with the interpreter of the
+/// venv created by the README setup steps
+fn source_backend_command() -> Result<Command, StartupError> {
+    let source_dir = Path::new(BACKEND_SOURCE_DIR);
+    let python = if cfg!(windows) {
+        "venv/Scripts/python.exe"
+    } else {
+        "venv/bin/python"
+    };
+    let python_path = source_dir.join(python);
+
+    if !python_path.exists() {
+        return Err(StartupError::VenvMissing(python_path));
     }
 
-    println!("Starting backend from: {}", backend_path.display());
+    let mut command = Command::new(python_path);
+    command.current_dir(source_dir).arg("src/main.py");
+    Ok(command)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// Fail with a clear error when something else already listens on `port`,
+/// the health check would otherwise mistake it for our backend
+fn ensure_port_available(port: u16) -> Result<(), StartupError> {
+    TcpListener::bind(("127.0.0.1", port))
+        .map(drop)
+        .map_err(|source| StartupError::PortInUse { port, source })
+}
+
+/// Pass the port and token to a backend command, and give it its own process
+/// group so the interpreter spawned by the PyInstaller bootloader can be
+/// stopped along with it
+fn configure_backend_command<'a>(command: &'a mut Command, port: u16, token: &str) -> &'a mut Command {
+    process_tree::isolate(command)
+        .arg("--port")
+        .arg(port.to_string())
+        .env(BACKEND_PORT_ENV, port.to_string())
+        .env(BACKEND_TOKEN_ENV, token)
+}
 
-    let child = Command::new(&backend_path)
+/// Start the backend process: the bundled Python executable, the Python
+/// sources or the mock
+fn start_backend<R: Runtime>(app: &AppHandle<R>, port: u16) -> Result<Child, StartupError> {
+    let mut command = backend_command(|| {
+        app.path()
+            .resource_dir()
+            .map_err(|e| StartupError::ResourceDirMissing(e.to_string()))
+    })?;
+    let token = app.state::<BackendConfig>().token().unwrap_or_default();
+
+    let _span = info_span!("backend_spawn", port, path = %command.get_program().to_string_lossy()).entered();
+    info!("Starting backend");
+
+    let mut child = configure_backend_command(&mut command, port, &token)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
-        .map_err(|e| format!("Failed to spawn backend process: {}", e))?;
+        .map_err(StartupError::Spawn)?;
 
-    println!("Backend process started with PID: {:?}", child.id());
+    info!(pid = child.id(), "Backend process started");
+
+    if let Err(e) = app.state::<BackendLock>().write(child.id(), port, &token) {
+        warn!(error = %e, "Failed to write the backend lock file");
+    }
+
+    // Keep the backend output, it is otherwise lost in packaged builds
+    backend_log::capture(app, &mut child);
 
     Ok(child)
 }
 
-/// Wait for the backend to become ready by polling the health endpoint
-fn wait_for_backend() -> Result<(), String> {
-    let backend_url = "http://127.0.0.1:8000/";
+/// Wait for the backend to become ready by polling the health endpoint, and
+/// return its health check.
+///
+/// `on_attempt` is called before every attempt with the attempt number and the
+/// maximum number of attempts. Fails right away when whatever answers is not
+/// the backend.
+fn wait_for_backend(
+    client: &BackendClient,
+    mut on_attempt: impl FnMut(u32, u32),
+) -> Result<HealthResponse, StartupError> {
+    let backend_url = format!("{}/", client.base_url());
     let max_retries = 60;  // 30 seconds total (PyInstaller backend needs time to start)
     let retry_delay = std::time::Duration::from_millis(500);
 
-    println!("Waiting for backend to be ready at {}", backend_url);
+    let _span = info_span!("backend_readiness", url = %backend_url).entered();
+    let started = Instant::now();
+    info!("Waiting for backend to be ready");
 
     for attempt in 1..=max_retries {
-        match ureq::get(backend_url).call() {
-            Ok(_) => {
-                println!("Backend is ready!");
-                return Ok(());
+        on_attempt(attempt, max_retries);
+        match client.health() {
+            Ok(health) => {
+                info!(
+                    attempt,
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    version = health.version.as_deref().unwrap_or("unknown"),
+                    api_version = health.api_version,
+                    "Backend is ready"
+                );
+                return Ok(health);
+            }
+            Err(ApiError::InvalidResponse(e)) => {
+                error!(error = %e, "Unexpected health check response");
+                return Err(StartupError::NotOurBackend {
+                    url: backend_url,
+                    detail: e,
+                });
+            }
+            // The health check needs the session token too, a backend started
+            // with another one is not ours
+            Err(ApiError::Backend { status: 401, detail }) => {
+                error!(error = %detail, "Health check rejected the session token");
+                return Err(StartupError::NotOurBackend {
+                    url: backend_url,
+                    detail,
+                });
             }
             Err(e) => {
                 if attempt == max_retries {
-                    return Err(format!(
-                        "Backend failed to start after {} attempts: {}",
-                        max_retries, e
-                    ));
+                    error!(
+                        attempt,
+                        elapsed_ms = started.elapsed().as_millis() as u64,
+                        error = %e,
+                        "Backend never became ready"
+                    );
+                    return Err(StartupError::ReadinessTimeout {
+                        attempts: max_retries,
+                        last_error: e.to_string(),
+                    });
                 }
-                println!("Attempt {}/{}: Backend not ready yet...", attempt, max_retries);
+                debug!(attempt, max_attempts = max_retries, error = %e, "Backend not ready yet");
                 std::thread::sleep(retry_delay);
             }
         }
     }
 
-    Err("Backend startup timeout".to_string())
+    Err(StartupError::ReadinessTimeout {
+        attempts: max_retries,
+        last_error: "no attempt made".to_string(),
+    })
+}
+
+/// Record and broadcast a new startup status
+fn set_startup_status<R: Runtime>(app: &AppHandle<R>, status: StartupStatus) {
+    if let Ok(mut current) = app.state::<StartupState>().0.lock() {
+        *current = status.clone();
+    }
+    if tray::is_headless(app) {
+        tray::show_startup_status(app, &status);
+    }
+    let _ = app.emit("backend://startup", status);
+}
+
+/// Log a startup failure and show it on the splash window, or the tray when
+/// headless
+fn fail_startup<R: Runtime>(app: &AppHandle<R>, error: &StartupError) {
+    error!(kind = error.kind(), error = %error, "Backend startup failed");
+    set_startup_status(app, error.into());
+}
+
+/// Spawn the backend and wait until it answers its health check
+fn launch_backend<R: Runtime>(app: &AppHandle<R>) -> Result<u16, StartupError> {
+    // A backend left by a crashed session would hold the panel and maybe the port
+    app.state::<BackendLock>().reclaim_stale()?;
+
+    let port = pick_backend_port()?;
+    ensure_port_available(port)?;
+    let config = app.state::<BackendConfig>();
+    if let Ok(mut current) = config.url.lock() {
+        *current = Some(format!("http://127.0.0.1:{}", port));
+    }
+    if let Ok(mut current) = config.token.lock() {
+        *current = Some(generate_backend_token()?);
+    }
+
+    let child = start_backend(app, port)?;
+    let backend = app.state::<BackendProcess>();
+    if let Ok(mut child_opt) = backend.child.lock() {
+        *child_opt = Some(child);
+    }
+
+    let client = config
+        .client()
+        .ok_or_else(|| StartupError::Internal("Backend configuration is missing".to_string()))?;
+    let result = wait_for_backend(&client, |attempt, max_attempts| {
+        set_startup_status(app, StartupStatus::Starting { attempt, max_attempts });
+    })
+    .and_then(|health| {
+        health.check_compatible()?;
+        set_capabilities(app, health.capabilities);
+        Ok(())
+    });
+
+    if result.is_err() {
+        // Don't leave a half-started backend behind, a retry spawns a new one
+        if let Some(mut child) = backend.child.lock().ok().and_then(|mut c| c.take()) {
+            let _ = process_tree::kill_tree(&mut child);
+            app.state::<BackendLock>().remove();
+        }
+    }
+
+    result.map(|_| port)
 }
 
-/// Cleanup the backend process on app shutdown
-fn cleanup_backend(backend_process: &BackendProcess) {
-    if let Ok(mut child_opt) = backend_process.0.lock() {
-        if let Some(mut child) = child_opt.take() {
-            println!("Stopping backend process...");
-            match child.kill() {
-                Ok(_) => println!("Backend process terminated"),
-                Err(e) => eprintln!("Failed to kill backend process: {}", e),
+fn set_capabilities<R: Runtime>(app: &AppHandle<R>, capabilities: Vec<Capability>) {
+    if let Ok(mut current) = app.state::<BackendCapabilities>().0.lock() {
+        *current = capabilities;
+    }
+}
+
+/// The splash window, created from its configuration the first time. Headless,
+/// it only exists once shown from the tray.
+fn splash_window<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<WebviewWindow<R>> {
+    if let Some(window) = app.get_webview_window(SPLASH_WINDOW) {
+        return Ok(window);
+    }
+    let config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|window| window.label == SPLASH_WINDOW)
+        .ok_or(tauri::Error::WindowNotFound)?;
+    WebviewWindowBuilder::from_config(app, config)?.build()
+}
+
+/// Mark the startup as done and switch from the splash to the main window.
+/// Headless, the main window stays hidden and the last panel is reconnected.
+fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    set_startup_status(app, StartupStatus::Ready);
+
+    if tray::is_headless(app) {
+        tray::reconnect(app);
+    } else if let Some(main_window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = main_window.show();
+        let _ = main_window.set_focus();
+    }
+    if let Some(splash_window) = app.get_webview_window(SPLASH_WINDOW) {
+        let _ = splash_window.close();
+    }
+
+    launch_request::flush(app);
+}
+
+/// Drive the panel from Rust, no backend process is started
+#[cfg(feature = "native-ble")]
+fn start_native_transport<R: Runtime>(app: &AppHandle<R>) {
+    info!("Using the native Bluetooth transport");
+    let native = std::sync::Arc::new(panel::native::NativePanel::default());
+    if let Ok(health) = native.health() {
+        set_capabilities(app, health.capabilities);
+    }
+    app.manage(native);
+    show_main_window(app);
+}
+
+/// Use a backend started by hand, once it answers its health check
+fn attach_to_backend<R: Runtime>(app: AppHandle<R>, url: String) {
+    tauri::async_runtime::spawn_blocking(move || {
+        info!(%url, "Attaching to an external backend");
+        let config = app.state::<BackendConfig>();
+        if let Ok(mut current) = config.url.lock() {
+            *current = Some(url.clone());
+        }
+        // Only needed when the external backend was given a token too
+        if let Ok(mut current) = config.token.lock() {
+            *current = std::env::var(BACKEND_TOKEN_ENV).ok().filter(|token| !token.is_empty());
+        }
+
+        let client = BackendClient::new(url.clone()).with_token(config.token());
+        let result = wait_for_backend(&client, |attempt, max_attempts| {
+            set_startup_status(&app, StartupStatus::Starting { attempt, max_attempts });
+        })
+        .map_err(|e| match e {
+            StartupError::ReadinessTimeout { last_error, .. } => StartupError::ExternalUnreachable {
+                url: url.clone(),
+                last_error,
+            },
+            e => e,
+        })
+        .and_then(|health| {
+            health.check_compatible()?;
+            set_capabilities(&app, health.capabilities);
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => {
+                show_main_window(&app);
+                status_relay::start(&app);
             }
+            Err(e) => fail_startup(&app, &e),
         }
+    });
+}
+
+#[cfg(not(feature = "native-ble"))]
+fn start_native_transport<R: Runtime>(app: &AppHandle<R>) {
+    fail_startup(
+        app,
+        &StartupError::FeatureMissing {
+            setting: format!("{}=native", BACKEND_MODE_ENV),
+            feature: "native-ble",
+        },
+    );
+}
+
+/// Set up access to the panel, through a backend process or the native transport
+fn start_panel_access<R: Runtime>(app: AppHandle<R>) {
+    match BackendMode::from_env() {
+        Ok(BackendMode::Native) => start_native_transport(&app),
+        Ok(BackendMode::External(url)) => attach_to_backend(app, url),
+        Ok(BackendMode::Bundled | BackendMode::Source | BackendMode::Mock) => {
+            start_backend_in_background(app)
+        }
+        Err(e) => fail_startup(&app, &e),
     }
 }
 
+/// Start the backend in the background and switch from the splash to the main
+/// window once it is ready. On failure the splash window shows the error.
+fn start_backend_in_background<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn_blocking(move || match launch_backend(&app) {
+        Ok(port) => {
+            show_main_window(&app);
+            status_relay::start(&app);
+
+            // Restart the backend if it dies while the app is running
+            spawn_backend_supervisor(app, port);
+        }
+        Err(e) => fail_startup(&app, &e),
+    });
+}
+
+/// Delay before the given restart attempt (exponential backoff, capped)
+fn restart_delay(restarts: u32) -> Duration {
+    RESTART_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(restarts))
+        .min(RESTART_MAX_DELAY)
+}
+
+/// Reconnect the last panel once the restarted backend answers, nobody is
+/// there to do it from the window in headless mode
+fn reconnect_when_ready<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let Some(client) = app.state::<BackendConfig>().client() else {
+            return;
+        };
+        match wait_for_backend(&client, |_, _| {}) {
+            Ok(_) => tray::reconnect(&app),
+            Err(e) => warn!(error = %e, "Restarted backend never became ready"),
+        }
+    });
+}
+
+/// Watch the backend process and restart it when it exits unexpectedly.
+///
+/// Emits `backend://crashed` for every unexpected exit and `backend://restarted`
+/// once a replacement process is running. Gives up after `MAX_BACKEND_RESTARTS`
+/// consecutive crashes.
+fn spawn_backend_supervisor<R: Runtime>(app: AppHandle<R>, port: u16) {
+    std::thread::spawn(move || {
+        let backend = app.state::<BackendProcess>();
+        let mut restarts = 0;
+        let mut started_at = Instant::now();
+
+        loop {
+            std::thread::sleep(SUPERVISOR_POLL_INTERVAL);
+
+            if backend.is_shutting_down() {
+                return;
+            }
+
+            let exit_status = match backend.child.lock() {
+                Ok(mut child_opt) => match child_opt.as_mut().map(|child| child.try_wait()) {
+                    Some(Ok(Some(status))) => {
+                        // The interpreter may outlive a crashed bootloader and
+                        // keep the port, which would make the restart fail
+                        if let Some(child) = child_opt.take() {
+                            if let Err(e) = process_tree::kill_leftovers(&child) {
+                                warn!(error = %e, "Failed to kill leftover backend processes");
+                            }
+                        }
+                        status
+                    }
+                    Some(Ok(None)) => continue,
+                    Some(Err(e)) => {
+                        warn!(error = %e, "Failed to check backend process status");
+                        continue;
+                    }
+                    // Child already taken by cleanup
+                    None => return,
+                },
+                Err(_) => return,
+            };
+
+            if backend.is_shutting_down() {
+                return;
+            }
+
+            if started_at.elapsed() >= BACKEND_STABLE_UPTIME {
+                restarts = 0;
+            }
+
+            let will_restart = restarts < MAX_BACKEND_RESTARTS;
+            warn!(
+                exit_code = exit_status.code(),
+                restarts,
+                uptime_s = started_at.elapsed().as_secs(),
+                "Backend process exited unexpectedly"
+            );
+            let _ = app.emit(
+                "backend://crashed",
+                BackendCrashed {
+                    exit_code: exit_status.code(),
+                    restarts,
+                    will_restart,
+                },
+            );
+
+            if !will_restart {
+                error!(restarts, "Backend crashed too many times in a row, giving up");
+                return;
+            }
+
+            // Keep trying to bring a new process up, backing off between attempts
+            loop {
+                std::thread::sleep(restart_delay(restarts));
+                restarts += 1;
+
+                if backend.is_shutting_down() {
+                    return;
+                }
+
+                match start_backend(&app, port) {
+                    Ok(child) => {
+                        let pid = child.id();
+                        if let Ok(mut child_opt) = backend.child.lock() {
+                            *child_opt = Some(child);
+                        }
+                        started_at = Instant::now();
+                        info!(pid, restarts, "Backend restarted");
+                        let _ = app.emit("backend://restarted", BackendRestarted { pid, restarts });
+                        if tray::is_headless(&app) {
+                            reconnect_when_ready(&app);
+                        }
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(restarts, error = %e, "Failed to restart backend");
+                        if restarts >= MAX_BACKEND_RESTARTS {
+                            error!(restarts, "Backend could not be restarted, giving up");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Cleanup the backend process on app shutdown.
+///
+/// Asks the backend to close the BLE connection and exit, so the panel is
+/// released cleanly, and only kills the process tree if it doesn't exit in time.
+fn cleanup_backend<R: Runtime>(app: &AppHandle<R>) {
+    let backend_process = app.state::<BackendProcess>();
+    backend_process.shutting_down.store(true, Ordering::SeqCst);
+
+    let Some(mut child) = backend_process.child.lock().ok().and_then(|mut c| c.take()) else {
+        return;
+    };
+
+    let config = app.state::<BackendConfig>();
+    stop_backend_process(
+        &mut child,
+        config.url().as_deref(),
+        &config.token().unwrap_or_default(),
+    );
+    app.state::<BackendLock>().remove();
+}
+
+/// Ask the backend to release the panel and exit, and kill its process tree
+/// when it doesn't in time
+fn stop_backend_process(child: &mut Child, base_url: Option<&str>, token: &str) {
+    let _span = info_span!("backend_shutdown", pid = child.id()).entered();
+    let started = Instant::now();
+    info!("Stopping backend process");
+
+    if let Some(base_url) = base_url {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(SHUTDOWN_REQUEST_TIMEOUT)
+            .build();
+        let authorization = panel::bearer(token);
+
+        if let Err(e) = agent
+            .post(&format!("{}/devices/disconnect", base_url))
+            .set("Authorization", &authorization)
+            .call()
+        {
+            warn!(error = %e, "Failed to disconnect the panel before shutdown");
+        }
+        if let Err(e) = agent
+            .post(&format!("{}/shutdown", base_url))
+            .set("Authorization", &authorization)
+            .call()
+        {
+            warn!(error = %e, "Failed to request backend shutdown");
+        }
+    }
+
+    if process_tree::wait_for_exit(child, SHUTDOWN_GRACE_PERIOD) {
+        info!(elapsed_ms = started.elapsed().as_millis() as u64, "Backend process exited gracefully");
+        if let Err(e) = process_tree::kill_leftovers(child) {
+            warn!(error = %e, "Failed to kill leftover backend processes");
+        }
+        return;
+    }
+
+    warn!(grace_period_s = SHUTDOWN_GRACE_PERIOD.as_secs(), "Backend did not exit in time, killing it");
+    match process_tree::kill_tree(child) {
+        Ok(_) => info!(elapsed_ms = started.elapsed().as_millis() as u64, "Backend process terminated"),
+        Err(e) => error!(error = %e, "Failed to kill backend process"),
+    }
+}
+
+/// Features of the running backend, for the frontend to hide what it lacks
+#[tauri::command]
+fn get_backend_capabilities(state: State<'_, BackendCapabilities>) -> Vec<Capability> {
+    state.0.lock().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// Base URL of the backend
+#[tauri::command]
+fn get_backend_url(config: State<'_, BackendConfig>) -> Result<String, String> {
+    config.url().ok_or_else(|| "Backend is not started yet".to_string())
+}
+
+/// Current startup status, polled by the splash window when it loads
+#[tauri::command]
+fn get_startup_status(state: State<'_, StartupState>) -> StartupStatus {
+    state.0.lock().map(|s| s.clone()).unwrap_or(StartupStatus::Ready)
+}
+
+/// Recent backend output, used to fill the log viewer when it opens
+#[tauri::command]
+fn get_backend_logs(log: State<'_, BackendLog>) -> Vec<BackendLogLine> {
+    log.recent_lines()
+}
+
+/// Retry the backend startup after a failure
+#[tauri::command]
+fn retry_backend_startup<R: Runtime>(app: AppHandle<R>, state: State<'_, StartupState>) {
+    let failed = matches!(state.0.lock().as_deref(), Ok(StartupStatus::Failed { .. }));
+    if failed {
+        start_panel_access(app);
+    }
+}
+
+/// Quit the application from the splash error screen
+#[tauri::command]
+fn quit_app<R: Runtime>(app: AppHandle<R>) {
+    app.exit(1);
+}
+
+/// App configuration and assets, also used by the command line interface
+fn context() -> tauri::Context<tauri::Wry> {
+    tauri::generate_context!()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    #[cfg(target_os = "linux")]
+    if let Some(code) = service::handle_args(&args) {
+        std::process::exit(code);
+    }
+    let headless = cfg!(desktop) && args.iter().any(|arg| arg == HEADLESS_ARG);
+
+    let builder = tauri::Builder::default();
+
+    // A second instance would start a second backend fighting for the panel,
+    // it hands its arguments over to the running one instead. Must be the
+    // first plugin.
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+        info!(?args, "Another instance was launched");
+        tray::show_window(app);
+        let requests = LaunchRequest::parse(args.get(1..).unwrap_or_default(), Path::new(&cwd));
+        launch_request::submit(app, requests);
+    }));
+
+    let app = builder
         .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
-            // Start the backend process
-            let child = start_backend(&app.handle())?;
+        .setup(move |app| {
+            let log_dir = app
+                .path()
+                .app_log_dir()
+                .unwrap_or_else(|_| std::env::temp_dir().join("pixelart-controller"));
+            app.manage(logging::init(&log_dir));
+            app.manage(BackendLog::open(&log_dir));
+            let data_dir = app
+                .path()
+                .app_local_data_dir()
+                .unwrap_or_else(|_| std::env::temp_dir().join("pixelart-controller"));
+            app.manage(BackendLock::new(&data_dir));
+            app.manage(BackendProcess::default());
+            app.manage(BackendConfig::default());
+            app.manage(BackendCapabilities::default());
+            app.manage(LastDevice::new(&data_dir));
+            app.manage(CalibrationStore::new(&data_dir));
+            app.manage(FontLibrary::new(&data_dir));
+            app.manage(RecentContent::default());
+            app.manage(ConnectionState::default());
+            let cwd = std::env::current_dir().unwrap_or_default();
+            app.manage(PendingLaunchRequests::new(LaunchRequest::parse(&args, &cwd)));
 
-            // Store the process in app state
-            app.manage(BackendProcess(Mutex::new(Some(child))));
+            if headless {
+                info!("Running headless from the system tray");
+                app.manage(tray::Headless);
+                tray::create(app.handle())?;
+            } else {
+                splash_window(app.handle())?;
+            }
+            app.manage(StartupState(Mutex::new(StartupStatus::Starting {
+                attempt: 0,
+                max_attempts: 0,
+            })));
 
-            // Wait for backend to be ready
-            wait_for_backend()?;
+            // Start the backend without blocking window creation, the splash
+            // window reports progress until the main window is shown
+            start_panel_access(app.handle().clone());
 
             Ok(())
         })
+        .invoke_handler(tauri::generate_handler![
+            get_backend_url,
+            status_relay::get_connection_state,
+            get_startup_status,
+            get_backend_capabilities,
+            get_backend_logs,
+            retry_backend_startup,
+            quit_app,
+            panel::commands::ping_backend,
+            panel::commands::scan_devices,
+            panel::commands::connect_device,
+            panel::commands::disconnect_device,
+            panel::commands::get_device_status,
+            panel::commands::get_device_info,
+            panel::commands::send_text,
+            panel::commands::send_image,
+            panel::commands::convert_image,
+            panel::commands::estimate_upload,
+            panel::commands::set_panel_mode,
+            panel::commands::set_brightness,
+            panel::commands::set_orientation,
+            panel::commands::send_pixels,
+            panel::commands::set_clock_mode,
+            panel::commands::set_rhythm_mode,
+            panel::commands::set_rhythm_mode_2,
+            panel::commands::set_power,
+            calibration::get_color_profile,
+            calibration::save_color_profile,
+            calibration::reset_color_profile,
+            calibration::send_test_pattern,
+            font_library::list_fonts,
+            font_library::import_font,
+            font_library::remove_font,
+            font_library::render_text,
+            font_library::send_rendered_text
+        ])
         .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                // Headless, closing a window only hides it: the app keeps
+                // running in the tray until quit from there
+                let ready = matches!(
+                    window.state::<StartupState>().0.lock().as_deref(),
+                    Ok(StartupStatus::Ready)
+                );
+                let keep = match window.label() {
+                    MAIN_WINDOW => true,
+                    SPLASH_WINDOW => !ready,
+                    _ => false,
+                };
+                if keep && tray::is_headless(window.app_handle()) {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
             if let tauri::WindowEvent::Destroyed = event {
-                // Cleanup backend when window is closed
-                let backend_process = window.state::<BackendProcess>();
-                cleanup_backend(&backend_process);
+                match window.label() {
+                    MAIN_WINDOW => {
+                        // Cleanup backend when the main window is closed
+                        cleanup_backend(window.app_handle());
+                    }
+                    SPLASH_WINDOW => {
+                        // Closing the splash before the backend is ready quits the app,
+                        // otherwise only the hidden main window would be left
+                        let ready = matches!(
+                            window.state::<StartupState>().0.lock().as_deref(),
+                            Ok(StartupStatus::Ready)
+                        );
+                        if !ready {
+                            cleanup_backend(window.app_handle());
+                            window.app_handle().exit(0);
+                        }
+                    }
+                    _ => {}
+                }
             }
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(context());
+
+    // No window can show the error at this point, don't panic with a backtrace
+    let app = match app {
+        Ok(app) => app,
+        Err(e) => {
+            error!(error = %e, "Failed to build the application");
+            eprintln!("PixelArt Controller failed to start: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    app.run(|app, event| {
+        if let tauri::RunEvent::ExitRequested { .. } = event {
+            // Also covers quitting from the menu or the OS, not only closing the window
+            cleanup_backend(app);
+        }
+    });
 }
@@ -0,0 +1,58 @@
+//! Structured logging for the Tauri shell.
+//!
+//! Logs go to the console and to a daily rolling file in the app log
+//! directory. The level is read from `PIXELART_LOG` using the `tracing`
+//! filter syntax, e.g. `PIXELART_LOG=debug` or
+//! `PIXELART_LOG=pixelart_controller_lib=trace,backend=warn`.
+
+use std::path::Path;
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable holding the log filter
+const LOG_FILTER_ENV: &str = "PIXELART_LOG";
+/// Filter used when `PIXELART_LOG` is not set
+const DEFAULT_LOG_FILTER: &str = "info";
+/// Prefix of the app log files in the log directory
+const LOG_FILE_PREFIX: &str = "pixelart-controller.log";
+
+/// Keeps the background log writer alive, must be held until the app exits
+pub struct LogGuard(#[allow(dead_code)] Option<WorkerGuard>);
+
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_env(LOG_FILTER_ENV).unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER))
+}
+
+/// Install the global subscriber, logging to the console and to `log_dir`
+pub fn init(log_dir: &Path) -> LogGuard {
+    let console_layer = fmt::layer().with_target(true);
+
+    let (file_layer, guard) = match std::fs::create_dir_all(log_dir) {
+        Ok(()) => {
+            let appender = tracing_appender::rolling::daily(log_dir, LOG_FILE_PREFIX);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer().with_ansi(false).with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        Err(_) => (None, None),
+    };
+
+    let registry = tracing_subscriber::registry()
+        .with(env_filter())
+        .with(console_layer)
+        .with(file_layer);
+
+    if registry.try_init().is_err() {
+        // Already initialised, e.g. by a test harness
+        return LogGuard(None);
+    }
+
+    if guard.is_none() {
+        tracing::warn!(log_dir = %log_dir.display(), "Could not create log directory, logging to console only");
+    }
+
+    LogGuard(guard)
+}
@@ -2,5 +2,20 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    #[cfg(feature = "mock-backend")]
+    {
+        use pixelart_controller_lib::mock_backend;
+
+        let mut args = std::env::args().skip(1);
+        if args.next().as_deref() == Some(mock_backend::MOCK_BACKEND_ARG) {
+            return mock_backend::main(args);
+        }
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = pixelart_controller_lib::cli::run(&args) {
+        std::process::exit(code);
+    }
+
     pixelart_controller_lib::run()
 }
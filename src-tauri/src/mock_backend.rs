@@ -0,0 +1,857 @@
+//! Mock of the Python backend, for working on the app without a panel.
+//!
+//! Serves the same HTTP and WebSocket contract as `python-backend/src/main.py`
+//! against a virtual panel kept in memory. Pixel writes land in a framebuffer
+//! that can be read back from `GET /mock/state`, and failures (scan or connect
+//! errors, a flaky link, dropped connections, latency) can be turned on with
+//! command line flags or at runtime with `PUT /mock/failures`.
+//!
+//! The app binary runs it with `pixelart-controller mock-backend --port <port>`
+//! when started with `PIXELART_BACKEND=mock`.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Multipart, Request, State};
+use axum::http::{header, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, Notify};
+use tracing::{info, warn};
+
+use crate::panel::types::*;
+use crate::panel::API_VERSION;
+pub use crate::MOCK_BACKEND_ARG;
+
+/// Panel returned by scans
+const MOCK_DEVICE_NAME: &str = "LED_BLE_MOCK";
+const MOCK_DEVICE_ADDRESS: &str = "AA:BB:CC:DD:EE:01";
+
+/// Failures the mock can simulate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Failures {
+    /// Scans fail
+    pub scan: bool,
+    /// Connection attempts fail
+    pub connect: bool,
+    /// Panel commands fail while the panel stays connected
+    pub commands: bool,
+    /// The panel drops the connection after this many commands
+    pub drop_after: Option<u32>,
+    /// Delay added to every device and panel request, in milliseconds
+    pub latency_ms: u64,
+}
+
+/// Mock settings, from the command line
+#[derive(Debug, Clone)]
+pub struct MockOptions {
+    pub port: u16,
+    pub width: u32,
+    pub height: u32,
+    pub failures: Failures,
+    /// Session token required on every request, as the real backend does when
+    /// started by the app
+    pub token: Option<String>,
+}
+
+impl Default for MockOptions {
+    fn default() -> Self {
+        Self {
+            port: 8000,
+            width: 32,
+            height: 32,
+            failures: Failures::default(),
+            token: None,
+        }
+    }
+}
+
+impl MockOptions {
+    /// Parse the arguments following `mock-backend`, with the environment
+    /// variables given by `env`
+    pub fn from_args(
+        mut args: impl Iterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, String> {
+        let mut options = Self::default();
+        if let Some(port) = env("PIXELART_BACKEND_PORT").and_then(|p| p.parse().ok()) {
+            options.port = port;
+        }
+        options.token = env("PIXELART_BACKEND_TOKEN").filter(|token| !token.is_empty());
+
+        fn value<T: std::str::FromStr>(
+            flag: &str,
+            args: &mut impl Iterator<Item = String>,
+        ) -> Result<T, String> {
+            args.next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| format!("{} expects a value", flag))
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--port" => options.port = value(&arg, &mut args)?,
+                "--size" => {
+                    let size: String = value(&arg, &mut args)?;
+                    let (width, height) = size
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .ok_or_else(|| format!("Invalid panel size: {}", size))?;
+                    options.width = width;
+                    options.height = height;
+                }
+                "--fail-scan" => options.failures.scan = true,
+                "--fail-connect" => options.failures.connect = true,
+                "--fail-commands" => options.failures.commands = true,
+                "--drop-after" => options.failures.drop_after = Some(value(&arg, &mut args)?),
+                "--latency-ms" => options.failures.latency_ms = value(&arg, &mut args)?,
+                _ => return Err(format!("Unknown mock-backend argument: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Upload received by `/panel/image`, the mock doesn't decode it
+#[derive(Debug, Clone, Serialize)]
+struct Upload {
+    filename: String,
+    size: usize,
+}
+
+/// State of the virtual panel
+#[derive(Debug, Clone, Serialize)]
+struct VirtualPanel {
+    connected: bool,
+    device_address: Option<String>,
+    width: u32,
+    height: u32,
+    power: bool,
+    brightness: u8,
+    orientation: u8,
+    mode: Option<String>,
+    text: Option<String>,
+    image: Option<Upload>,
+    /// Row-major "RRGGBB" colors
+    framebuffer: Vec<String>,
+    /// Panel commands received since the last connection
+    commands: u32,
+    failures: Failures,
+}
+
+impl VirtualPanel {
+    fn new(options: &MockOptions) -> Self {
+        Self {
+            connected: false,
+            device_address: None,
+            width: options.width,
+            height: options.height,
+            power: true,
+            brightness: 100,
+            orientation: 0,
+            mode: None,
+            text: None,
+            image: None,
+            framebuffer: vec!["000000".to_string(); (options.width * options.height) as usize],
+            commands: 0,
+            failures: options.failures.clone(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.framebuffer.fill("000000".to_string());
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "type": "status",
+            "connected": self.connected,
+            "device_address": self.device_address,
+        })
+    }
+}
+
+/// Error answered like FastAPI's `HTTPException`
+struct HttpError(StatusCode, String);
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "detail": self.1 }))).into_response()
+    }
+}
+
+impl From<crate::panel::ApiError> for HttpError {
+    fn from(e: crate::panel::ApiError) -> Self {
+        HttpError(StatusCode::BAD_REQUEST, e.to_string())
+    }
+}
+
+type HttpResult = Result<Json<Value>, HttpError>;
+
+struct MockBackend {
+    panel: Mutex<VirtualPanel>,
+    /// Status messages for the WebSocket clients
+    events: broadcast::Sender<String>,
+    shutdown: Notify,
+    token: Option<String>,
+}
+
+type Shared = Arc<MockBackend>;
+
+impl MockBackend {
+    fn broadcast_status(&self, panel: &VirtualPanel) {
+        let _ = self.events.send(panel.status().to_string());
+    }
+
+    async fn latency(&self) {
+        let latency_ms = self.panel.lock().unwrap().failures.latency_ms;
+        if latency_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(latency_ms)).await;
+        }
+    }
+
+    /// Run a command on the connected panel, applying the failure modes
+    async fn command(
+        &self,
+        name: &str,
+        apply: impl FnOnce(&mut VirtualPanel) -> Result<Value, HttpError>,
+    ) -> HttpResult {
+        self.latency().await;
+        let mut panel = self.panel.lock().unwrap();
+        if !panel.connected {
+            return Err(HttpError(
+                StatusCode::BAD_REQUEST,
+                "No device connected".to_string(),
+            ));
+        }
+
+        panel.commands += 1;
+        if panel
+            .failures
+            .drop_after
+            .is_some_and(|limit| panel.commands > limit)
+        {
+            warn!("Mock panel dropped the connection");
+            panel.connected = false;
+            panel.device_address = None;
+            self.broadcast_status(&panel);
+            return Err(HttpError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to {}: device disconnected", name),
+            ));
+        }
+        if panel.failures.commands {
+            return Err(HttpError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to {}: simulated failure", name),
+            ));
+        }
+
+        let mut response = apply(&mut panel)?;
+        response["status"] = json!("success");
+        Ok(Json(response))
+    }
+}
+
+async fn root() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        message: Some("Mock LED Panel Control Backend is running".to_string()),
+        version: Some("1.0.0".to_string()),
+        api_version: Some(API_VERSION),
+        capabilities: vec![
+            Capability::Text,
+            Capability::Image,
+            Capability::Gif,
+            Capability::Brightness,
+            Capability::Orientation,
+            Capability::DeviceInfo,
+            Capability::ClockMode,
+            Capability::RhythmMode,
+            Capability::RhythmMode2,
+            Capability::DiyMode,
+            Capability::PixelBatch,
+            Capability::Power,
+            Capability::Shutdown,
+            Capability::StatusWebsocket,
+        ],
+    })
+}
+
+async fn scan_devices(State(backend): State<Shared>) -> Result<Json<Vec<Device>>, HttpError> {
+    backend.latency().await;
+    if backend.panel.lock().unwrap().failures.scan {
+        return Err(HttpError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Scan failed: simulated failure".to_string(),
+        ));
+    }
+    Ok(Json(vec![Device {
+        name: MOCK_DEVICE_NAME.to_string(),
+        address: MOCK_DEVICE_ADDRESS.to_string(),
+        rssi: Some(-42),
+    }]))
+}
+
+async fn connect_device(
+    State(backend): State<Shared>,
+    Json(request): Json<ConnectRequest>,
+) -> HttpResult {
+    backend.latency().await;
+    let mut panel = backend.panel.lock().unwrap();
+    if panel.failures.connect {
+        return Err(HttpError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Connection failed: simulated failure".to_string(),
+        ));
+    }
+    if !request.address.eq_ignore_ascii_case(MOCK_DEVICE_ADDRESS) {
+        return Err(HttpError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Connection failed: device {} not found", request.address),
+        ));
+    }
+
+    panel.connected = true;
+    panel.device_address = Some(MOCK_DEVICE_ADDRESS.to_string());
+    panel.commands = 0;
+    backend.broadcast_status(&panel);
+    info!(address = %request.address, "Mock panel connected");
+
+    Ok(Json(
+        json!({ "status": "connected", "address": request.address }),
+    ))
+}
+
+async fn disconnect_device(State(backend): State<Shared>) -> Json<Value> {
+    let mut panel = backend.panel.lock().unwrap();
+    if !panel.connected {
+        return Json(json!({ "status": "disconnected", "message": "Already disconnected" }));
+    }
+    panel.connected = false;
+    panel.device_address = None;
+    backend.broadcast_status(&panel);
+    Json(json!({ "status": "disconnected" }))
+}
+
+async fn get_status(State(backend): State<Shared>) -> Json<DeviceStatus> {
+    let panel = backend.panel.lock().unwrap();
+    Json(DeviceStatus {
+        connected: panel.connected,
+        device_address: panel.device_address.clone(),
+    })
+}
+
+async fn send_text(State(backend): State<Shared>, Json(request): Json<TextRequest>) -> HttpResult {
+    request.validate()?;
+    backend
+        .command("send text", |panel| {
+            panel.mode = Some("text".to_string());
+            panel.text = Some(request.text.clone());
+            Ok(json!({ "text": request.text }))
+        })
+        .await
+}
+
+async fn send_image(State(backend): State<Shared>, mut multipart: Multipart) -> HttpResult {
+    let mut upload = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| HttpError(StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        if field.name() == Some("file") {
+            let filename = field.file_name().unwrap_or("image").to_string();
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| HttpError(StatusCode::BAD_REQUEST, e.to_string()))?;
+            upload = Some(Upload {
+                filename,
+                size: data.len(),
+            });
+        }
+    }
+    let upload = upload.ok_or_else(|| {
+        HttpError(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Missing file field".to_string(),
+        )
+    })?;
+
+    backend
+        .command("send image", |panel| {
+            panel.mode = Some("image".to_string());
+            panel.image = Some(upload.clone());
+            Ok(json!({ "filename": upload.filename, "size": upload.size }))
+        })
+        .await
+}
+
+async fn set_brightness(
+    State(backend): State<Shared>,
+    Json(request): Json<BrightnessRequest>,
+) -> HttpResult {
+    request.validate()?;
+    backend
+        .command("set brightness", |panel| {
+            panel.brightness = request.brightness;
+            Ok(json!({ "brightness": request.brightness }))
+        })
+        .await
+}
+
+async fn set_orientation(
+    State(backend): State<Shared>,
+    Json(request): Json<OrientationRequest>,
+) -> HttpResult {
+    request.validate()?;
+    backend
+        .command("set orientation", |panel| {
+            panel.orientation = request.orientation;
+            Ok(json!({ "orientation": request.orientation }))
+        })
+        .await
+}
+
+async fn get_device_info(State(backend): State<Shared>) -> Result<Json<DeviceInfo>, HttpError> {
+    let panel = backend.panel.lock().unwrap();
+    if !panel.connected {
+        return Err(HttpError(
+            StatusCode::BAD_REQUEST,
+            "No device connected".to_string(),
+        ));
+    }
+    Ok(Json(DeviceInfo {
+        width: panel.width,
+        height: panel.height,
+        device_type: 0x82,
+        led_type: 0,
+        has_wifi: false,
+    }))
+}
+
+async fn set_clock_mode(
+    State(backend): State<Shared>,
+    Json(settings): Json<ClockSettings>,
+) -> HttpResult {
+    settings.validate()?;
+    backend
+        .command("set clock mode", |panel| {
+            panel.mode = Some("clock".to_string());
+            Ok(json!({ "mode": "clock", "settings": settings }))
+        })
+        .await
+}
+
+async fn set_rhythm_mode(
+    State(backend): State<Shared>,
+    Json(settings): Json<RhythmSettings>,
+) -> HttpResult {
+    settings.validate()?;
+    backend
+        .command("set rhythm mode", |panel| {
+            panel.mode = Some("rhythm".to_string());
+            Ok(json!({ "mode": "rhythm", "settings": settings }))
+        })
+        .await
+}
+
+async fn set_rhythm_mode_2(
+    State(backend): State<Shared>,
+    Json(settings): Json<RhythmSettings2>,
+) -> HttpResult {
+    settings.validate()?;
+    backend
+        .command("set rhythm mode v2", |panel| {
+            panel.mode = Some("rhythm2".to_string());
+            Ok(json!({ "mode": "rhythm2", "settings": settings }))
+        })
+        .await
+}
+
+async fn set_diy_mode(State(backend): State<Shared>) -> HttpResult {
+    backend
+        .command("set DIY mode", |panel| {
+            panel.mode = Some("diy".to_string());
+            panel.clear();
+            Ok(json!({ "mode": "diy" }))
+        })
+        .await
+}
+
+async fn set_power(State(backend): State<Shared>, Json(request): Json<PowerRequest>) -> HttpResult {
+    backend
+        .command("set power", |panel| {
+            panel.power = request.on;
+            Ok(json!({ "power": if request.on { "on" } else { "off" } }))
+        })
+        .await
+}
+
+async fn send_pixels(
+    State(backend): State<Shared>,
+    Json(request): Json<PixelsRequest>,
+) -> HttpResult {
+    request.validate()?;
+    backend
+        .command("send pixels", |panel| {
+            if panel.mode.as_deref() != Some("diy") {
+                panel.mode = Some("diy".to_string());
+                panel.clear();
+            }
+            for pixel in &request.pixels {
+                let (x, y) = (u32::from(pixel.x), u32::from(pixel.y));
+                if x >= panel.width || y >= panel.height {
+                    return Err(HttpError(
+                        StatusCode::BAD_REQUEST,
+                        format!("Pixel ({}, {}) is outside the panel", x, y),
+                    ));
+                }
+                let index = (y * panel.width + x) as usize;
+                let color = pixel.color.trim_start_matches('#').to_uppercase();
+                panel.framebuffer[index] = color;
+            }
+            Ok(json!({ "pixels_sent": request.pixels.len() }))
+        })
+        .await
+}
+
+async fn shutdown(State(backend): State<Shared>) -> Json<Value> {
+    info!("Shutdown requested");
+    backend.shutdown.notify_one();
+    Json(json!({ "status": "shutting_down" }))
+}
+
+async fn mock_state(State(backend): State<Shared>) -> Json<VirtualPanel> {
+    Json(backend.panel.lock().unwrap().clone())
+}
+
+async fn set_failures(
+    State(backend): State<Shared>,
+    Json(failures): Json<Failures>,
+) -> Json<Failures> {
+    info!(?failures, "Mock failure modes updated");
+    backend.panel.lock().unwrap().failures = failures.clone();
+    Json(failures)
+}
+
+async fn websocket(State(backend): State<Shared>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| forward_status(backend, socket))
+}
+
+/// Send the current status, then every change, until the client goes away
+async fn forward_status(backend: Shared, mut socket: WebSocket) {
+    let mut events = backend.events.subscribe();
+    let initial = backend.panel.lock().unwrap().status().to_string();
+    if socket.send(Message::Text(initial.into())).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if socket.send(Message::Text(event.into())).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Reject requests without the session token, like the real backend
+async fn require_token(State(backend): State<Shared>, request: Request, next: Next) -> Response {
+    let Some(expected) = &backend.token else {
+        return next.run(request).await;
+    };
+    if request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+
+    // Browsers can't set headers on a WebSocket, the token comes in the query
+    let token = if request.uri().path() == "/ws" {
+        request.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+        })
+    } else {
+        request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+    };
+
+    if token.is_some_and(|token| tokens_match(token, expected)) {
+        next.run(request).await
+    } else {
+        HttpError(
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid backend token".to_string(),
+        )
+        .into_response()
+    }
+}
+
+/// Compare tokens without leaking the length of the matching prefix
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+fn router(backend: Shared) -> Router {
+    Router::new()
+        .route("/", get(root))
+        .route("/shutdown", post(shutdown))
+        .route("/devices/scan", get(scan_devices))
+        .route("/devices/scan/all", get(scan_devices))
+        .route("/devices/connect", post(connect_device))
+        .route("/devices/disconnect", post(disconnect_device))
+        .route("/devices/status", get(get_status))
+        .route("/panel/text", post(send_text))
+        .route("/panel/image", post(send_image))
+        .route("/panel/brightness", post(set_brightness))
+        .route("/panel/orientation", post(set_orientation))
+        .route("/panel/device-info", get(get_device_info))
+        .route("/panel/mode/clock", post(set_clock_mode))
+        .route("/panel/mode/rhythm", post(set_rhythm_mode))
+        .route("/panel/mode/rhythm2", post(set_rhythm_mode_2))
+        .route("/panel/mode/diy", post(set_diy_mode))
+        .route("/panel/power", post(set_power))
+        .route("/panel/pixels", post(send_pixels))
+        .route("/mock/state", get(mock_state))
+        .route("/mock/failures", put(set_failures))
+        .route("/ws", get(websocket))
+        .route_layer(middleware::from_fn_with_state(
+            backend.clone(),
+            require_token,
+        ))
+        .with_state(backend)
+}
+
+/// Serve the mock on `listener` until `/shutdown` is called
+pub async fn serve(listener: TcpListener, options: MockOptions) -> std::io::Result<()> {
+    let backend = Arc::new(MockBackend {
+        panel: Mutex::new(VirtualPanel::new(&options)),
+        events: broadcast::channel(16).0,
+        shutdown: Notify::new(),
+        token: options.token.clone(),
+    });
+    let app = router(backend.clone());
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { backend.shutdown.notified().await })
+        .await
+}
+
+/// Entry point of `pixelart-controller mock-backend`
+pub fn main(args: impl Iterator<Item = String>) {
+    let options = match MockOptions::from_args(args, |name| std::env::var(name).ok()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_env("PIXELART_LOG")
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start the async runtime");
+    let result = runtime.block_on(async {
+        let address = SocketAddr::from(([127, 0, 0, 1], options.port));
+        let listener = TcpListener::bind(address).await?;
+        info!(%address, "Mock backend listening");
+        serve(listener, options).await
+    });
+
+    if let Err(e) = result {
+        eprintln!("Mock backend failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::panel::{BackendClient, PanelApi};
+
+    /// Start the mock on a free port and return its base URL
+    async fn start(options: MockOptions) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, options));
+        url
+    }
+
+    fn post(url: &str, body: Value) -> Result<Value, u16> {
+        match ureq::post(url).send_json(body) {
+            Ok(response) => Ok(response.into_json().unwrap()),
+            Err(ureq::Error::Status(status, _)) => Err(status),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn connect(base: &str) {
+        post(
+            &format!("{}/devices/connect", base),
+            json!({ "address": MOCK_DEVICE_ADDRESS }),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pixels_land_in_the_framebuffer() {
+        let base = start(MockOptions::default()).await;
+
+        let state = tokio::task::spawn_blocking(move || {
+            assert_eq!(
+                post(
+                    &format!("{}/panel/brightness", base),
+                    json!({ "brightness": 10 })
+                ),
+                Err(400)
+            );
+            connect(&base);
+            post(
+                &format!("{}/panel/pixels", base),
+                json!({ "pixels": [{ "x": 1, "y": 2, "color": "ff0000" }] }),
+            )
+            .unwrap();
+            ureq::get(&format!("{}/mock/state", base))
+                .call()
+                .unwrap()
+                .into_json::<Value>()
+                .unwrap()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(state["connected"], json!(true));
+        assert_eq!(state["mode"], json!("diy"));
+        assert_eq!(state["framebuffer"][2 * 32 + 1], json!("FF0000"));
+        assert_eq!(state["framebuffer"][0], json!("000000"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn connection_drops_after_the_configured_commands() {
+        let options = MockOptions {
+            failures: Failures {
+                drop_after: Some(1),
+                ..Failures::default()
+            },
+            ..MockOptions::default()
+        };
+        let base = start(options).await;
+
+        tokio::task::spawn_blocking(move || {
+            connect(&base);
+            let brightness = format!("{}/panel/brightness", base);
+            assert!(post(&brightness, json!({ "brightness": 10 })).is_ok());
+            assert_eq!(post(&brightness, json!({ "brightness": 20 })), Err(500));
+            assert_eq!(post(&brightness, json!({ "brightness": 30 })), Err(400));
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn requests_without_the_session_token_are_rejected() {
+        let options = MockOptions {
+            token: Some("secret".to_string()),
+            ..MockOptions::default()
+        };
+        let base = start(options).await;
+
+        tokio::task::spawn_blocking(move || {
+            let status = |request: ureq::Request| match request.call() {
+                Ok(response) => response.status(),
+                Err(ureq::Error::Status(status, _)) => status,
+                Err(e) => panic!("{}", e),
+            };
+            let url = format!("{}/devices/status", base);
+
+            assert_eq!(status(ureq::get(&url)), 401);
+            assert_eq!(
+                status(ureq::get(&url).set("Authorization", "Bearer wrong")),
+                401
+            );
+            assert_eq!(status(ureq::get(&format!("{}/ws", base))), 401);
+            assert_eq!(status(ureq::get(&format!("{}/", base))), 401);
+
+            let client = BackendClient::new(base.clone()).with_token(Some("secret".to_string()));
+            assert!(client.health().is_ok());
+            assert!(!client.status().unwrap().connected);
+            assert!(matches!(
+                BackendClient::new(base).status(),
+                Err(crate::panel::ApiError::Backend { status: 401, .. })
+            ));
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn parses_command_line() {
+        let args = [
+            "--port",
+            "9000",
+            "--size",
+            "64x16",
+            "--fail-scan",
+            "--latency-ms",
+            "50",
+        ];
+        let no_env = |_: &str| None;
+        let options = MockOptions::from_args(args.iter().map(|a| a.to_string()), no_env).unwrap();
+
+        assert_eq!(options.port, 9000);
+        assert_eq!((options.width, options.height), (64, 16));
+        assert!(options.failures.scan);
+        assert_eq!(options.failures.latency_ms, 50);
+        assert_eq!(options.token, None);
+        assert!(MockOptions::from_args(["--bogus".to_string()].into_iter(), no_env).is_err());
+    }
+
+    #[test]
+    fn reads_the_environment_given() {
+        let env = |name: &str| match name {
+            "PIXELART_BACKEND_PORT" => Some("9100".to_string()),
+            "PIXELART_BACKEND_TOKEN" => Some("secret".to_string()),
+            _ => None,
+        };
+        let options = MockOptions::from_args(std::iter::empty(), env).unwrap();
+        assert_eq!(options.port, 9100);
+        assert_eq!(options.token.as_deref(), Some("secret"));
+
+        // The flag wins over the environment, an empty token disables the check
+        let env = |name: &str| (name != "PIXELART_BACKEND_PORT").then(String::new);
+        let args = ["--port".to_string(), "9000".to_string()];
+        let options = MockOptions::from_args(args.into_iter(), env).unwrap();
+        assert_eq!((options.port, options.token), (9000, None));
+    }
+}
@@ -0,0 +1,290 @@
+//! Blocking HTTP client for the backend API
+
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::types::*;
+use super::{ApiError, PanelApi};
+
+/// Timeout of regular panel requests
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// BLE scans take 10 seconds on the backend side
+const SCAN_TIMEOUT: Duration = Duration::from_secs(20);
+/// Connecting and uploading images over BLE can be slow
+const SLOW_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Client for the backend REST API
+#[derive(Clone)]
+pub struct BackendClient {
+    base_url: String,
+    token: Option<String>,
+}
+
+impl BackendClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            token: None,
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Authenticate requests with the session token given to the backend
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    fn request(&self, method: &str, path: &str, timeout: Duration) -> ureq::Request {
+        let request = ureq::AgentBuilder::new()
+            .timeout(timeout)
+            .build()
+            .request(method, &format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.set("Authorization", &bearer(token)),
+            None => request,
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str, timeout: Duration) -> Result<T, ApiError> {
+        read_json(self.request("GET", path, timeout).call())
+    }
+
+    fn post<T: DeserializeOwned>(&self, path: &str, timeout: Duration) -> Result<T, ApiError> {
+        read_json(self.request("POST", path, timeout).call())
+    }
+
+    fn post_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+        timeout: Duration,
+    ) -> Result<T, ApiError> {
+        read_json(self.request("POST", path, timeout).send_json(body))
+    }
+}
+
+impl PanelApi for BackendClient {
+    fn health(&self) -> Result<HealthResponse, ApiError> {
+        self.get("/", DEFAULT_TIMEOUT)
+    }
+
+    fn scan_devices(&self) -> Result<Vec<Device>, ApiError> {
+        self.get("/devices/scan", SCAN_TIMEOUT)
+    }
+
+    fn connect(&self, request: &ConnectRequest) -> Result<ApiResponse, ApiError> {
+        self.post_json("/devices/connect", request, SLOW_TIMEOUT)
+    }
+
+    fn disconnect(&self) -> Result<ApiResponse, ApiError> {
+        self.post("/devices/disconnect", DEFAULT_TIMEOUT)
+    }
+
+    fn status(&self) -> Result<DeviceStatus, ApiError> {
+        self.get("/devices/status", DEFAULT_TIMEOUT)
+    }
+
+    fn device_info(&self) -> Result<DeviceInfo, ApiError> {
+        self.get("/panel/device-info", DEFAULT_TIMEOUT)
+    }
+
+    fn send_text(&self, request: &TextRequest) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/text", request, SLOW_TIMEOUT)
+    }
+
+    /// Upload an image or GIF as `multipart/form-data`, like a browser form would
+    fn send_image(&self, file_name: &str, data: &[u8]) -> Result<ApiResponse, ApiError> {
+        if data.is_empty() {
+            return Err(ApiError::Invalid("Image is empty".to_string()));
+        }
+
+        let boundary = format!("----pixelart-{:016x}", rand_boundary());
+        let file_name = file_name.replace(['"', '\r', '\n'], "_");
+
+        let mut body = Vec::with_capacity(data.len() + 256);
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(
+            format!(
+                "Content-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n",
+                file_name
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+        body.extend_from_slice(data);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        read_json(
+            self.request("POST", "/panel/image", SLOW_TIMEOUT)
+                .set(
+                    "Content-Type",
+                    &format!("multipart/form-data; boundary={}", boundary),
+                )
+                .send_bytes(&body),
+        )
+    }
+
+    fn set_mode(&self, mode: PanelMode) -> Result<ApiResponse, ApiError> {
+        self.post(&format!("/panel/mode/{}", mode.as_str()), DEFAULT_TIMEOUT)
+    }
+
+    fn set_brightness(&self, request: &BrightnessRequest) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/brightness", request, DEFAULT_TIMEOUT)
+    }
+
+    fn set_orientation(&self, request: &OrientationRequest) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/orientation", request, DEFAULT_TIMEOUT)
+    }
+
+    fn send_pixels(&self, request: &PixelsRequest) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/pixels", request, SLOW_TIMEOUT)
+    }
+
+    fn set_clock_mode(&self, settings: &ClockSettings) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/mode/clock", settings, DEFAULT_TIMEOUT)
+    }
+
+    fn set_rhythm_mode(&self, settings: &RhythmSettings) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/mode/rhythm", settings, DEFAULT_TIMEOUT)
+    }
+
+    fn set_rhythm_mode_2(&self, settings: &RhythmSettings2) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/mode/rhythm2", settings, DEFAULT_TIMEOUT)
+    }
+
+    fn set_power(&self, request: &PowerRequest) -> Result<ApiResponse, ApiError> {
+        self.post_json("/panel/power", request, DEFAULT_TIMEOUT)
+    }
+}
+
+/// `Authorization` header value for a session token
+pub fn bearer(token: &str) -> String {
+    format!("Bearer {}", token)
+}
+
+/// Pseudo-random value for multipart boundaries, no need for a real RNG here
+fn rand_boundary() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    RandomState::new().build_hasher().finish()
+}
+
+/// Decode a JSON response, mapping HTTP and transport failures to `ApiError`
+fn read_json<T: DeserializeOwned>(
+    result: Result<ureq::Response, ureq::Error>,
+) -> Result<T, ApiError> {
+    match result {
+        Ok(response) => response
+            .into_json()
+            .map_err(|e| ApiError::InvalidResponse(e.to_string())),
+        Err(ureq::Error::Status(status, response)) => {
+            // FastAPI reports errors as {"detail": "..."}
+            let detail = response
+                .into_json::<serde_json::Value>()
+                .ok()
+                .and_then(|body| {
+                    body.get("detail").map(|d| {
+                        d.as_str()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| d.to_string())
+                    })
+                })
+                .unwrap_or_else(|| format!("HTTP {}", status));
+            Err(ApiError::from_status(status, detail))
+        }
+        Err(ureq::Error::Transport(transport)) if is_timeout(&transport) => Err(ApiError::Timeout),
+        Err(ureq::Error::Transport(transport)) => Err(ApiError::Unreachable(transport.to_string())),
+    }
+}
+
+fn is_timeout(transport: &ureq::Transport) -> bool {
+    std::error::Error::source(transport)
+        .and_then(|e| e.downcast_ref::<std::io::Error>())
+        .is_some_and(|e| {
+            matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    fn http_error(status: u16, body: &str) -> ureq::Error {
+        let response = ureq::Response::new(status, "Error", body).unwrap();
+        ureq::Error::Status(status, response)
+    }
+
+    fn read_error(error: ureq::Error) -> ApiError {
+        read_json::<ApiResponse>(Err(error)).unwrap_err()
+    }
+
+    #[test]
+    fn maps_http_statuses() {
+        let kind = |status, body| read_error(http_error(status, body)).kind();
+        assert_eq!(
+            kind(400, r#"{"detail": "No device connected"}"#),
+            "not_connected"
+        );
+        assert_eq!(kind(400, r#"{"detail": "Text is empty"}"#), "invalid");
+        assert_eq!(kind(422, r#"{"detail": "Unknown font"}"#), "invalid");
+        assert_eq!(kind(501, r#"{"detail": "Not supported"}"#), "unsupported");
+        assert_eq!(kind(500, "Internal Server Error"), "backend");
+
+        match read_error(http_error(503, r#"{"detail": "Panel busy"}"#)) {
+            ApiError::Backend { status, detail } => {
+                assert_eq!(status, 503);
+                assert_eq!(detail, "Panel busy");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        match read_error(http_error(500, "not json")) {
+            ApiError::Backend { detail, .. } => assert_eq!(detail, "HTTP 500"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        // FastAPI validation errors list the failing fields
+        let body = r#"{"detail": [{"msg": "field required"}]}"#;
+        match read_error(http_error(422, body)) {
+            ApiError::Invalid(detail) => assert_eq!(detail, r#"[{"msg":"field required"}]"#),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unexpected_bodies() {
+        let response = ureq::Response::new(200, "OK", "<html></html>").unwrap();
+        assert_eq!(
+            read_json::<ApiResponse>(Ok(response)).unwrap_err().kind(),
+            "invalid_response"
+        );
+    }
+
+    #[test]
+    fn maps_timeouts() {
+        // Accepts connections through the backlog but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/status", listener.local_addr().unwrap());
+        let result = ureq::get(&url).timeout(Duration::from_millis(100)).call();
+        assert_eq!(read_error(result.unwrap_err()).kind(), "timeout");
+    }
+
+    #[test]
+    fn maps_unreachable_backends() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/status", listener.local_addr().unwrap());
+        drop(listener);
+        let result = ureq::get(&url).timeout(Duration::from_secs(5)).call();
+        assert_eq!(read_error(result.unwrap_err()).kind(), "unreachable");
+    }
+}
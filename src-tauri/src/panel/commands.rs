@@ -0,0 +1,318 @@
+//! Tauri commands proxying the panel API to the backend
+
+use std::sync::Arc;
+
+use tauri::ipc::{InvokeBody, Request, Response};
+use tauri::{AppHandle, Manager, Runtime};
+
+use super::types::*;
+use super::{ApiError, PanelApi};
+use crate::calibration::{CalibratedPanel, CalibrationStore};
+use crate::imaging::{ConvertOptions, UploadReport};
+use crate::ipixel::protocol::Rgb;
+use crate::last_device::LastDevice;
+use crate::{status_relay, BackendConfig};
+
+/// Header carrying the file name of a raw image upload
+pub(crate) const FILE_NAME_HEADER: &str = "x-file-name";
+/// Header selecting how an image is fitted to the panel: crop, fit or stretch
+const RESIZE_METHOD_HEADER: &str = "x-resize-method";
+/// Header carrying the letterbox color of an image upload, as `RRGGBB`
+const BACKGROUND_HEADER: &str = "x-background";
+/// Header selecting the resampling filter: auto, nearest, box or lanczos
+const FILTER_HEADER: &str = "x-filter";
+/// Header selecting the palette: full, a number of colors or `RRGGBB,...`
+const PALETTE_HEADER: &str = "x-palette";
+/// Header selecting the dithering: none, floyd-steinberg, atkinson or ordered
+const DITHER_HEADER: &str = "x-dither";
+/// Header carrying the largest upload the panel accepts, in bytes
+const MAX_BYTES_HEADER: &str = "x-max-bytes";
+
+/// Panel API of the commands, correcting colors for the connected panel
+pub(crate) fn panel_api<R: Runtime>(app: &AppHandle<R>) -> Result<Arc<dyn PanelApi>, ApiError> {
+    let api = uncalibrated_api(app)?;
+    let store = app.state::<CalibrationStore>().inner().clone();
+    Ok(Arc::new(CalibratedPanel::new(api, store)))
+}
+
+/// Panel implementation in use: the native transport when it is enabled,
+/// otherwise the running backend
+pub(crate) fn uncalibrated_api<R: Runtime>(
+    app: &AppHandle<R>,
+) -> Result<Arc<dyn PanelApi>, ApiError> {
+    #[cfg(feature = "native-ble")]
+    if let Some(native) = app.try_state::<Arc<super::native::NativePanel>>() {
+        return Ok(native.inner().clone());
+    }
+
+    app.state::<BackendConfig>()
+        .client()
+        .map(|client| Arc::new(client) as Arc<dyn PanelApi>)
+        .ok_or(ApiError::NotReady)
+}
+
+/// Run a blocking panel call off the async runtime
+async fn blocking<R, T, F>(app: &AppHandle<R>, call: F) -> Result<T, ApiError>
+where
+    R: Runtime,
+    T: Send + 'static,
+    F: FnOnce(&dyn PanelApi) -> Result<T, ApiError> + Send + 'static,
+{
+    let api = panel_api(app)?;
+    tauri::async_runtime::spawn_blocking(move || call(api.as_ref()))
+        .await
+        .map_err(|e| ApiError::Unreachable(e.to_string()))?
+}
+
+/// Check that the backend answers its health check
+#[tauri::command]
+pub async fn ping_backend<R: Runtime>(app: AppHandle<R>) -> Result<HealthResponse, ApiError> {
+    blocking(&app, |api| api.health()).await
+}
+
+#[tauri::command]
+pub async fn scan_devices<R: Runtime>(app: AppHandle<R>) -> Result<Vec<Device>, ApiError> {
+    blocking(&app, |api| api.scan_devices()).await
+}
+
+#[tauri::command]
+pub async fn connect_device<R: Runtime>(
+    app: AppHandle<R>,
+    address: String,
+) -> Result<ApiResponse, ApiError> {
+    let handle = app.clone();
+    blocking(&app, move |api| {
+        let request = ConnectRequest { address };
+        request.validate()?;
+        let response = api.connect(&request)?;
+        handle.state::<LastDevice>().write(&request.address);
+        // Already relayed from the backend, but the native transport has no
+        // status stream
+        status_relay::publish(
+            &handle,
+            DeviceStatus {
+                connected: true,
+                device_address: Some(request.address),
+            },
+        );
+        Ok(response)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn disconnect_device<R: Runtime>(app: AppHandle<R>) -> Result<ApiResponse, ApiError> {
+    let handle = app.clone();
+    blocking(&app, move |api| {
+        let response = api.disconnect()?;
+        status_relay::publish(&handle, DeviceStatus::default());
+        Ok(response)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_device_status<R: Runtime>(app: AppHandle<R>) -> Result<DeviceStatus, ApiError> {
+    blocking(&app, |api| api.status()).await
+}
+
+#[tauri::command]
+pub async fn get_device_info<R: Runtime>(app: AppHandle<R>) -> Result<DeviceInfo, ApiError> {
+    blocking(&app, |api| api.device_info()).await
+}
+
+#[tauri::command]
+pub async fn send_text<R: Runtime>(
+    app: AppHandle<R>,
+    request: TextRequest,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| {
+        request.validate()?;
+        api.send_text(&request)
+    })
+    .await
+}
+
+pub(crate) fn header<'a>(request: &'a Request<'_>, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+pub(crate) fn raw_body(request: &Request<'_>) -> Result<Vec<u8>, ApiError> {
+    match request.body() {
+        InvokeBody::Raw(data) => Ok(data.clone()),
+        _ => Err(ApiError::Invalid("Expected raw file bytes".to_string())),
+    }
+}
+
+/// Conversion options of an image upload, defaults for the missing headers
+fn convert_options(request: &Request<'_>) -> Result<ConvertOptions, ApiError> {
+    let mut options = ConvertOptions::default();
+    if let Some(method) = header(request, RESIZE_METHOD_HEADER) {
+        options.method = method.parse().map_err(ApiError::Invalid)?;
+    }
+    if let Some(color) = header(request, BACKGROUND_HEADER) {
+        options.background = Rgb::from_hex(color).map_err(|e| ApiError::Invalid(e.to_string()))?;
+    }
+    if let Some(filter) = header(request, FILTER_HEADER) {
+        options.filter = filter.parse().map_err(ApiError::Invalid)?;
+    }
+    if let Some(palette) = header(request, PALETTE_HEADER) {
+        options.palette = palette.parse().map_err(ApiError::Invalid)?;
+    }
+    if let Some(dither) = header(request, DITHER_HEADER) {
+        options.dither = dither.parse().map_err(ApiError::Invalid)?;
+    }
+    if let Some(max_bytes) = header(request, MAX_BYTES_HEADER) {
+        options.budget.max_bytes = max_bytes
+            .parse()
+            .map_err(|_| ApiError::Invalid(format!("Invalid byte budget: {}", max_bytes)))?;
+    }
+    Ok(options)
+}
+
+/// Upload an image or animation, sent as the raw invoke body with its file
+/// name in the `x-file-name` header. It is converted for the panel first, as
+/// set by the `x-resize-method`, `x-background`, `x-filter`, `x-palette`,
+/// `x-dither` and `x-max-bytes` headers.
+#[tauri::command]
+pub async fn send_image<R: Runtime>(
+    app: AppHandle<R>,
+    request: Request<'_>,
+) -> Result<ApiResponse, ApiError> {
+    let data = raw_body(&request)?;
+    let options = convert_options(&request)?;
+    let file_name = header(&request, FILE_NAME_HEADER)
+        .unwrap_or("image.png")
+        .to_string();
+
+    blocking(&app, move |api| {
+        super::send_image_resized(api, &file_name, &data, &options)
+    })
+    .await
+}
+
+/// Convert an image the way `send_image` would, e.g. for a preview. Returns
+/// the PNG, or the GIF of an animation, as raw bytes.
+#[tauri::command]
+pub async fn convert_image<R: Runtime>(
+    app: AppHandle<R>,
+    request: Request<'_>,
+) -> Result<Response, ApiError> {
+    let data = raw_body(&request)?;
+    let options = convert_options(&request)?;
+
+    blocking(&app, move |api| {
+        super::convert_for_panel(api, "image", &data, &options)
+    })
+    .await
+    .map(|converted| Response::new(converted.upload.data))
+}
+
+/// Frames, size and upload time of an image or animation once converted
+/// the way `send_image` would
+#[tauri::command]
+pub async fn estimate_upload<R: Runtime>(
+    app: AppHandle<R>,
+    request: Request<'_>,
+) -> Result<UploadReport, ApiError> {
+    let data = raw_body(&request)?;
+    let options = convert_options(&request)?;
+
+    blocking(&app, move |api| {
+        super::convert_for_panel(api, "image", &data, &options)
+    })
+    .await
+    .map(|converted| converted.upload.report)
+}
+
+#[tauri::command]
+pub async fn set_panel_mode<R: Runtime>(
+    app: AppHandle<R>,
+    mode: PanelMode,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| api.set_mode(mode)).await
+}
+
+#[tauri::command]
+pub async fn set_brightness<R: Runtime>(
+    app: AppHandle<R>,
+    request: BrightnessRequest,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| {
+        request.validate()?;
+        api.set_brightness(&request)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_orientation<R: Runtime>(
+    app: AppHandle<R>,
+    request: OrientationRequest,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| {
+        request.validate()?;
+        api.set_orientation(&request)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn send_pixels<R: Runtime>(
+    app: AppHandle<R>,
+    pixels: Vec<PixelData>,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| {
+        let request = PixelsRequest { pixels };
+        request.validate()?;
+        api.send_pixels(&request)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_clock_mode<R: Runtime>(
+    app: AppHandle<R>,
+    settings: ClockSettings,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| {
+        settings.validate()?;
+        api.set_clock_mode(&settings)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_rhythm_mode<R: Runtime>(
+    app: AppHandle<R>,
+    settings: RhythmSettings,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| {
+        settings.validate()?;
+        api.set_rhythm_mode(&settings)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_rhythm_mode_2<R: Runtime>(
+    app: AppHandle<R>,
+    settings: RhythmSettings2,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| {
+        settings.validate()?;
+        api.set_rhythm_mode_2(&settings)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_power<R: Runtime>(
+    app: AppHandle<R>,
+    request: PowerRequest,
+) -> Result<ApiResponse, ApiError> {
+    blocking(&app, move |api| api.set_power(&request)).await
+}
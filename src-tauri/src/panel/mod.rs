@@ -0,0 +1,176 @@
+//! Typed access to the LED panel API.
+//!
+//! The webview talks to the panel through the Tauri commands in [`commands`],
+//! which validate requests and run them against a [`PanelApi`]: the Python
+//! backend through [`BackendClient`] or, with the `native-ble` feature, the
+//! native Bluetooth transport. Failures are mapped to [`ApiError`].
+
+mod client;
+pub mod commands;
+#[cfg(feature = "native-ble")]
+pub mod native;
+pub mod types;
+
+use std::path::Path;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+use crate::font::FontError;
+use crate::imaging::{self, ColorProfile, ConvertOptions, ImageError};
+pub use client::{bearer, BackendClient};
+use types::*;
+
+/// Version of the backend HTTP API this app is built against, announced by
+/// the backend in its health check. Bump it with the backend's on breaking
+/// changes to the API.
+pub const API_VERSION: u32 = 1;
+
+/// Operations on the LED panel.
+///
+/// Requests are validated by the caller, implementations only carry them out.
+pub trait PanelApi: Send + Sync {
+    fn health(&self) -> Result<HealthResponse, ApiError>;
+    fn scan_devices(&self) -> Result<Vec<Device>, ApiError>;
+    fn connect(&self, request: &ConnectRequest) -> Result<ApiResponse, ApiError>;
+    fn disconnect(&self) -> Result<ApiResponse, ApiError>;
+    fn status(&self) -> Result<DeviceStatus, ApiError>;
+    fn device_info(&self) -> Result<DeviceInfo, ApiError>;
+    fn send_text(&self, request: &TextRequest) -> Result<ApiResponse, ApiError>;
+    fn send_image(&self, file_name: &str, data: &[u8]) -> Result<ApiResponse, ApiError>;
+    fn set_mode(&self, mode: PanelMode) -> Result<ApiResponse, ApiError>;
+    fn set_brightness(&self, request: &BrightnessRequest) -> Result<ApiResponse, ApiError>;
+    fn set_orientation(&self, request: &OrientationRequest) -> Result<ApiResponse, ApiError>;
+    fn send_pixels(&self, request: &PixelsRequest) -> Result<ApiResponse, ApiError>;
+    fn set_clock_mode(&self, settings: &ClockSettings) -> Result<ApiResponse, ApiError>;
+    fn set_rhythm_mode(&self, settings: &RhythmSettings) -> Result<ApiResponse, ApiError>;
+    fn set_rhythm_mode_2(&self, settings: &RhythmSettings2) -> Result<ApiResponse, ApiError>;
+    fn set_power(&self, request: &PowerRequest) -> Result<ApiResponse, ApiError>;
+
+    /// Color profile images are converted with for the connected panel,
+    /// described by `info`
+    fn color_profile(&self, _info: &DeviceInfo) -> Result<ColorProfile, ApiError> {
+        Ok(ColorProfile::default())
+    }
+}
+
+/// Error returned by panel commands, serialized as `{ kind, message }`
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// The request was rejected before reaching the backend
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+    /// The backend is not started yet
+    #[error("Backend is not running")]
+    NotReady,
+    /// The backend could not be reached
+    #[error("Backend is unreachable: {0}")]
+    Unreachable(String),
+    /// The backend did not answer in time
+    #[error("Backend request timed out")]
+    Timeout,
+    /// The operation needs a connected panel
+    #[error("No device connected")]
+    NotConnected,
+    /// The backend reported an error
+    #[error("{detail}")]
+    Backend { status: u16, detail: String },
+    /// The backend answered with an unexpected body
+    #[error("Invalid backend response: {0}")]
+    InvalidResponse(String),
+    /// The operation is not available with the current transport
+    #[error("{0}")]
+    Unsupported(String),
+    /// App settings could not be saved
+    #[error("Failed to save the settings: {0}")]
+    Storage(String),
+}
+
+impl ApiError {
+    /// Stable identifier of the error, for the frontend to branch on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ApiError::Invalid(_) => "invalid",
+            ApiError::NotReady => "not_ready",
+            ApiError::Unreachable(_) => "unreachable",
+            ApiError::Timeout => "timeout",
+            ApiError::NotConnected => "not_connected",
+            ApiError::Backend { .. } => "backend",
+            ApiError::InvalidResponse(_) => "invalid_response",
+            ApiError::Unsupported(_) => "unsupported",
+            ApiError::Storage(_) => "storage",
+        }
+    }
+
+    /// Map an HTTP error status from the backend
+    fn from_status(status: u16, detail: String) -> Self {
+        match status {
+            400 if detail == "No device connected" => ApiError::NotConnected,
+            400 | 422 => ApiError::Invalid(detail),
+            501 => ApiError::Unsupported(detail),
+            _ => ApiError::Backend { status, detail },
+        }
+    }
+}
+
+impl From<ImageError> for ApiError {
+    fn from(e: ImageError) -> Self {
+        ApiError::Invalid(e.to_string())
+    }
+}
+
+impl From<FontError> for ApiError {
+    fn from(e: FontError) -> Self {
+        ApiError::Invalid(e.to_string())
+    }
+}
+
+impl Serialize for ApiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ApiError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+/// An image or animation converted for the connected panel
+pub struct PanelUpload {
+    /// Name to upload under, its extension matching the converted format
+    pub file_name: String,
+    pub upload: imaging::Upload,
+}
+
+/// Convert an image to a PNG, or an animation to a GIF, of the size and with
+/// the color profile of the connected panel
+pub fn convert_for_panel(
+    api: &dyn PanelApi,
+    file_name: &str,
+    data: &[u8],
+    options: &ConvertOptions,
+) -> Result<PanelUpload, ApiError> {
+    let info = api.device_info()?;
+    let options = ConvertOptions {
+        color: api.color_profile(&info)?,
+        ..options.clone()
+    };
+    let upload = imaging::convert_upload(data, info.width, info.height, &options)?;
+    // The backend picks the decoder from the extension
+    let extension = if upload.animated { "gif" } else { "png" };
+    let file_name = Path::new(file_name).with_extension(extension);
+    Ok(PanelUpload {
+        file_name: file_name.to_string_lossy().into_owned(),
+        upload,
+    })
+}
+
+/// Send an image or animation converted for the connected panel
+pub fn send_image_resized(
+    api: &dyn PanelApi,
+    file_name: &str,
+    data: &[u8],
+    options: &ConvertOptions,
+) -> Result<ApiResponse, ApiError> {
+    let converted = convert_for_panel(api, file_name, data, options)?;
+    api.send_image(&converted.file_name, &converted.upload.data)
+}
@@ -0,0 +1,301 @@
+//! [`PanelApi`] backed by the native iPixel implementation, without the
+//! Python backend
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Datelike, Local, Timelike};
+use serde_json::{json, Map, Value};
+
+use super::types::*;
+use super::{ApiError, PanelApi, API_VERSION};
+use crate::ipixel::ble::{self, BleTransport};
+use crate::ipixel::protocol::{ClockDate, DeviceInfoReport, Rgb};
+use crate::ipixel::{Panel, PanelError, TransportError};
+
+/// Same scan duration as the Python backend
+const SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Scan duration used to find the panel again when connecting
+const CONNECT_SCAN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Panel connected over Bluetooth from the Rust side
+#[derive(Default)]
+pub struct NativePanel {
+    connection: Mutex<Option<Connection>>,
+}
+
+struct Connection {
+    address: String,
+    panel: Panel<BleTransport>,
+    info: Option<DeviceInfoReport>,
+}
+
+impl From<PanelError> for ApiError {
+    fn from(e: PanelError) -> Self {
+        match e {
+            PanelError::Protocol(e) => ApiError::Invalid(e.to_string()),
+            PanelError::Transport(TransportError::NotConnected) => ApiError::NotConnected,
+            PanelError::Transport(TransportError::Timeout) => ApiError::Timeout,
+            PanelError::Transport(e) => ApiError::Unreachable(e.to_string()),
+        }
+    }
+}
+
+fn success(extra: Value) -> ApiResponse {
+    let extra = match extra {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    };
+    ApiResponse {
+        status: "success".to_string(),
+        extra,
+    }
+}
+
+fn today() -> ClockDate {
+    let now = Local::now();
+    ClockDate {
+        year: now.year() as u16,
+        month: now.month() as u8,
+        day: now.day() as u8,
+        weekday: now.weekday().number_from_monday() as u8,
+    }
+}
+
+fn parse_color(hex: &str) -> Result<Rgb, ApiError> {
+    Rgb::from_hex(hex).map_err(|e| ApiError::Invalid(e.to_string()))
+}
+
+fn to_u8(field: &str, value: u16) -> Result<u8, ApiError> {
+    u8::try_from(value)
+        .map_err(|_| ApiError::Invalid(format!("{} {} is out of the panel", field, value)))
+}
+
+impl NativePanel {
+    /// Run an operation on the connected panel
+    fn with_panel<T>(
+        &self,
+        operation: impl AsyncFnOnce(&mut Connection) -> Result<T, PanelError>,
+    ) -> Result<T, ApiError> {
+        let mut connection = self.connection.lock().map_err(|_| ApiError::NotConnected)?;
+        let connection = connection.as_mut().ok_or(ApiError::NotConnected)?;
+        Ok(tauri::async_runtime::block_on(operation(connection))?)
+    }
+}
+
+impl PanelApi for NativePanel {
+    fn health(&self) -> Result<HealthResponse, ApiError> {
+        Ok(HealthResponse {
+            status: "ok".to_string(),
+            message: Some("Native Bluetooth transport".to_string()),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            api_version: Some(API_VERSION),
+            // No text until glyphs can be rendered, and no backend process
+            // to shut down or push status over a WebSocket
+            capabilities: vec![
+                Capability::Image,
+                Capability::Gif,
+                Capability::Brightness,
+                Capability::Orientation,
+                Capability::DeviceInfo,
+                Capability::ClockMode,
+                Capability::RhythmMode,
+                Capability::RhythmMode2,
+                Capability::DiyMode,
+                Capability::PixelBatch,
+                Capability::Power,
+            ],
+        })
+    }
+
+    fn scan_devices(&self) -> Result<Vec<Device>, ApiError> {
+        let panels =
+            tauri::async_runtime::block_on(ble::scan(SCAN_TIMEOUT)).map_err(PanelError::from)?;
+        Ok(panels
+            .into_iter()
+            .map(|panel| Device {
+                name: panel.name,
+                address: panel.address,
+                rssi: panel.rssi.map(i32::from),
+            })
+            .collect())
+    }
+
+    fn connect(&self, request: &ConnectRequest) -> Result<ApiResponse, ApiError> {
+        let mut connection = self.connection.lock().map_err(|_| ApiError::NotConnected)?;
+        if let Some(mut previous) = connection.take() {
+            let _ = tauri::async_runtime::block_on(previous.panel.disconnect());
+        }
+
+        let transport = tauri::async_runtime::block_on(BleTransport::connect(
+            &request.address,
+            CONNECT_SCAN_TIMEOUT,
+        ))
+        .map_err(PanelError::from)?;
+        *connection = Some(Connection {
+            address: transport.address(),
+            panel: Panel::new(transport),
+            info: None,
+        });
+
+        Ok(success(json!({ "address": request.address })))
+    }
+
+    fn disconnect(&self) -> Result<ApiResponse, ApiError> {
+        let mut connection = self.connection.lock().map_err(|_| ApiError::NotConnected)?;
+        if let Some(mut connection) = connection.take() {
+            tauri::async_runtime::block_on(connection.panel.disconnect())?;
+        }
+        Ok(success(json!({})))
+    }
+
+    fn status(&self) -> Result<DeviceStatus, ApiError> {
+        let connection = self.connection.lock().map_err(|_| ApiError::NotConnected)?;
+        Ok(DeviceStatus {
+            connected: connection.is_some(),
+            device_address: connection.as_ref().map(|c| c.address.clone()),
+        })
+    }
+
+    fn device_info(&self) -> Result<DeviceInfo, ApiError> {
+        let info = self.with_panel(async |connection| {
+            if let Some(info) = connection.info {
+                return Ok(info);
+            }
+            let now = Local::now();
+            let info = connection
+                .panel
+                .device_info(now.hour() as u8, now.minute() as u8, now.second() as u8)
+                .await?;
+            connection.info = Some(info);
+            Ok(info)
+        })?;
+
+        Ok(DeviceInfo {
+            width: info.width.into(),
+            height: info.height.into(),
+            device_type: info.device_type.into(),
+            led_type: info.led_type.into(),
+            has_wifi: info.has_wifi,
+        })
+    }
+
+    fn send_text(&self, _request: &TextRequest) -> Result<ApiResponse, ApiError> {
+        // The panel expects pre-rendered glyphs and there is no font renderer yet
+        Err(ApiError::Unsupported(
+            "Text is not supported by the native transport yet".to_string(),
+        ))
+    }
+
+    fn send_image(&self, file_name: &str, data: &[u8]) -> Result<ApiResponse, ApiError> {
+        let is_gif = data.starts_with(b"GIF8");
+        if !is_gif && !data.starts_with(b"\x89PNG") {
+            return Err(ApiError::Unsupported(
+                "The native transport only sends PNG and GIF files".to_string(),
+            ));
+        }
+
+        self.with_panel(async |connection| {
+            if is_gif {
+                connection.panel.send_gif(data, 0).await
+            } else {
+                connection.panel.send_image(data).await
+            }
+        })?;
+
+        Ok(success(
+            json!({ "filename": file_name, "size": data.len() }),
+        ))
+    }
+
+    fn set_mode(&self, mode: PanelMode) -> Result<ApiResponse, ApiError> {
+        self.with_panel(async |connection| match mode {
+            PanelMode::Clock => {
+                connection
+                    .panel
+                    .set_clock_mode(0, true, true, today())
+                    .await
+            }
+            PanelMode::Rhythm => connection.panel.set_rhythm_mode(0, &[0; 11]).await,
+            PanelMode::Diy => connection.panel.set_fun_mode(true).await,
+        })?;
+        Ok(success(json!({ "mode": mode.as_str() })))
+    }
+
+    fn set_brightness(&self, request: &BrightnessRequest) -> Result<ApiResponse, ApiError> {
+        self.with_panel(async |connection| {
+            connection.panel.set_brightness(request.brightness).await
+        })?;
+        Ok(success(json!({ "brightness": request.brightness })))
+    }
+
+    fn set_orientation(&self, request: &OrientationRequest) -> Result<ApiResponse, ApiError> {
+        self.with_panel(async |connection| {
+            connection.panel.set_orientation(request.orientation).await
+        })?;
+        Ok(success(json!({ "orientation": request.orientation })))
+    }
+
+    fn send_pixels(&self, request: &PixelsRequest) -> Result<ApiResponse, ApiError> {
+        let pixels = request
+            .pixels
+            .iter()
+            .map(|pixel| {
+                Ok((
+                    to_u8("x", pixel.x)?,
+                    to_u8("y", pixel.y)?,
+                    parse_color(&pixel.color)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, ApiError>>()?;
+
+        self.with_panel(async |connection| connection.panel.set_pixels(&pixels).await)?;
+        Ok(success(json!({ "pixels_sent": pixels.len() })))
+    }
+
+    fn set_clock_mode(&self, settings: &ClockSettings) -> Result<ApiResponse, ApiError> {
+        self.with_panel(async |connection| {
+            connection
+                .panel
+                .set_clock_mode(
+                    settings.style,
+                    settings.format_24,
+                    settings.show_date,
+                    today(),
+                )
+                .await
+        })?;
+        Ok(success(json!({ "mode": "clock" })))
+    }
+
+    fn set_rhythm_mode(&self, settings: &RhythmSettings) -> Result<ApiResponse, ApiError> {
+        let levels: [u8; 11] = settings.levels.as_slice().try_into().map_err(|_| {
+            ApiError::Invalid(format!(
+                "Rhythm mode needs 11 levels, got {}",
+                settings.levels.len()
+            ))
+        })?;
+        self.with_panel(async |connection| {
+            connection
+                .panel
+                .set_rhythm_mode(settings.style, &levels)
+                .await
+        })?;
+        Ok(success(json!({ "mode": "rhythm" })))
+    }
+
+    fn set_rhythm_mode_2(&self, settings: &RhythmSettings2) -> Result<ApiResponse, ApiError> {
+        self.with_panel(async |connection| {
+            connection
+                .panel
+                .set_rhythm_mode_2(settings.style, settings.time)
+                .await
+        })?;
+        Ok(success(json!({ "mode": "rhythm2" })))
+    }
+
+    fn set_power(&self, request: &PowerRequest) -> Result<ApiResponse, ApiError> {
+        self.with_panel(async |connection| connection.panel.set_power(request.on).await)?;
+        Ok(success(json!({ "on": request.on })))
+    }
+}
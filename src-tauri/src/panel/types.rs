@@ -0,0 +1,404 @@
+//! Request and response types of the backend API.
+//!
+//! These mirror the Pydantic models in `python-backend/src/main.py` and the
+//! TypeScript types in `src/types/led-panel.ts`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{ApiError, API_VERSION};
+use crate::startup_error::StartupError;
+
+/// BLE device found by a scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    pub name: String,
+    pub address: String,
+    #[serde(default)]
+    pub rssi: Option<i32>,
+}
+
+/// Connection status of the backend
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceStatus {
+    pub connected: bool,
+    #[serde(default)]
+    pub device_address: Option<String>,
+}
+
+/// Panel information reported by the connected device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub width: u32,
+    pub height: u32,
+    pub device_type: u32,
+    pub led_type: u32,
+    pub has_wifi: bool,
+}
+
+/// Request to connect to a device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectRequest {
+    pub address: String,
+}
+
+/// Request to send text to the panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextRequest {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rainbow_mode: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_height: Option<u8>,
+}
+
+/// Request to set the brightness (0-100)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrightnessRequest {
+    pub brightness: u8,
+}
+
+/// Request to set the orientation (0-3: 0°, 90°, 180°, 270°)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrientationRequest {
+    pub orientation: u8,
+}
+
+/// A single pixel in DIY mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PixelData {
+    pub x: u16,
+    pub y: u16,
+    /// Hex color "RRGGBB"
+    pub color: String,
+}
+
+/// Request to send several pixels at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PixelsRequest {
+    pub pixels: Vec<PixelData>,
+}
+
+/// Clock mode settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockSettings {
+    /// 0-8
+    pub style: u8,
+    pub format_24: bool,
+    pub show_date: bool,
+}
+
+/// Rhythm mode v1 settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RhythmSettings {
+    /// 0-4
+    pub style: u8,
+    /// 11 values 0-15
+    pub levels: Vec<u8>,
+}
+
+/// Rhythm mode v2 settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RhythmSettings2 {
+    /// 0-1
+    pub style: u8,
+    /// 0-7
+    pub time: u8,
+}
+
+/// Request to power the panel on or off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerRequest {
+    pub on: bool,
+}
+
+/// Modes that can be activated without settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PanelMode {
+    Clock,
+    Rhythm,
+    Diy,
+}
+
+impl PanelMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PanelMode::Clock => "clock",
+            PanelMode::Rhythm => "rhythm",
+            PanelMode::Diy => "diy",
+        }
+    }
+}
+
+/// Generic backend response: a status plus endpoint specific fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiResponse {
+    pub status: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Features a backend implements, listed by its health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Text,
+    Image,
+    Gif,
+    Brightness,
+    Orientation,
+    DeviceInfo,
+    ClockMode,
+    RhythmMode,
+    RhythmMode2,
+    DiyMode,
+    /// Several pixels per `/panel/pixels` request
+    PixelBatch,
+    Power,
+    Shutdown,
+    /// Connection status pushed on `/ws`
+    StatusWebsocket,
+    /// Announced by a newer backend, unknown to this app
+    #[serde(other)]
+    Unknown,
+}
+
+/// Health check response of `GET /`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Version of the HTTP API, missing on backends older than the handshake
+    #[serde(default)]
+    pub api_version: Option<u32>,
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+impl HealthResponse {
+    /// Check that the backend speaks the API version this app was built for
+    pub fn check_compatible(&self) -> Result<(), StartupError> {
+        match self.api_version {
+            Some(API_VERSION) => Ok(()),
+            found => Err(StartupError::VersionMismatch {
+                found,
+                backend_version: self.version.clone().unwrap_or_else(|| "unknown".to_string()),
+            }),
+        }
+    }
+}
+
+/// Client-side validation, run before a request is sent to the backend
+pub trait Validate {
+    fn validate(&self) -> Result<(), ApiError>;
+}
+
+fn check_range(field: &str, value: u8, max: u8) -> Result<(), ApiError> {
+    if value > max {
+        return Err(ApiError::Invalid(format!(
+            "{} must be between 0 and {}, got {}",
+            field, max, value
+        )));
+    }
+    Ok(())
+}
+
+fn check_color(field: &str, color: &str) -> Result<(), ApiError> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ApiError::Invalid(format!(
+            "{} must be a hex color like \"FF00FF\", got \"{}\"",
+            field, color
+        )));
+    }
+    Ok(())
+}
+
+impl Validate for ConnectRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.address.trim().is_empty() {
+            return Err(ApiError::Invalid("Device address is empty".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for TextRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.text.is_empty() {
+            return Err(ApiError::Invalid("Text is empty".to_string()));
+        }
+        if let Some(color) = &self.color {
+            check_color("color", color)?;
+        }
+        if let Some(animation) = self.animation {
+            check_range("animation", animation, 7)?;
+        }
+        if let Some(speed) = self.speed {
+            check_range("speed", speed, 100)?;
+        }
+        if let Some(rainbow_mode) = self.rainbow_mode {
+            check_range("rainbow_mode", rainbow_mode, 9)?;
+        }
+        if self.char_height == Some(0) {
+            return Err(ApiError::Invalid("char_height must be positive".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for BrightnessRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_range("brightness", self.brightness, 100)
+    }
+}
+
+impl Validate for OrientationRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_range("orientation", self.orientation, 3)
+    }
+}
+
+impl Validate for PixelsRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        for pixel in &self.pixels {
+            check_color("pixel color", &pixel.color)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for ClockSettings {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_range("clock style", self.style, 8)
+    }
+}
+
+impl Validate for RhythmSettings {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_range("rhythm style", self.style, 4)?;
+        if self.levels.len() != 11 {
+            return Err(ApiError::Invalid(format!(
+                "Rhythm mode needs 11 levels, got {}",
+                self.levels.len()
+            )));
+        }
+        for level in &self.levels {
+            check_range("rhythm level", *level, 15)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for RhythmSettings2 {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_range("rhythm style", self.style, 1)?;
+        check_range("rhythm time", self.time, 7)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> TextRequest {
+        TextRequest {
+            text: text.to_string(),
+            color: None,
+            font: None,
+            animation: None,
+            speed: None,
+            rainbow_mode: None,
+            char_height: None,
+        }
+    }
+
+    fn pixels(color: &str) -> PixelsRequest {
+        PixelsRequest {
+            pixels: vec![PixelData {
+                x: 0,
+                y: 0,
+                color: color.to_string(),
+            }],
+        }
+    }
+
+    fn is_invalid<T: Validate>(request: &T) -> bool {
+        matches!(request.validate(), Err(ApiError::Invalid(_)))
+    }
+
+    #[test]
+    fn checks_the_brightness_range() {
+        assert!(BrightnessRequest { brightness: 0 }.validate().is_ok());
+        assert!(BrightnessRequest { brightness: 100 }.validate().is_ok());
+        assert!(is_invalid(&BrightnessRequest { brightness: 101 }));
+        assert_eq!(
+            BrightnessRequest { brightness: 101 }
+                .validate()
+                .unwrap_err()
+                .to_string(),
+            "Invalid request: brightness must be between 0 and 100, got 101"
+        );
+    }
+
+    #[test]
+    fn rejects_empty_text() {
+        assert!(text("Hello").validate().is_ok());
+        assert!(text(" ").validate().is_ok());
+        assert!(is_invalid(&text("")));
+        assert!(is_invalid(&TextRequest {
+            char_height: Some(0),
+            ..text("Hello")
+        }));
+        assert!(is_invalid(&TextRequest {
+            animation: Some(8),
+            ..text("Hello")
+        }));
+    }
+
+    #[test]
+    fn checks_hex_colors() {
+        for color in ["FF00FF", "#ff00ff", "00aa11"] {
+            assert!(pixels(color).validate().is_ok(), "{}", color);
+            let request = TextRequest {
+                color: Some(color.to_string()),
+                ..text("Hello")
+            };
+            assert!(request.validate().is_ok(), "{}", color);
+        }
+        let bad = ["", "#", "FF00F", "FF00FF0", "GG00FF", "##FF00FF", "red"];
+        for color in bad {
+            assert!(is_invalid(&pixels(color)), "{:?}", color);
+            let request = TextRequest {
+                color: Some(color.to_string()),
+                ..text("Hello")
+            };
+            assert!(is_invalid(&request), "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_addresses() {
+        let connect = |address: &str| ConnectRequest {
+            address: address.to_string(),
+        };
+        assert!(connect("AA:BB:CC:DD:EE:FF").validate().is_ok());
+        // macOS identifies devices by UUID instead of a MAC address
+        let uuid = "5A3E1C0B-8F2D-4E6A-9B7C-1D2E3F4A5B6C";
+        assert!(connect(uuid).validate().is_ok());
+        assert!(is_invalid(&connect("")));
+        assert!(is_invalid(&connect("  \t")));
+    }
+}
@@ -0,0 +1,143 @@
+//! Handling of the backend process tree.
+//!
+//! The bundled backend is a PyInstaller onefile build: the process we spawn is
+//! a bootloader which runs the Python interpreter as a child, so killing the
+//! spawned process alone leaves the interpreter running with the port and the
+//! BLE link. The backend is therefore started in its own process group and
+//! stopped as a whole.
+
+use std::io;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+/// How long the tree gets to handle `SIGTERM` before being killed
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Start the command in a new process group, led by the spawned process
+pub fn isolate(command: &mut Command) -> &mut Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+    command
+}
+
+/// Wait up to `timeout` for the child to exit on its own
+pub fn wait_for_exit(child: &mut Child, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(Some(_)) => return true,
+            Ok(None) => std::thread::sleep(Duration::from_millis(100)),
+            Err(_) => return false,
+        }
+    }
+    false
+}
+
+/// Stop a child started with [`isolate`] and everything it spawned.
+///
+/// The group first gets `SIGTERM` so the backend can release the panel, then
+/// `SIGKILL` for whatever is left. On Windows the tree is killed right away.
+pub fn kill_tree(child: &mut Child) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        signal_group(child.id(), libc::SIGTERM)?;
+        wait_for_exit(child, TERMINATE_GRACE_PERIOD);
+        signal_group(child.id(), libc::SIGKILL)?;
+    }
+    #[cfg(windows)]
+    {
+        // `/T` also kills the processes started by the child
+        let _ = Command::new("taskkill")
+            .args(["/PID", &child.id().to_string(), "/T", "/F"])
+            .output();
+        let _ = child.kill();
+    }
+    child.wait().map(|_| ())
+}
+
+/// Kill what is left of the group of a child that already exited, e.g. an
+/// interpreter that outlived its bootloader
+pub fn kill_leftovers(child: &Child) -> io::Result<()> {
+    #[cfg(unix)]
+    signal_group(child.id(), libc::SIGKILL)?;
+    #[cfg(not(unix))]
+    let _ = child;
+    Ok(())
+}
+
+/// Stop the process group led by `pid`, for a backend we have no [`Child`]
+/// handle for, e.g. one left behind by a previous session
+pub fn kill_group(pid: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        signal_group(pid, libc::SIGTERM)?;
+        let deadline = Instant::now() + TERMINATE_GRACE_PERIOD;
+        while group_exists(pid) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        signal_group(pid, libc::SIGKILL)?;
+    }
+    #[cfg(windows)]
+    {
+        let output = Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .output()?;
+        if !output.status.success() && is_running(pid) {
+            return Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a process with this pid exists
+pub fn is_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: signal 0 only checks that the process exists
+        if unsafe { libc::kill(pid as libc::pid_t, 0) } == 0 {
+            return true;
+        }
+        // The process exists but belongs to another user
+        io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(windows)]
+    {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(unix)]
+fn group_exists(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the group exists
+    unsafe { libc::killpg(pid as libc::pid_t, 0) == 0 }
+}
+
+/// Send `signal` to the process group led by `pid`, an empty group is fine
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) -> io::Result<()> {
+    // SAFETY: killpg has no memory safety requirements
+    if unsafe { libc::killpg(pid as libc::pid_t, signal) } == 0 {
+        return Ok(());
+    }
+    let error = io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::ESRCH) {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
@@ -0,0 +1,145 @@
+//! systemd user service running the app headless at login (Linux).
+//!
+//! `pixelart-controller --install-service` writes the unit and enables it,
+//! `--uninstall-service` removes it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::HEADLESS_ARG;
+
+pub const INSTALL_SERVICE_ARG: &str = "--install-service";
+pub const UNINSTALL_SERVICE_ARG: &str = "--uninstall-service";
+
+const SERVICE_NAME: &str = "pixelart-controller.service";
+
+/// Handle the service arguments, returns the process exit code when one was
+/// given
+pub fn handle_args(args: &[String]) -> Option<i32> {
+    let result = match args.first().map(String::as_str) {
+        Some(INSTALL_SERVICE_ARG) => install(),
+        Some(UNINSTALL_SERVICE_ARG) => uninstall(),
+        _ => return None,
+    };
+    Some(match result {
+        Ok(message) => {
+            println!("{}", message);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    })
+}
+
+fn unit_dir() -> io::Result<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or_else(|| io::Error::other("Neither XDG_CONFIG_HOME nor HOME is set"))?;
+    Ok(config_dir.join("systemd/user"))
+}
+
+/// Executable the service should run. An AppImage is mounted at a different
+/// path on every run, the image itself must be started.
+fn executable() -> io::Result<PathBuf> {
+    match std::env::var_os("APPIMAGE") {
+        Some(appimage) => Ok(PathBuf::from(appimage)),
+        None => std::env::current_exe(),
+    }
+}
+
+/// Content of the unit file. The tray needs the desktop session, so the
+/// service is tied to it rather than to the user manager.
+///
+/// systemd reads `%` as a specifier and `$` as a variable in `ExecStart=`,
+/// both are doubled to keep the path as is.
+fn unit_file(executable: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=PixelArt Controller (headless)\n\
+         PartOf=graphical-session.target\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         ExecStart=\"{}\" {}\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=graphical-session.target\n",
+        executable
+            .display()
+            .to_string()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%")
+            .replace('$', "$$"),
+        HEADLESS_ARG
+    )
+}
+
+fn systemctl(args: &[&str]) -> io::Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "systemctl --user {} failed ({})",
+            args.join(" "),
+            status
+        )))
+    }
+}
+
+fn install() -> io::Result<String> {
+    let dir = unit_dir()?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(SERVICE_NAME);
+    fs::write(&path, unit_file(&executable()?))?;
+
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", SERVICE_NAME])?;
+    Ok(format!(
+        "Installed and started {} ({})",
+        SERVICE_NAME,
+        path.display()
+    ))
+}
+
+fn uninstall() -> io::Result<String> {
+    let path = unit_dir()?.join(SERVICE_NAME);
+    if !path.exists() {
+        return Ok(format!("{} is not installed", SERVICE_NAME));
+    }
+
+    systemctl(&["disable", "--now", SERVICE_NAME])?;
+    fs::remove_file(&path)?;
+    systemctl(&["daemon-reload"])?;
+    Ok(format!("Removed {}", SERVICE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_runs_the_app_headless() {
+        let unit = unit_file(Path::new("/opt/Pixel Art/pixelart-controller"));
+        assert!(unit.contains("ExecStart=\"/opt/Pixel Art/pixelart-controller\" --headless\n"));
+        assert!(unit.contains("WantedBy=graphical-session.target"));
+    }
+
+    #[test]
+    fn unit_escapes_specifiers() {
+        let unit = unit_file(Path::new("/home/me/100%/$HOME/pixelart-controller"));
+        assert!(
+            unit.contains("ExecStart=\"/home/me/100%%/$$HOME/pixelart-controller\" --headless\n")
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Errors preventing the app from reaching the panel at startup.
+//!
+//! They end up on the splash window error page, so each one comes with a hint
+//! telling the user what to do about it.
+
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::panel::API_VERSION;
+
+#[derive(Debug, Error)]
+pub enum StartupError {
+    /// The app resources can't be located
+    #[error("Failed to get the resource directory: {0}")]
+    ResourceDirMissing(String),
+    /// The bundled backend is not where it should be
+    #[error("Backend executable not found at: {}", .0.display())]
+    BinaryMissing(PathBuf),
+    /// The bundled backend lost its execute permission
+    #[error("Backend executable is not executable: {}", .0.display())]
+    NotExecutable(PathBuf),
+    /// `PIXELART_BACKEND=source` without the development venv
+    #[error("Python venv not found at: {}", .0.display())]
+    VenvMissing(PathBuf),
+    /// The backend process could not be started
+    #[error("Failed to spawn backend process: {0}")]
+    Spawn(#[source] io::Error),
+    /// Another program holds the backend port
+    #[error("Port {port} is already in use by another program ({source})")]
+    PortInUse { port: u16, source: io::Error },
+    /// No loopback port could be reserved for the backend
+    #[error("Failed to find a free port for the backend: {0}")]
+    NoFreePort(#[source] io::Error),
+    /// The backend never answered its health check
+    #[error("Backend failed to start after {attempts} attempts: {last_error}")]
+    ReadinessTimeout { attempts: u32, last_error: String },
+    /// A program other than the backend answered the health check
+    #[error("Something other than the PixelArt backend answered on {url}: {detail}")]
+    NotOurBackend { url: String, detail: String },
+    /// The backend speaks another API version
+    #[error("{}", version_mismatch_message(*.found, .backend_version))]
+    VersionMismatch {
+        found: Option<u32>,
+        backend_version: String,
+    },
+    /// `PIXELART_BACKEND_URL` points at nothing
+    #[error("No backend answering at {url}: {last_error}")]
+    ExternalUnreachable { url: String, last_error: String },
+    /// The backend of a crashed session survived and couldn't be stopped
+    #[error("A backend from a previous session (pid {pid}) is still running and could not be stopped: {source}")]
+    StaleBackend { pid: u32, source: io::Error },
+    /// An environment variable has an invalid value
+    #[error("Invalid {variable} value: {value}")]
+    InvalidConfig {
+        variable: &'static str,
+        value: String,
+    },
+    /// The selected mode was left out of this build
+    #[error("{setting} needs a build with the {feature} feature")]
+    FeatureMissing {
+        setting: String,
+        feature: &'static str,
+    },
+    /// Unexpected failure of the app itself
+    #[error("{0}")]
+    Internal(String),
+}
+
+fn version_mismatch_message(found: Option<u32>, backend_version: &str) -> String {
+    match found {
+        Some(version) => format!(
+            "Backend API version {} (backend {}) doesn't match the version {} expected by this app",
+            version, backend_version, API_VERSION
+        ),
+        None => format!(
+            "Backend {} is too old for this app (no API version, expected {})",
+            backend_version, API_VERSION
+        ),
+    }
+}
+
+impl StartupError {
+    /// Stable identifier of the error, for the frontend to branch on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StartupError::ResourceDirMissing(_) => "resource_dir_missing",
+            StartupError::BinaryMissing(_) => "binary_missing",
+            StartupError::NotExecutable(_) => "not_executable",
+            StartupError::VenvMissing(_) => "venv_missing",
+            StartupError::Spawn(_) => "spawn",
+            StartupError::PortInUse { .. } => "port_in_use",
+            StartupError::NoFreePort(_) => "no_free_port",
+            StartupError::ReadinessTimeout { .. } => "readiness_timeout",
+            StartupError::NotOurBackend { .. } => "not_our_backend",
+            StartupError::VersionMismatch { .. } => "version_mismatch",
+            StartupError::ExternalUnreachable { .. } => "external_unreachable",
+            StartupError::StaleBackend { .. } => "stale_backend",
+            StartupError::InvalidConfig { .. } => "invalid_config",
+            StartupError::FeatureMissing { .. } => "feature_missing",
+            StartupError::Internal(_) => "internal",
+        }
+    }
+
+    /// What the user can do about it
+    pub fn hint(&self) -> String {
+        match self {
+            StartupError::ResourceDirMissing(_) => "Reinstall the app.".to_string(),
+            StartupError::BinaryMissing(_) => {
+                "Reinstall the app. In development, build the backend with `npm run build:backend`, \
+                 or use PIXELART_BACKEND=source or PIXELART_BACKEND_URL."
+                    .to_string()
+            }
+            StartupError::NotExecutable(path) => format!(
+                "Restore the permission with `chmod +x {}` or reinstall the app.",
+                path.display()
+            ),
+            StartupError::VenvMissing(_) if cfg!(windows) => {
+                "Create it in python-backend with `py -m venv venv` and \
+                 `venv\\Scripts\\pip install -r requirements.txt`."
+                    .to_string()
+            }
+            StartupError::VenvMissing(_) => {
+                "Create it in python-backend with `python3 -m venv venv` and \
+                 `venv/bin/pip install -r requirements.txt`."
+                    .to_string()
+            }
+            StartupError::Spawn(_) => {
+                "Check that no security software blocks the backend executable, then retry."
+                    .to_string()
+            }
+            StartupError::PortInUse { port, .. } => format!(
+                "Close the program using port {} or pick another port with PIXELART_BACKEND_PORT.",
+                port
+            ),
+            StartupError::NoFreePort(_) => {
+                "Check that the loopback network interface is up, then retry.".to_string()
+            }
+            StartupError::ReadinessTimeout { .. } => {
+                "Look for errors in backend.log in the app log directory, then retry."
+                    .to_string()
+            }
+            StartupError::NotOurBackend { .. } => {
+                "Another program answers on the backend port, pick another one with PIXELART_BACKEND_PORT."
+                    .to_string()
+            }
+            StartupError::VersionMismatch { .. } => {
+                "Reinstall the app to update it together with its backend.".to_string()
+            }
+            StartupError::ExternalUnreachable { .. } => {
+                "Start the backend (`python src/main.py` in python-backend) or fix PIXELART_BACKEND_URL, then retry."
+                    .to_string()
+            }
+            StartupError::StaleBackend { pid, .. } => {
+                format!("Stop process {} by hand, then retry.", pid)
+            }
+            StartupError::InvalidConfig { variable, .. } => {
+                format!("Fix or unset {}, then restart the app.", variable)
+            }
+            StartupError::FeatureMissing { feature, .. } => format!(
+                "Rebuild with `--features {}`, or unset PIXELART_BACKEND.",
+                feature
+            ),
+            StartupError::Internal(_) => "Retry, and report the issue if it persists.".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::StartupStatus;
+
+    fn failed(error: StartupError) -> serde_json::Value {
+        serde_json::to_value(StartupStatus::from(&error)).unwrap()
+    }
+
+    #[test]
+    fn serializes_for_the_splash_window() {
+        assert_eq!(
+            failed(StartupError::PortInUse {
+                port: 8000,
+                source: io::Error::other("address in use"),
+            }),
+            json!({
+                "state": "failed",
+                "kind": "port_in_use",
+                "message": "Port 8000 is already in use by another program (address in use)",
+                "hint": "Close the program using port 8000 or pick another port with PIXELART_BACKEND_PORT.",
+            })
+        );
+        assert_eq!(
+            failed(StartupError::VersionMismatch {
+                found: None,
+                backend_version: "0.9.0".to_string(),
+            })["message"],
+            format!(
+                "Backend 0.9.0 is too old for this app (no API version, expected {})",
+                API_VERSION
+            )
+        );
+
+        let status = failed(StartupError::NotOurBackend {
+            url: "http://127.0.0.1:8000/".to_string(),
+            detail: "Missing or invalid backend token".to_string(),
+        });
+        assert_eq!(status["kind"], "not_our_backend");
+        assert!(status["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("on http://127.0.0.1:8000/: Missing or invalid backend token"));
+    }
+
+    #[test]
+    fn venv_hint_fits_the_platform() {
+        let hint = StartupError::VenvMissing(PathBuf::from("venv")).hint();
+        let pip = if cfg!(windows) {
+            "venv\\Scripts\\pip"
+        } else {
+            "venv/bin/pip"
+        };
+        assert!(hint.contains(pip), "{}", hint);
+    }
+}
@@ -0,0 +1,247 @@
+//! Relay of the backend status WebSocket to the webview.
+//!
+//! The shell holds the only subscription to the backend `/ws` endpoint and
+//! re-emits its messages as `panel://status` events. It follows the backend
+//! across restarts and port changes, and keeps the last status so a window
+//! opened later gets it with `get_connection_state`.
+
+use std::io;
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{debug, info, warn};
+use tungstenite::handshake::HandshakeError;
+use tungstenite::http::Uri;
+use tungstenite::{Message, WebSocket};
+
+use crate::panel::types::DeviceStatus;
+use crate::{BackendConfig, BackendProcess};
+
+/// Delay before the first reconnection attempt
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound of the delay between reconnection attempts
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(10);
+/// Silence after which the backend is pinged, and then given up on if it
+/// doesn't answer either
+const READ_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Message sent by the backend on its status WebSocket
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BackendMessage {
+    Status(DeviceStatus),
+    /// Message types added by newer backends
+    #[serde(other)]
+    Unknown,
+}
+
+/// Last connection status reported by the backend
+#[derive(Default)]
+pub struct ConnectionState(Mutex<DeviceStatus>);
+
+impl ConnectionState {
+    fn get(&self) -> DeviceStatus {
+        self.0.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+}
+
+/// Record the status and emit `panel://status` when it changed
+pub fn publish<R: Runtime>(app: &AppHandle<R>, status: DeviceStatus) {
+    let state = app.state::<ConnectionState>();
+    let Ok(mut current) = state.0.lock() else {
+        return;
+    };
+    if *current == status {
+        return;
+    }
+    info!(connected = status.connected, address = ?status.device_address, "Panel connection changed");
+    *current = status.clone();
+    drop(current);
+    let _ = app.emit("panel://status", status);
+}
+
+/// Subscribe to the backend status in the background, until the app shuts down
+pub fn start<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut failures = 0;
+        while !app.state::<BackendProcess>().is_shutting_down() {
+            match subscribe(&app) {
+                // The backend went away after a working subscription, it may
+                // be restarting: start over from the shortest delay
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    debug!(error = %e, failures, "Backend status subscription failed");
+                    failures += 1;
+                }
+            }
+            std::thread::sleep(reconnect_delay(failures));
+        }
+    });
+}
+
+/// Delay before the next subscription attempt (exponential backoff, capped)
+fn reconnect_delay(failures: u32) -> Duration {
+    RECONNECT_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+/// Relay the messages of one WebSocket connection, returns once it is closed.
+/// The URL is read on every call, as the backend may have moved to another
+/// port after a failed startup was retried.
+fn subscribe<R: Runtime>(app: &AppHandle<R>) -> Result<(), tungstenite::Error> {
+    let Some(url) = ws_url(&app.state::<BackendConfig>()) else {
+        return Err(tungstenite::Error::ConnectionClosed);
+    };
+    let mut socket = connect(&url)?;
+    info!("Subscribed to the backend status");
+    let result = relay(&mut socket, |status| publish(app, status));
+
+    // The panel connection lives in the backend process, it is lost with it
+    info!("Backend status subscription closed");
+    publish(app, DeviceStatus::default());
+    result
+}
+
+/// Pass the status messages of the socket on until it is closed, or the
+/// backend stops answering
+fn relay(
+    socket: &mut WebSocket<TcpStream>,
+    mut on_status: impl FnMut(DeviceStatus),
+) -> Result<(), tungstenite::Error> {
+    let mut pinged = false;
+    loop {
+        let message = socket.read();
+        if message.is_ok() {
+            pinged = false;
+        }
+        match message {
+            Ok(Message::Text(text)) => match serde_json::from_str::<BackendMessage>(&text) {
+                Ok(BackendMessage::Status(status)) => on_status(status),
+                Ok(BackendMessage::Unknown) => debug!(%text, "Ignoring backend message"),
+                Err(e) => warn!(error = %e, %text, "Invalid backend status message"),
+            },
+            Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Ok(_) => {}
+            // A quiet backend is fine as long as it answers pings, a hung one
+            // would otherwise leave the status stale for good
+            Err(tungstenite::Error::Io(e)) if is_timeout(&e) => {
+                if pinged {
+                    warn!("Backend status stopped answering, reconnecting");
+                    return Err(e.into());
+                }
+                pinged = true;
+                socket.send(Message::Ping(Default::default()))?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Open the WebSocket with a read timeout on its socket, so that a hung
+/// backend can't block the handshake or the relay forever
+fn connect(url: &str) -> Result<WebSocket<TcpStream>, tungstenite::Error> {
+    let uri: Uri = url.parse()?;
+    let stream = TcpStream::connect(uri.authority().map_or("", |a| a.as_str()))?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    match tungstenite::client(uri, stream) {
+        Ok((socket, _)) => Ok(socket),
+        Err(HandshakeError::Failure(e)) => Err(e),
+        Err(HandshakeError::Interrupted(_)) => Err(io::Error::from(io::ErrorKind::TimedOut).into()),
+    }
+}
+
+/// Read timeouts are reported as `WouldBlock` on Unix and `TimedOut` on Windows
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// URL of the backend status WebSocket. Browsers can't set headers on a
+/// WebSocket, so the backend takes the session token from the query string.
+fn ws_url(config: &BackendConfig) -> Option<String> {
+    let url = config.url()?;
+    Some(format!(
+        "{}/ws?token={}",
+        url.replacen("http", "ws", 1),
+        config.token().unwrap_or_default()
+    ))
+}
+
+/// Last known panel connection status, for windows opened after it changed
+#[tauri::command]
+pub fn get_connection_state(state: State<'_, ConnectionState>) -> DeviceStatus {
+    state.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_backend_messages() {
+        assert_eq!(
+            serde_json::from_str::<BackendMessage>(
+                r#"{"type": "status", "connected": true, "device_address": "AA:BB:CC:DD:EE:FF"}"#
+            )
+            .unwrap(),
+            BackendMessage::Status(DeviceStatus {
+                connected: true,
+                device_address: Some("AA:BB:CC:DD:EE:FF".to_string()),
+            })
+        );
+        assert_eq!(
+            serde_json::from_str::<BackendMessage>(
+                r#"{"type": "status", "connected": false, "device_address": null}"#
+            )
+            .unwrap(),
+            BackendMessage::Status(DeviceStatus::default())
+        );
+        assert_eq!(
+            serde_json::from_str::<BackendMessage>(r#"{"type": "progress", "percent": 40}"#)
+                .unwrap(),
+            BackendMessage::Unknown
+        );
+        assert!(serde_json::from_str::<BackendMessage>(r#"{"type": "status"}"#).is_err());
+    }
+
+    #[test]
+    fn gives_up_on_a_hung_backend() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let (done, wait) = std::sync::mpsc::channel::<()>();
+        let backend = std::thread::spawn(move || {
+            let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            socket
+                .send(Message::text(r#"{"type": "status", "connected": true}"#))
+                .unwrap();
+            // Hung: neither reads the ping nor closes the connection
+            let _ = wait.recv();
+        });
+
+        let mut socket = connect(&url).unwrap();
+        socket
+            .get_ref()
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let mut statuses = Vec::new();
+        let result = relay(&mut socket, |status| statuses.push(status.connected));
+        assert!(matches!(result, Err(tungstenite::Error::Io(e)) if is_timeout(&e)));
+        assert_eq!(statuses, [true]);
+        drop(done);
+        backend.join().unwrap();
+    }
+
+    #[test]
+    fn backs_off_between_attempts() {
+        assert_eq!(reconnect_delay(0), Duration::from_millis(500));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(10), RECONNECT_MAX_DELAY);
+    }
+}
@@ -0,0 +1,233 @@
+//! System tray of the headless mode.
+//!
+//! With `--headless` the app supervises the backend and keeps the panel
+//! connected without showing a window, for panels used as status displays.
+//! The tray menu covers the everyday controls and can bring the window back.
+
+use tauri::menu::{IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{info, warn};
+
+use crate::last_device::LastDevice;
+use crate::launch_request::{self, RecentContent};
+use crate::panel::commands::panel_api;
+use crate::panel::types::{BrightnessRequest, ConnectRequest, PowerRequest};
+use crate::panel::ApiError;
+use crate::{splash_window, StartupState, StartupStatus, MAIN_WINDOW};
+
+const TRAY_ID: &str = "main";
+const TOOLTIP: &str = "PixelArt Controller";
+/// Brightness entries of the tray menu, in percent
+const BRIGHTNESS_PRESETS: [u8; 5] = [10, 25, 50, 75, 100];
+/// Longest label of a "Recent" entry, in characters
+const MAX_LABEL_CHARS: usize = 32;
+
+/// Marker state of the headless mode
+pub struct Headless;
+
+pub fn is_headless<R: Runtime>(app: &AppHandle<R>) -> bool {
+    app.try_state::<Headless>().is_some()
+}
+
+/// Create the tray icon
+pub fn create<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(TOOLTIP)
+        .menu(&build_menu(app)?)
+        .show_menu_on_left_click(true)
+        .on_menu_event(|app, event| handle_menu_event(app, event.id.as_ref()));
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    Ok(())
+}
+
+/// Rebuild the menu, e.g. after new content was sent
+pub fn refresh<R: Runtime>(app: &AppHandle<R>) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    match build_menu(app) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => warn!(error = %e, "Failed to rebuild the tray menu"),
+    }
+}
+
+/// Show the startup progress on the tray, the only thing visible headless.
+/// Tooltips are not supported everywhere, a failure also gets a menu entry.
+pub fn show_startup_status<R: Runtime>(app: &AppHandle<R>, status: &StartupStatus) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let tooltip = match status {
+        StartupStatus::Failed { message, hint, .. } => {
+            format!("{}: {}\n{}", TOOLTIP, message, hint)
+        }
+        _ => TOOLTIP.to_string(),
+    };
+    let _ = tray.set_tooltip(Some(tooltip));
+    refresh(app);
+}
+
+fn build_menu<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<Menu<R>> {
+    let brightness_items = BRIGHTNESS_PRESETS
+        .iter()
+        .map(|level| {
+            MenuItem::with_id(
+                app,
+                format!("brightness:{}", level),
+                format!("{}%", level),
+                true,
+                None::<&str>,
+            )
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let brightness = Submenu::with_items(app, "Brightness", true, &as_items(&brightness_items))?;
+
+    let recent_items = app
+        .state::<RecentContent>()
+        .list()
+        .iter()
+        .enumerate()
+        .map(|(index, request)| {
+            MenuItem::with_id(
+                app,
+                format!("recent:{}", index),
+                truncate(&request.describe()),
+                true,
+                None::<&str>,
+            )
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let recent = Submenu::with_items(
+        app,
+        "Recent",
+        !recent_items.is_empty(),
+        &as_items(&recent_items),
+    )?;
+
+    let failed = matches!(
+        app.state::<StartupState>().0.lock().as_deref(),
+        Ok(StartupStatus::Failed { .. })
+    );
+    // The splash window shows the error, its hint and a retry button
+    let show_label = if failed {
+        "Backend failed to start, show details"
+    } else {
+        "Show window"
+    };
+
+    Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(app, "show", show_label, true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, "power:on", "Power on", true, None::<&str>)?,
+            &MenuItem::with_id(app, "power:off", "Power off", true, None::<&str>)?,
+            &brightness,
+            &recent,
+            &MenuItem::with_id(app, "reconnect", "Reconnect", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?,
+        ],
+    )
+}
+
+fn as_items<R: Runtime>(items: &[MenuItem<R>]) -> Vec<&dyn IsMenuItem<R>> {
+    items
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<R>)
+        .collect()
+}
+
+fn truncate(label: &str) -> String {
+    if label.chars().count() <= MAX_LABEL_CHARS {
+        return label.to_string();
+    }
+    let mut short: String = label.chars().take(MAX_LABEL_CHARS - 1).collect();
+    short.push('…');
+    short
+}
+
+fn handle_menu_event<R: Runtime>(app: &AppHandle<R>, id: &str) {
+    match id {
+        "show" => show_window(app),
+        "reconnect" => reconnect(app),
+        "quit" => app.exit(0),
+        "power:on" | "power:off" => {
+            let on = id == "power:on";
+            run_panel_action(app, "power", move |api| {
+                api.set_power(&PowerRequest { on }).map(drop)
+            });
+        }
+        _ => {
+            if let Some(brightness) = id
+                .strip_prefix("brightness:")
+                .and_then(|level| level.parse().ok())
+            {
+                run_panel_action(app, "brightness", move |api| {
+                    api.set_brightness(&BrightnessRequest { brightness })
+                        .map(drop)
+                });
+            } else if let Some(index) = id
+                .strip_prefix("recent:")
+                .and_then(|index| index.parse::<usize>().ok())
+            {
+                if let Some(request) = app.state::<RecentContent>().list().into_iter().nth(index) {
+                    launch_request::submit(app, vec![request]);
+                }
+            }
+        }
+    }
+}
+
+/// Show the main window, or the splash window while the backend is starting
+/// or after it failed to
+pub fn show_window<R: Runtime>(app: &AppHandle<R>) {
+    let ready = matches!(
+        app.state::<StartupState>().0.lock().as_deref(),
+        Ok(StartupStatus::Ready)
+    );
+    let window = if ready {
+        app.get_webview_window(MAIN_WINDOW)
+    } else {
+        splash_window(app).ok()
+    };
+    if let Some(window) = window {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Connect again to the last panel, e.g. after it was switched off or the
+/// backend restarted
+pub fn reconnect<R: Runtime>(app: &AppHandle<R>) {
+    let Some(address) = app.state::<LastDevice>().read() else {
+        info!("No panel to reconnect to yet, connect to one from the window first");
+        return;
+    };
+    run_panel_action(app, "reconnect", move |api| {
+        let _ = api.disconnect();
+        api.connect(&ConnectRequest { address }).map(drop)
+    });
+}
+
+fn run_panel_action<R, F>(app: &AppHandle<R>, action: &'static str, call: F)
+where
+    R: Runtime,
+    F: FnOnce(&dyn crate::panel::PanelApi) -> Result<(), ApiError> + Send + 'static,
+{
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = panel_api(&app).and_then(|api| call(api.as_ref()));
+        match result {
+            Ok(()) => info!(action, "Tray action done"),
+            Err(e) => warn!(action, error = %e, "Tray action failed"),
+        }
+    });
+}
@@ -0,0 +1,93 @@
+//! Stopping the backend must not leave processes behind.
+//!
+//! The fake backend mimics the PyInstaller onefile layout: a shell standing in
+//! for the bootloader starts a second shell (the interpreter) which spawns a
+//! long running worker and records its pid.
+
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use pixelart_controller_lib::process_tree;
+
+/// Worker started two levels below the spawned process, its pid is written to `$1`
+const FAKE_BACKEND: &str = r#"sh -c 'sleep 300 & echo $! > "$0"; wait' "$1" & wait"#;
+
+/// Same tree, but the bootloader exits right away and leaves the worker running
+const ORPHANING_BACKEND: &str = r#"sh -c 'sleep 300 & echo $! > "$0"; wait' "$1" &"#;
+
+fn pid_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pixelart-{}-{}.pid", name, std::process::id()))
+}
+
+fn spawn(script: &str, pid_file: &Path) -> Child {
+    let _ = std::fs::remove_file(pid_file);
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script).arg("sh").arg(pid_file);
+    process_tree::isolate(&mut command).spawn().unwrap()
+}
+
+fn read_worker_pid(pid_file: &Path) -> u32 {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        if let Some(pid) = std::fs::read_to_string(pid_file)
+            .ok()
+            .and_then(|content| content.trim().parse().ok())
+        {
+            return pid;
+        }
+        assert!(Instant::now() < deadline, "fake backend never started its worker");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Whether `pid` is running, zombies waiting to be reaped don't count
+fn is_running(pid: u32) -> bool {
+    let output = Command::new("ps")
+        .args(["-o", "stat=", "-p", &pid.to_string()])
+        .output()
+        .unwrap();
+    let state = String::from_utf8_lossy(&output.stdout);
+    !state.trim().is_empty() && !state.trim().starts_with('Z')
+}
+
+fn wait_until_stopped(pid: u32) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if !is_running(pid) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+#[test]
+fn kill_tree_stops_the_grandchildren() {
+    let pid_file = pid_file("tree");
+    let mut child = spawn(FAKE_BACKEND, &pid_file);
+    let worker = read_worker_pid(&pid_file);
+    assert!(is_running(worker));
+
+    process_tree::kill_tree(&mut child).unwrap();
+
+    assert!(child.try_wait().unwrap().is_some());
+    assert!(wait_until_stopped(worker), "worker {} is still running", worker);
+    let _ = std::fs::remove_file(pid_file);
+}
+
+#[test]
+fn kill_leftovers_stops_processes_orphaned_by_the_bootloader() {
+    let pid_file = pid_file("orphan");
+    let mut child = spawn(ORPHANING_BACKEND, &pid_file);
+    let worker = read_worker_pid(&pid_file);
+    child.wait().unwrap();
+    assert!(is_running(worker));
+
+    process_tree::kill_leftovers(&child).unwrap();
+
+    assert!(wait_until_stopped(worker), "worker {} is still running", worker);
+    let _ = std::fs::remove_file(pid_file);
+}
@@ -6,8 +6,9 @@ import {
 } from 'reactstrap';
 import classNames from 'classnames';
 import { Toaster, toast } from 'react-hot-toast';
+import { listen } from '@tauri-apps/api/event';
 import { api } from './services/api';
-import { Device, DeviceStatus, DeviceInfo } from './types/led-panel';
+import { Capability, ColorProfile, ConvertOptions, Device, DeviceStatus, DeviceInfo, Dither, FontInfo, PanelCalibration, RenderTextRequest, ResampleFilter, ResizeMethod, TestPattern, TextAlign, UploadReport } from './types/led-panel';
 
 function App() {
   // Device state managed locally
@@ -16,6 +17,9 @@ function App() {
 
   // State management
   const [activeTab, setActiveTab] = useState<'text' | 'image' | 'pixels' | 'modes' | 'settings'>('text');
+  // Backend features, null until known
+  const [capabilities, setCapabilities] = useState<Capability[] | null>(null);
+  const supports = (capability: Capability) => capabilities === null || capabilities.includes(capability);
   const [devices, setDevices] = useState<Device[]>([]);
   const [scanning, setScanning] = useState(false);
 
@@ -28,6 +32,16 @@ function App() {
   const [rainbowMode, setRainbowMode] = useState(0);
   const [charHeight, setCharHeight] = useState<number | undefined>(16);
 
+  // Imported fonts, text in them is drawn by the app and sent as an image
+  const [fonts, setFonts] = useState<FontInfo[]>([]);
+  const [textSize, setTextSize] = useState<number | undefined>(undefined); // As large as fits
+  const [antialias, setAntialias] = useState(true);
+  const [textAlign, setTextAlign] = useState<TextAlign>('center');
+  const [wrapText, setWrapText] = useState(false);
+  const [textPreview, setTextPreview] = useState<string | null>(null);
+  const [textPreviewError, setTextPreviewError] = useState<string | null>(null);
+  const importedFont = fonts.find((f) => f.name === font);
+
   // Clock settings
   const [clockStyle, setClockStyle] = useState(1);
   const [format24, setFormat24] = useState(true);
@@ -45,7 +59,21 @@ function App() {
   const [designName, setDesignName] = useState('');
 
   // Image preview state
+  const [imageFile, setImageFile] = useState<File | null>(null);
   const [imagePreview, setImagePreview] = useState<string | null>(null);
+  const [previewError, setPreviewError] = useState<string | null>(null);
+  const [uploadReport, setUploadReport] = useState<UploadReport | null>(null);
+  const [resizeMethod, setResizeMethod] = useState<ResizeMethod>('fit');
+  const [letterboxColor, setLetterboxColor] = useState('#000000');
+  const [resampleFilter, setResampleFilter] = useState<ResampleFilter>('auto');
+  const [palette, setPalette] = useState('full'); // "full", a number of colors or "custom"
+  const [customPalette, setCustomPalette] = useState('000000,FFFFFF');
+  const [dither, setDither] = useState<Dither>('none');
+
+  // Color calibration state
+  const [calibration, setCalibration] = useState<PanelCalibration | null>(null);
+  const [colorProfile, setColorProfile] = useState<ColorProfile | null>(null);
+  const [testPattern, setTestPattern] = useState<TestPattern>('ramps');
 
   // Event handlers
   const handleScan = async () => {
@@ -92,11 +120,31 @@ function App() {
     }
   };
 
+  const renderTextRequest = (): RenderTextRequest => ({
+    text,
+    font,
+    size: textSize,
+    antialias,
+    color: textColor,
+    align: textAlign,
+    wrap: wrapText,
+  });
+
   const handleSendText = async () => {
     if (!status.connected) {
       toast.error('Connect to device first');
       return;
     }
+    if (importedFont) {
+      try {
+        await api.sendRenderedText(renderTextRequest());
+        toast.success('Text sent');
+      } catch (error) {
+        console.error('Send text failed:', error);
+        toast.error(`Failed to send text: ${error instanceof Error ? error.message : error}`);
+      }
+      return;
+    }
     try {
       await api.sendText({
         text,
@@ -114,11 +162,53 @@ function App() {
     }
   };
 
-  const handleImageUpload = async (event: React.ChangeEvent<HTMLInputElement>) => {
+  const convertOptions = (): ConvertOptions => ({
+    method: resizeMethod,
+    background: letterboxColor,
+    filter: resampleFilter,
+    palette: palette === 'custom' ? customPalette : palette,
+    // Dithering needs a palette
+    dither: palette === 'full' ? 'none' : dither,
+  });
+
+  const handleFontImport = async (event: React.ChangeEvent<HTMLInputElement>) => {
+    const file = event.target.files?.[0];
+    event.target.value = '';
+    if (!file) return;
+
+    try {
+      const imported = await api.importFont(file);
+      setFonts(await api.listFonts());
+      setFont(imported.name);
+      toast.success(`Imported ${imported.name}`);
+    } catch (error) {
+      console.error('Font import failed:', error);
+      toast.error(`Failed to import the font: ${error instanceof Error ? error.message : error}`);
+    }
+  };
+
+  const handleRemoveFont = async () => {
+    if (!importedFont) return;
+    try {
+      await api.removeFont(importedFont.name);
+      setFonts(await api.listFonts());
+      setFont('CUSONG');
+    } catch (error) {
+      console.error('Font removal failed:', error);
+      toast.error('Failed to remove the font');
+    }
+  };
+
+  const handleImageUpload = (event: React.ChangeEvent<HTMLInputElement>) => {
     const file = event.target.files?.[0];
     if (!file) return;
 
+    setImageFile(file);
     setImagePreview(URL.createObjectURL(file));
+  };
+
+  const handleSendImage = async () => {
+    if (!imageFile) return;
 
     if (!status.connected) {
       toast.error('Connect to device first');
@@ -126,11 +216,11 @@ function App() {
     }
 
     try {
-      await api.sendImage(file);
+      await api.sendImage(imageFile, convertOptions());
       toast.success('Image sent!');
     } catch (error) {
       console.error('Image upload failed:', error);
-      toast.error('Failed to send image');
+      toast.error(`Failed to send image: ${error instanceof Error ? error.message : error}`);
     }
   };
 
@@ -203,6 +293,49 @@ function App() {
     }
   };
 
+  const loadCalibration = (current: PanelCalibration) => {
+    setCalibration(current);
+    setColorProfile(current.profile);
+  };
+
+  // White balance gains are the diagonal of the matrix, the rest is kept
+  const setWhiteBalanceGain = (channel: number, gain: number) => {
+    setColorProfile((profile) => profile && {
+      ...profile,
+      white_balance: profile.white_balance.map((row, index) =>
+        index === channel ? row.map((value, column) => (column === channel ? gain : value)) : row
+      ) as ColorProfile['white_balance'],
+    });
+  };
+
+  const handleSendTestPattern = async () => {
+    if (!colorProfile) return;
+    try {
+      await api.sendTestPattern(testPattern, colorProfile);
+    } catch (error) {
+      toast.error(`Failed to send the test pattern: ${error instanceof Error ? error.message : error}`);
+    }
+  };
+
+  const handleSaveColorProfile = async () => {
+    if (!colorProfile) return;
+    try {
+      loadCalibration(await api.saveColorProfile(colorProfile));
+      toast.success('Color profile saved');
+    } catch (error) {
+      toast.error(`Failed to save the color profile: ${error instanceof Error ? error.message : error}`);
+    }
+  };
+
+  const handleResetColorProfile = async () => {
+    try {
+      loadCalibration(await api.resetColorProfile());
+      toast.success('Color profile reset');
+    } catch (error) {
+      toast.error(`Failed to reset the color profile: ${error instanceof Error ? error.message : error}`);
+    }
+  };
+
   const handleSetOrientation = async (value: number) => {
     setOrientation(value);
     if (!status.connected) return;
@@ -273,21 +406,131 @@ function App() {
     }
   }, []);
 
-  // Check connection status on mount
+  // Report backend crashes and restarts from the Tauri supervisor
   useEffect(() => {
-    const checkStatus = async () => {
-      try {
-        const currentStatus = await api.getStatus();
-        setStatus(currentStatus);
-        if (currentStatus.connected) {
-          const info = await api.getDeviceInfo();
-          setDeviceInfo(info);
-        }
-      } catch (error) {
-        console.error('Failed to get status:', error);
+    const unlistenCrashed = listen<{ will_restart: boolean }>('backend://crashed', (event) => {
+      if (event.payload.will_restart) {
+        toast.error('Backend crashed, restarting...');
+      } else {
+        toast.error('Backend crashed and could not be restarted. Please restart the app.', { duration: Infinity });
+      }
+      setStatus({ connected: false });
+      setDeviceInfo(null);
+    });
+    const unlistenRestarted = listen('backend://restarted', () => {
+      toast.success('Backend restarted, please reconnect to your panel');
+    });
+    // Content passed on the command line, possibly by a second launch of the app
+    const unlistenLaunch = listen<{ request: string; error: string | null }>('app://launch-request', (event) => {
+      const { request, error } = event.payload;
+      if (error) {
+        toast.error(`Failed to send ${request}: ${error}`);
+      } else {
+        toast.success(`Sent ${request}`);
       }
+    });
+
+    return () => {
+      unlistenCrashed.then((unlisten) => unlisten());
+      unlistenRestarted.then((unlisten) => unlisten());
+      unlistenLaunch.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // Hide what the backend can't do, e.g. the panel fonts with the native
+  // transport, which still shows text in imported fonts
+  useEffect(() => {
+    api.getCapabilities()
+      .then(setCapabilities)
+      .catch((error) => console.error('Failed to get backend capabilities:', error));
+    api.listFonts()
+      .then(setFonts)
+      .catch((error) => console.error('Failed to list the fonts:', error));
+  }, []);
+
+  // Without the panel fonts, text can only be shown in an imported font
+  useEffect(() => {
+    if (!supports('text') && !importedFont && fonts.length > 0) {
+      setFont(fonts[0].name);
+    }
+  }, [capabilities, fonts]);
+
+  // Show text in an imported font the way the panel will, it needs the panel size
+  useEffect(() => {
+    setTextPreview(null);
+    setTextPreviewError(null);
+    if (!importedFont || !status.connected || !text) {
+      return;
+    }
+    let cancelled = false;
+    api.renderText(renderTextRequest())
+      .then((png) => {
+        if (!cancelled) {
+          setTextPreview(URL.createObjectURL(png));
+        }
+      })
+      .catch((error) => {
+        if (!cancelled) {
+          setTextPreviewError(error instanceof Error ? error.message : String(error));
+        }
+      });
+    return () => {
+      cancelled = true;
     };
-    checkStatus();
+  }, [importedFont, status.connected, text, textColor, textSize, antialias, textAlign, wrapText]);
+
+  // Show the image the way the panel will, again whenever an option changes.
+  // The conversion needs the panel size.
+  useEffect(() => {
+    setUploadReport(null);
+    if (!imageFile || !status.connected) {
+      setPreviewError(null);
+      return;
+    }
+    let cancelled = false;
+    const options = convertOptions();
+    Promise.all([api.convertImage(imageFile, options), api.estimateUpload(imageFile, options)])
+      .then(([converted, report]) => {
+        if (!cancelled) {
+          setImagePreview(URL.createObjectURL(converted));
+          setUploadReport(report);
+          setPreviewError(null);
+        }
+      })
+      .catch((error) => {
+        if (!cancelled) {
+          setPreviewError(error instanceof Error ? error.message : String(error));
+        }
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [imageFile, status.connected, resizeMethod, letterboxColor, resampleFilter, palette, customPalette, dither]);
+
+  // Load the color profile of the panel when it connects
+  useEffect(() => {
+    if (!status.connected) {
+      setCalibration(null);
+      setColorProfile(null);
+      return;
+    }
+    api.getColorProfile()
+      .then(loadCalibration)
+      .catch((error) => console.error('Failed to get the color profile:', error));
+  }, [status.connected, status.device_address]);
+
+  // Follow the connection status, e.g. a reconnection from the tray
+  useEffect(() => {
+    return api.onStatusChange((currentStatus) => {
+      setStatus(currentStatus);
+      if (currentStatus.connected) {
+        api.getDeviceInfo()
+          .then(setDeviceInfo)
+          .catch((error) => console.error('Failed to get device info:', error));
+      } else {
+        setDeviceInfo(null);
+      }
+    });
   }, [setStatus, setDeviceInfo]);
 
   return (
@@ -382,7 +625,8 @@ function App() {
                               <FormGroup>
                                 <Label>Text Message</Label>
                                 <Input
-                                  type="text"
+                                  type={importedFont ? 'textarea' : 'text'}
+                                  rows={3}
                                   value={text}
                                   onChange={(e) => setText(e.target.value)}
                                   placeholder="Enter text to display..."
@@ -409,14 +653,122 @@ function App() {
                                       value={font}
                                       onChange={(e) => setFont(e.target.value)}
                                     >
-                                      <option value="CUSONG">CUSONG</option>
-                                      <option value="SIMSUN">SIMSUN</option>
-                                      <option value="VCR_OSD_MONO">VCR OSD MONO</option>
+                                      {supports('text') && (
+                                        <optgroup label="Panel fonts">
+                                          <option value="CUSONG">CUSONG</option>
+                                          <option value="SIMSUN">SIMSUN</option>
+                                          <option value="VCR_OSD_MONO">VCR OSD MONO</option>
+                                        </optgroup>
+                                      )}
+                                      {fonts.length > 0 && (
+                                        <optgroup label="Imported fonts">
+                                          {fonts.map((f) => (
+                                            <option key={f.name} value={f.name}>
+                                              {f.size ? `${f.name} (${f.size}px)` : f.name}
+                                            </option>
+                                          ))}
+                                        </optgroup>
+                                      )}
                                     </Input>
                                   </FormGroup>
                                 </Col>
                               </Row>
 
+                              <Row>
+                                <Col md={importedFont ? 10 : 12}>
+                                  <FormGroup>
+                                    <Label>Import a Font (BDF, PCF, TrueType, OpenType)</Label>
+                                    <Input
+                                      type="file"
+                                      accept=".bdf,.pcf,.ttf,.otf,.ttc"
+                                      onChange={handleFontImport}
+                                    />
+                                  </FormGroup>
+                                </Col>
+                                {importedFont && (
+                                  <Col md="2" className="d-flex align-items-end">
+                                    <FormGroup>
+                                      <Button color="danger" outline onClick={handleRemoveFont}>
+                                        Remove Font
+                                      </Button>
+                                    </FormGroup>
+                                  </Col>
+                                )}
+                              </Row>
+
+                              {importedFont ? (
+                                <>
+                                  <Row>
+                                    <Col md="6">
+                                      <FormGroup>
+                                        <Label>Text Size</Label>
+                                        <Input
+                                          type="select"
+                                          value={textSize ?? 'auto'}
+                                          onChange={(e) => setTextSize(e.target.value === 'auto' ? undefined : Number(e.target.value))}
+                                        >
+                                          <option value="auto">As large as fits</option>
+                                          {(importedFont.size
+                                            ? [1, 2, 3, 4].map((factor) => factor * importedFont.size!)
+                                            : [8, 10, 12, 16, 20, 24, 32]
+                                          ).map((size) => (
+                                            <option key={size} value={size}>{size}px</option>
+                                          ))}
+                                        </Input>
+                                      </FormGroup>
+                                    </Col>
+                                    <Col md="6">
+                                      <FormGroup>
+                                        <Label>Alignment</Label>
+                                        <Input
+                                          type="select"
+                                          value={textAlign}
+                                          onChange={(e) => setTextAlign(e.target.value as TextAlign)}
+                                        >
+                                          <option value="left">Left</option>
+                                          <option value="center">Center</option>
+                                          <option value="right">Right</option>
+                                        </Input>
+                                      </FormGroup>
+                                    </Col>
+                                  </Row>
+                                  <FormGroup check>
+                                    <Label check>
+                                      <Input
+                                        type="checkbox"
+                                        checked={wrapText}
+                                        onChange={(e) => setWrapText(e.target.checked)}
+                                      />
+                                      {' '}Wrap long lines
+                                    </Label>
+                                  </FormGroup>
+                                  {importedFont.scalable && (
+                                    <FormGroup check>
+                                      <Label check>
+                                        <Input
+                                          type="checkbox"
+                                          checked={antialias}
+                                          onChange={(e) => setAntialias(e.target.checked)}
+                                        />
+                                        {' '}Smooth edges (off for crisp pixels)
+                                      </Label>
+                                    </FormGroup>
+                                  )}
+                                  {textPreview && (
+                                    <div className="text-center mt-3">
+                                      <img
+                                        src={textPreview}
+                                        alt="Text preview"
+                                        style={{ width: '100%', maxWidth: '300px', maxHeight: '300px', objectFit: 'contain', imageRendering: 'pixelated' }}
+                                      />
+                                    </div>
+                                  )}
+                                  {textPreviewError && (
+                                    <p className="text-danger text-center mt-2">{textPreviewError}</p>
+                                  )}
+                                </>
+                              ) : (
+                              <>
                               <Row>
                                 <Col md="6">
                                   <FormGroup>
@@ -474,8 +826,17 @@ function App() {
                                   ))}
                                 </Input>
                               </FormGroup>
+                              </>
+                              )}
 
-                              <Button color="primary" size="lg" block onClick={handleSendText}>
+                              <Button
+                                color="primary"
+                                size="lg"
+                                block
+                                className="mt-3"
+                                disabled={!importedFont && !supports('text')}
+                                onClick={handleSendText}
+                              >
                                 Send Text
                               </Button>
                             </Form>
@@ -491,7 +852,80 @@ function App() {
                           </CardHeader>
                           <CardBody>
                             <FormGroup>
-                              <Label>Select Image (PNG, GIF, JPEG)</Label>
+                              <Label>Resize</Label>
+                              <Input
+                                type="select"
+                                value={resizeMethod}
+                                onChange={(e) => setResizeMethod(e.target.value as ResizeMethod)}
+                              >
+                                <option value="fit">Fit (show the whole image)</option>
+                                <option value="crop">Crop (fill the panel)</option>
+                                <option value="stretch">Stretch</option>
+                              </Input>
+                            </FormGroup>
+                            {resizeMethod === 'fit' && (
+                              <FormGroup>
+                                <Label>Background</Label>
+                                <Input
+                                  type="color"
+                                  value={letterboxColor}
+                                  onChange={(e) => setLetterboxColor(e.target.value)}
+                                />
+                              </FormGroup>
+                            )}
+                            <FormGroup>
+                              <Label>Scaling</Label>
+                              <Input
+                                type="select"
+                                value={resampleFilter}
+                                onChange={(e) => setResampleFilter(e.target.value as ResampleFilter)}
+                              >
+                                <option value="auto">Auto</option>
+                                <option value="nearest">Nearest (pixel art)</option>
+                                <option value="box">Box (average)</option>
+                                <option value="lanczos">Lanczos (photos)</option>
+                              </Input>
+                            </FormGroup>
+                            <FormGroup>
+                              <Label>Colors</Label>
+                              <Input
+                                type="select"
+                                value={palette}
+                                onChange={(e) => setPalette(e.target.value)}
+                              >
+                                <option value="full">All colors</option>
+                                {[2, 4, 8, 16, 32, 64].map((count) => (
+                                  <option key={count} value={count}>{count} colors</option>
+                                ))}
+                                <option value="custom">Custom palette</option>
+                              </Input>
+                            </FormGroup>
+                            {palette === 'custom' && (
+                              <FormGroup>
+                                <Label>Palette (RRGGBB, comma separated)</Label>
+                                <Input
+                                  type="text"
+                                  value={customPalette}
+                                  onChange={(e) => setCustomPalette(e.target.value)}
+                                />
+                              </FormGroup>
+                            )}
+                            <FormGroup>
+                              <Label>Dithering</Label>
+                              <Input
+                                type="select"
+                                value={palette === 'full' ? 'none' : dither}
+                                disabled={palette === 'full'}
+                                onChange={(e) => setDither(e.target.value as Dither)}
+                              >
+                                <option value="none">None</option>
+                                <option value="floyd-steinberg">Floyd-Steinberg</option>
+                                <option value="atkinson">Atkinson</option>
+                                <option value="ordered">Ordered (Bayer)</option>
+                              </Input>
+                            </FormGroup>
+                            <FormGroup>
+                              <Label>Select Image (PNG, JPEG, BMP, WebP) or Animation (GIF, APNG, WebP)</Label>
                               <Input
                                 type="file"
                                 accept="image/*"
@@ -500,9 +934,37 @@ function App() {
                             </FormGroup>
                             {imagePreview && (
                               <div className="text-center mt-3">
-                                <img src={imagePreview} alt="Preview" style={{ maxWidth: '100%', maxHeight: '300px' }} />
+                                <img
+                                  src={imagePreview}
+                                  alt="Preview"
+                                  style={{ width: '100%', maxWidth: '300px', maxHeight: '300px', objectFit: 'contain', imageRendering: 'pixelated' }}
+                                />
                               </div>
                             )}
+                            {uploadReport && (
+                              <p className="text-muted text-center mt-2">
+                                {uploadReport.duration_ms > 0 &&
+                                  `${uploadReport.frames} of ${uploadReport.source_frames} frames, ` +
+                                  `${(uploadReport.duration_ms / 1000).toFixed(1)} s loop, `}
+                                {`${(uploadReport.bytes / 1024).toFixed(1)} KiB, ` +
+                                  `~${Math.ceil(uploadReport.upload_ms / 1000)} s to upload`}
+                                {uploadReport.reduced_colors !== null &&
+                                  `, reduced to ${uploadReport.reduced_colors} colors to fit`}
+                              </p>
+                            )}
+                            {previewError && (
+                              <p className="text-danger text-center mt-2">{previewError}</p>
+                            )}
+                            <Button
+                              color="primary"
+                              size="lg"
+                              block
+                              className="mt-3"
+                              disabled={!imageFile || previewError !== null}
+                              onClick={handleSendImage}
+                            >
+                              Send Image
+                            </Button>
                           </CardBody>
                         </Card>
                       )}
@@ -806,6 +1268,69 @@ function App() {
                             )}
                           </CardBody>
                         </Card>
+
+                          {/* Color Calibration Card */}
+                          {colorProfile && calibration && (
+                            <Card>
+                              <CardHeader>
+                                <CardTitle tag="h4">Color Calibration</CardTitle>
+                              </CardHeader>
+                              <CardBody>
+                                <p className="text-muted">
+                                  {calibration.source === 'device' && 'Profile saved for this panel.'}
+                                  {calibration.source === 'led_type' && `Profile of another panel with LED type ${calibration.led_type}.`}
+                                  {calibration.source === 'default' && 'Default profile, this panel was never calibrated.'}
+                                  {' '}Applied to text, pixels, images and animations.
+                                </p>
+                                <FormGroup>
+                                  <Label>Gamma: {colorProfile.gamma.toFixed(2)}</Label>
+                                  <Input
+                                    type="range"
+                                    min="1"
+                                    max="3"
+                                    step="0.05"
+                                    value={colorProfile.gamma}
+                                    onChange={(e) => setColorProfile({ ...colorProfile, gamma: Number(e.target.value) })}
+                                  />
+                                </FormGroup>
+                                {['Red', 'Green', 'Blue'].map((name, channel) => (
+                                  <FormGroup key={name}>
+                                    <Label>{name} gain: {colorProfile.white_balance[channel][channel].toFixed(2)}</Label>
+                                    <Input
+                                      type="range"
+                                      min="0"
+                                      max="1.5"
+                                      step="0.01"
+                                      value={colorProfile.white_balance[channel][channel]}
+                                      onChange={(e) => setWhiteBalanceGain(channel, Number(e.target.value))}
+                                    />
+                                  </FormGroup>
+                                ))}
+                                <FormGroup>
+                                  <Label>Test Pattern</Label>
+                                  <Input
+                                    type="select"
+                                    value={testPattern}
+                                    onChange={(e) => setTestPattern(e.target.value as TestPattern)}
+                                  >
+                                    <option value="ramps">Ramps (gamma)</option>
+                                    <option value="color-bars">Color bars</option>
+                                    <option value="skin-tones">Skin tones</option>
+                                    <option value="white">White (white balance)</option>
+                                  </Input>
+                                </FormGroup>
+                                <Button color="info" onClick={handleSendTestPattern}>
+                                  Show Test Pattern
+                                </Button>
+                                <Button color="primary" onClick={handleSaveColorProfile}>
+                                  Save
+                                </Button>
+                                <Button color="secondary" onClick={handleResetColorProfile}>
+                                  Reset
+                                </Button>
+                              </CardBody>
+                            </Card>
+                          )}
                         </>
                       )}
                     </div>
@@ -58,9 +58,9 @@ export function AppProvider({ children }: { children: ReactNode }) {
   const [scanning, setScanning] = useState(false);
   const [status, setStatus] = useState<DeviceStatus>({ connected: false });
 
-  // Initialize WebSocket connection
+  // Follow the panel connection, relayed by the Tauri shell from the backend
   useEffect(() => {
-    api.connectWebSocket((msg) => {
+    return api.onStatusChange((msg) => {
       setStatus({
         connected: msg.connected,
         device_address: msg.device_address,
@@ -70,10 +70,6 @@ export function AppProvider({ children }: { children: ReactNode }) {
         setDeviceAddress(msg.device_address);
       }
     });
-
-    return () => {
-      api.disconnectWebSocket();
-    };
   }, []);
 
   // Scan for devices
@@ -1,8 +1,11 @@
 /**
  * API Client for LED Panel Control Backend
- * Handles all communication with the Python FastAPI backend
+ * Panel requests go through typed Tauri commands, which validate them and
+ * proxy them to the Python FastAPI backend
  */
 
+import { invoke, InvokeArgs, InvokeOptions } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import type {
   Device,
   DeviceStatus,
@@ -16,338 +19,302 @@ import type {
   RhythmSettings2,
   PowerRequest,
   ApiResponse,
-  WebSocketMessage,
-  PanelMode
+  ApiError,
+  Capability,
+  HealthResponse,
+  PanelMode,
+  ConvertOptions,
+  UploadReport,
+  ColorProfile,
+  PanelCalibration,
+  TestPattern,
+  FontInfo,
+  RenderTextRequest
 } from '../types/led-panel';
 
-class LEDPanelAPI {
-  private baseUrl: string;
-  private ws: WebSocket | null = null;
-  private wsReconnectTimer: number | null = null;
-  private statusCallbacks: ((message: WebSocketMessage) => void)[] = [];
+/**
+ * Invoke a panel command, turning its ApiError into a regular Error
+ */
+async function call<T>(command: string, args?: InvokeArgs, options?: InvokeOptions): Promise<T> {
+  try {
+    return await invoke<T>(command, args, options);
+  } catch (error) {
+    const apiError = error as ApiError;
+    throw new Error(apiError?.message ?? String(error));
+  }
+}
 
-  constructor(baseUrl: string = 'http://127.0.0.1:8000') {
-    this.baseUrl = baseUrl;
+/**
+ * Headers passing the conversion options along with raw image bytes
+ */
+function convertHeaders(options?: ConvertOptions): Record<string, string> {
+  if (!options) {
+    return {};
   }
+  const headers: Record<string, string> = { 'x-resize-method': options.method };
+  if (options.background) {
+    headers['x-background'] = options.background;
+  }
+  if (options.filter) {
+    headers['x-filter'] = options.filter;
+  }
+  if (options.palette) {
+    headers['x-palette'] = options.palette;
+  }
+  if (options.dither) {
+    headers['x-dither'] = options.dither;
+  }
+  if (options.maxBytes) {
+    headers['x-max-bytes'] = String(options.maxBytes);
+  }
+  return headers;
+}
 
+class LEDPanelAPI {
   /**
    * Check if the backend is running
    */
   async ping(): Promise<boolean> {
     try {
-      const response = await fetch(`${this.baseUrl}/`);
-      const data = await response.json();
-      return data.status === 'ok';
+      const health = await call<HealthResponse>('ping_backend');
+      return health.status === 'ok';
     } catch (error) {
       console.error('Backend ping failed:', error);
       return false;
     }
   }
 
+  /**
+   * Features of the running backend, checked by the app at startup
+   */
+  async getCapabilities(): Promise<Capability[]> {
+    return invoke<Capability[]>('get_backend_capabilities');
+  }
+
   /**
    * Scan for BLE devices (iPixel Color panels)
    */
   async scanDevices(): Promise<Device[]> {
-    const response = await fetch(`${this.baseUrl}/devices/scan`);
-    if (!response.ok) {
-      throw new Error(`Scan failed: ${response.statusText}`);
-    }
-    return response.json();
+    return call<Device[]>('scan_devices');
   }
 
   /**
    * Connect to a specific device
    */
   async connect(deviceAddress: string): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/devices/connect`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify({ address: deviceAddress })
-    });
-
-    if (!response.ok) {
-      throw new Error(`Connection failed: ${response.statusText}`);
-    }
-
-    return response.json();
+    return call<ApiResponse>('connect_device', { address: deviceAddress });
   }
 
   /**
    * Disconnect from the current device
    */
   async disconnect(): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/devices/disconnect`, {
-      method: 'POST'
-    });
-
-    if (!response.ok) {
-      throw new Error(`Disconnection failed: ${response.statusText}`);
-    }
-
-    return response.json();
+    return call<ApiResponse>('disconnect_device');
   }
 
   /**
    * Get current device status
    */
   async getStatus(): Promise<DeviceStatus> {
-    const response = await fetch(`${this.baseUrl}/devices/status`);
-    if (!response.ok) {
-      throw new Error(`Get status failed: ${response.statusText}`);
-    }
-    return response.json();
+    return call<DeviceStatus>('get_device_status');
   }
 
   /**
    * Send text to the LED panel
    */
   async sendText(request: TextRequest): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/text`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify(request)
+    return call<ApiResponse>('send_text', { request });
+  }
+
+  /**
+   * Fonts imported in the app
+   */
+  async listFonts(): Promise<FontInfo[]> {
+    return call<FontInfo[]>('list_fonts');
+  }
+
+  /**
+   * Import a BDF, PCF, TrueType or OpenType font, replacing the one of the
+   * same file name
+   */
+  async importFont(file: File): Promise<FontInfo> {
+    const data = new Uint8Array(await file.arrayBuffer());
+    return call<FontInfo>('import_font', data, {
+      headers: { 'x-file-name': file.name }
     });
+  }
 
-    if (!response.ok) {
-      const error = await response.json();
-      throw new Error(error.detail || 'Failed to send text');
-    }
+  async removeFont(name: string): Promise<void> {
+    return call<void>('remove_font', { name });
+  }
 
-    return response.json();
+  /**
+   * Text drawn in an imported font as it would be sent, as a PNG of the
+   * panel size
+   */
+  async renderText(request: RenderTextRequest): Promise<Blob> {
+    const png = await call<ArrayBuffer>('render_text', { request });
+    return new Blob([png], { type: 'image/png' });
   }
 
   /**
-   * Upload and send an image or GIF to the panel
+   * Send text drawn in an imported font, as an image
    */
-  async sendImage(file: File): Promise<ApiResponse> {
-    const formData = new FormData();
-    formData.append('file', file);
+  async sendRenderedText(request: RenderTextRequest): Promise<ApiResponse> {
+    return call<ApiResponse>('send_rendered_text', { request });
+  }
 
-    const response = await fetch(`${this.baseUrl}/panel/image`, {
-      method: 'POST',
-      body: formData
+  /**
+   * Upload and send an image or animation (GIF, APNG, animated WebP) to the
+   * panel, converted for it first
+   */
+  async sendImage(file: File, options?: ConvertOptions): Promise<ApiResponse> {
+    const data = new Uint8Array(await file.arrayBuffer());
+    return call<ApiResponse>('send_image', data, {
+      headers: { 'x-file-name': file.name, ...convertHeaders(options) }
     });
+  }
 
-    if (!response.ok) {
-      const error = await response.json();
-      throw new Error(error.detail || 'Failed to send image');
-    }
+  /**
+   * Image as it would be sent to the connected panel, as a PNG, or as a GIF
+   * for an animation
+   */
+  async convertImage(file: File, options?: ConvertOptions): Promise<Blob> {
+    const data = new Uint8Array(await file.arrayBuffer());
+    const converted = await call<ArrayBuffer>('convert_image', data, {
+      headers: convertHeaders(options)
+    });
+    const isGif = new TextDecoder().decode(converted.slice(0, 4)) === 'GIF8';
+    return new Blob([converted], { type: isGif ? 'image/gif' : 'image/png' });
+  }
 
-    return response.json();
+  /**
+   * Frames, size and upload time of an image or animation once converted
+   */
+  async estimateUpload(file: File, options?: ConvertOptions): Promise<UploadReport> {
+    const data = new Uint8Array(await file.arrayBuffer());
+    return call<UploadReport>('estimate_upload', data, {
+      headers: convertHeaders(options)
+    });
   }
 
   /**
    * Set panel mode (clock, rhythm, DIY)
    */
   async setMode(mode: PanelMode): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/mode/${mode}`, {
-      method: 'POST'
-    });
-
-    if (!response.ok) {
-      const error = await response.json();
-      throw new Error(error.detail || `Failed to set ${mode} mode`);
-    }
-
-    return response.json();
+    return call<ApiResponse>('set_panel_mode', { mode });
   }
 
   /**
    * Set panel brightness (0-100)
    */
   async setBrightness(request: BrightnessRequest): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/brightness`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify(request)
-    });
-
-    if (!response.ok) {
-      const error = await response.json();
-      throw new Error(error.detail || 'Failed to set brightness');
-    }
-
-    return response.json();
+    return call<ApiResponse>('set_brightness', { request });
   }
 
   /**
    * Set panel orientation (0-3)
    */
   async setOrientation(request: OrientationRequest): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/orientation`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify(request)
-    });
-
-    if (!response.ok) {
-      const error = await response.json();
-      throw new Error(error.detail || 'Failed to set orientation');
-    }
-
-    return response.json();
+    return call<ApiResponse>('set_orientation', { request });
   }
 
   /**
-   * Connect to WebSocket for real-time status updates
+   * Follow the panel connection status. The Tauri shell relays it from the
+   * backend and keeps the last one, which the callback gets right away.
+   * @returns Function ending the subscription
    */
-  connectWebSocket(onMessage: (message: WebSocketMessage) => void): void {
-    this.statusCallbacks.push(onMessage);
-
-    if (this.ws?.readyState === WebSocket.OPEN) {
-      return;
-    }
-
-    const wsUrl = this.baseUrl.replace('http', 'ws') + '/ws';
-
-    try {
-      this.ws = new WebSocket(wsUrl);
-
-      this.ws.onopen = () => {
-        console.log('WebSocket connected');
-        if (this.wsReconnectTimer) {
-          clearTimeout(this.wsReconnectTimer);
-          this.wsReconnectTimer = null;
-        }
-      };
-
-      this.ws.onmessage = (event) => {
-        try {
-          const message: WebSocketMessage = JSON.parse(event.data);
-          this.statusCallbacks.forEach(callback => callback(message));
-        } catch (error) {
-          console.error('Failed to parse WebSocket message:', error);
+  onStatusChange(callback: (status: DeviceStatus) => void): () => void {
+    let active = true;
+    let received = false;
+    const unlisten = listen<DeviceStatus>('panel://status', (event) => {
+      received = true;
+      callback(event.payload);
+    });
+    invoke<DeviceStatus>('get_connection_state')
+      .then((status) => {
+        // An event may already have brought a newer status
+        if (active && !received) {
+          callback(status);
         }
-      };
-
-      this.ws.onerror = (error) => {
-        console.error('WebSocket error:', error);
-      };
-
-      this.ws.onclose = () => {
-        console.log('WebSocket disconnected');
-        // Try to reconnect after 3 seconds
-        this.wsReconnectTimer = setTimeout(() => {
-          this.connectWebSocket(onMessage);
-        }, 3000) as unknown as number;
-      };
-    } catch (error) {
-      console.error('Failed to create WebSocket:', error);
-    }
-  }
-
-  /**
-   * Disconnect WebSocket
-   */
-  disconnectWebSocket(): void {
-    if (this.wsReconnectTimer) {
-      clearTimeout(this.wsReconnectTimer);
-      this.wsReconnectTimer = null;
-    }
+      })
+      .catch((error) => console.error('Failed to get the connection state:', error));
 
-    if (this.ws) {
-      this.ws.close();
-      this.ws = null;
-    }
-
-    this.statusCallbacks = [];
+    return () => {
+      active = false;
+      unlisten.then((stop) => stop());
+    };
   }
 
   /**
    * Get device information (dimensions, type, etc.)
    */
   async getDeviceInfo(): Promise<DeviceInfo> {
-    const response = await fetch(`${this.baseUrl}/panel/device-info`);
-    if (!response.ok) {
-      throw new Error('Failed to get device info');
-    }
-    return response.json();
+    return call<DeviceInfo>('get_device_info');
   }
 
   /**
    * Send pixel art (multiple pixels at once)
    */
   async sendPixels(pixels: PixelData[]): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/pixels`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify({ pixels })
-    });
-    if (!response.ok) {
-      throw new Error('Failed to send pixels');
-    }
-    return response.json();
+    return call<ApiResponse>('send_pixels', { pixels });
   }
 
   /**
    * Set clock mode with style options
    */
   async setClockMode(settings: ClockSettings): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/mode/clock`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify(settings)
-    });
-    if (!response.ok) {
-      throw new Error('Failed to set clock mode');
-    }
-    return response.json();
+    return call<ApiResponse>('set_clock_mode', { settings });
   }
 
   /**
    * Set rhythm/beat mode v1 (11 level controls)
    */
   async setRhythmMode(settings: RhythmSettings): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/mode/rhythm`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify(settings)
-    });
-    if (!response.ok) {
-      throw new Error('Failed to set rhythm mode');
-    }
-    return response.json();
+    return call<ApiResponse>('set_rhythm_mode', { settings });
   }
 
   /**
    * Set rhythm/beat mode v2 (alternative version)
    */
   async setRhythmMode2(settings: RhythmSettings2): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/mode/rhythm2`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify(settings)
-    });
-    if (!response.ok) {
-      throw new Error('Failed to set rhythm mode 2');
-    }
-    return response.json();
+    return call<ApiResponse>('set_rhythm_mode_2', { settings });
   }
 
   /**
    * Set power (on/off)
    */
   async setPower(request: PowerRequest): Promise<ApiResponse> {
-    const response = await fetch(`${this.baseUrl}/panel/power`, {
-      method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify(request)
-    });
-    if (!response.ok) {
-      const error = await response.json();
-      throw new Error(error.detail || 'Failed to set power');
-    }
-    return response.json();
+    return call<ApiResponse>('set_power', { request });
   }
 
   /**
-   * Remove a status callback
+   * Color profile of the connected panel
    */
-  removeStatusCallback(callback: (message: WebSocketMessage) => void): void {
-    const index = this.statusCallbacks.indexOf(callback);
-    if (index > -1) {
-      this.statusCallbacks.splice(index, 1);
-    }
+  async getColorProfile(): Promise<PanelCalibration> {
+    return call<PanelCalibration>('get_color_profile');
+  }
+
+  /**
+   * Save the color profile of the connected panel, applied to everything sent
+   * to it from now on
+   */
+  async saveColorProfile(profile: ColorProfile): Promise<PanelCalibration> {
+    return call<PanelCalibration>('save_color_profile', { profile });
+  }
+
+  /**
+   * Go back to the default color profile for the connected panel
+   */
+  async resetColorProfile(): Promise<PanelCalibration> {
+    return call<PanelCalibration>('reset_color_profile');
+  }
+
+  /**
+   * Show a test pattern corrected with a profile that doesn't need to be saved
+   */
+  async sendTestPattern(pattern: TestPattern, profile: ColorProfile): Promise<ApiResponse> {
+    return call<ApiResponse>('send_test_pattern', { pattern, profile });
   }
 }
 
@@ -0,0 +1,59 @@
+/**
+ * Splash window shown while the Tauri shell starts the backend
+ * Displays startup progress and an error screen if the backend fails to start
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+type StartupStatus =
+  | { state: 'starting'; attempt: number; max_attempts: number }
+  | { state: 'ready' }
+  | { state: 'failed'; kind: string; message: string; hint: string };
+
+const loading = document.getElementById('loading') as HTMLDivElement;
+const progressBar = document.getElementById('progress-bar') as HTMLDivElement;
+const message = document.getElementById('message') as HTMLDivElement;
+const error = document.getElementById('error') as HTMLDivElement;
+const errorMessage = document.getElementById('error-message') as HTMLParagraphElement;
+const errorHint = document.getElementById('error-hint') as HTMLParagraphElement;
+
+function render(status: StartupStatus): void {
+  switch (status.state) {
+    case 'starting':
+      loading.style.display = 'block';
+      error.style.display = 'none';
+      if (status.max_attempts > 0) {
+        progressBar.style.width = `${(status.attempt / status.max_attempts) * 100}%`;
+        message.textContent = `Waiting for backend (attempt ${status.attempt}/${status.max_attempts})...`;
+      } else {
+        progressBar.style.width = '0';
+        message.textContent = 'Starting backend...';
+      }
+      break;
+    case 'ready':
+      progressBar.style.width = '100%';
+      message.textContent = 'Backend ready';
+      break;
+    case 'failed':
+      loading.style.display = 'none';
+      error.style.display = 'block';
+      errorMessage.textContent = status.message;
+      errorHint.textContent = status.hint;
+      break;
+  }
+}
+
+listen<StartupStatus>('backend://startup', (event) => render(event.payload));
+
+// Catch up with events emitted before this page was loaded
+invoke<StartupStatus>('get_startup_status').then(render);
+
+document.getElementById('retry')?.addEventListener('click', () => {
+  render({ state: 'starting', attempt: 0, max_attempts: 0 });
+  invoke('retry_backend_startup');
+});
+
+document.getElementById('quit')?.addEventListener('click', () => {
+  invoke('quit_app');
+});
@@ -65,17 +65,120 @@ export interface PowerRequest {
   on: boolean;
 }
 
-export type ResizeMethod = "crop" | "fit";
+export type ResizeMethod = "crop" | "fit" | "stretch";
+
+export type ResampleFilter = "auto" | "nearest" | "box" | "lanczos";
+
+export type Dither = "none" | "floyd-steinberg" | "atkinson" | "ordered";
+
+/**
+ * How an image is converted for the panel before it is sent
+ */
+export interface ConvertOptions {
+  method: ResizeMethod;
+  background?: string; // RRGGBB, color of the "fit" bars and of transparent areas
+  filter?: ResampleFilter;
+  palette?: string; // "full", a number of colors (2-256) or "RRGGBB,RRGGBB,..."
+  dither?: Dither; // needs a palette
+  maxBytes?: number; // animations are reduced to fit, 64 KiB by default
+}
+
+/**
+ * What converting an image or animation produced, before it is sent
+ */
+export interface UploadReport {
+  source_frames: number;
+  frames: number;
+  reduced_colors: number | null;
+  bytes: number;
+  upload_ms: number;
+  duration_ms: number; // one loop of the animation, 0 for a still image
+}
 
 export interface ApiResponse {
   status: string;
   [key: string]: any;
 }
 
-export interface WebSocketMessage {
-  type: string;
-  connected: boolean;
-  device_address?: string;
+/**
+ * Feature announced by the backend in its health check
+ */
+export type Capability =
+  | 'text'
+  | 'image'
+  | 'gif'
+  | 'brightness'
+  | 'orientation'
+  | 'device_info'
+  | 'clock_mode'
+  | 'rhythm_mode'
+  | 'rhythm_mode2'
+  | 'diy_mode'
+  | 'pixel_batch'
+  | 'power'
+  | 'shutdown'
+  | 'status_websocket'
+  | 'unknown';
+
+export interface HealthResponse {
+  status: string;
+  message?: string;
+  version?: string;
+  api_version?: number;
+  capabilities: Capability[];
+}
+
+/**
+ * Error returned by the panel Tauri commands
+ */
+export interface ApiError {
+  kind: 'invalid' | 'not_ready' | 'unreachable' | 'timeout' | 'not_connected' | 'backend' | 'invalid_response' | 'unsupported' | 'storage';
+  message: string;
 }
 
 export type PanelMode = 'clock' | 'rhythm' | 'diy';
+
+/**
+ * Color correction applied to everything sent to a panel
+ */
+export interface ColorProfile {
+  gamma: number; // 1.0-3.0, 1 sends colors unchanged
+  white_balance: [number, number, number][]; // 3x3, one row per LED channel
+}
+
+/**
+ * Color profile of the connected panel and where it comes from
+ */
+export interface PanelCalibration {
+  address: string;
+  led_type: number;
+  profile: ColorProfile;
+  source: 'device' | 'led_type' | 'default';
+}
+
+export type TestPattern = 'ramps' | 'color-bars' | 'skin-tones' | 'white';
+
+/**
+ * A font imported in the app, to draw text with instead of the panel fonts
+ */
+export interface FontInfo {
+  name: string;
+  scalable: boolean; // TrueType or OpenType, drawn at any size
+  size?: number; // Line height of bitmap fonts
+}
+
+export type TextAlign = 'left' | 'center' | 'right';
+
+/**
+ * Text drawn in an imported font and sent as an image
+ */
+export interface RenderTextRequest {
+  text: string;
+  font: string; // Name of an imported font
+  size?: number; // Line height in pixels, as large as fits by default
+  antialias?: boolean;
+  color?: string; // Hex format "RRGGBB"
+  background?: string;
+  align?: TextAlign;
+  wrap?: boolean;
+}